@@ -0,0 +1,29 @@
+//! Per-call cost of text preprocessing, which runs once per queued chunk. Compare runs
+//! with `cargo bench --bench preprocess -- --save-baseline <name>` and `--baseline <name>`.
+
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use test_tonic_mob_lib::bench::{preprocess_text, split_sentences, strip_markdown, PreprocessOptions};
+
+const SENTENCE: &str = "Dr. Smith arrived at 3:45 p.m. — right on time 🎉 for the 2nd meeting.";
+const MARKDOWN: &str = "## Notes\n\n- **First** item with `code`\n- See [the docs](https://example.com).\n\n> Quoted _text_.";
+
+fn preprocess(c: &mut Criterion) {
+    let lexicon = HashMap::new();
+    let options = PreprocessOptions::default();
+    c.bench_function("preprocess_text", |b| {
+        b.iter(|| preprocess_text(black_box(SENTENCE), "en", &lexicon, &options).unwrap())
+    });
+
+    let markdown = PreprocessOptions { strip_markdown: true, ..PreprocessOptions::default() };
+    c.bench_function("preprocess_text markdown", |b| {
+        b.iter(|| preprocess_text(black_box(MARKDOWN), "en", &lexicon, &markdown).unwrap())
+    });
+
+    c.bench_function("strip_markdown", |b| b.iter(|| strip_markdown(black_box(MARKDOWN))));
+    c.bench_function("split_sentences", |b| b.iter(|| split_sentences(black_box(SENTENCE))));
+}
+
+criterion_group!(benches, preprocess);
+criterion_main!(benches);
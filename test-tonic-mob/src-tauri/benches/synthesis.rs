@@ -0,0 +1,29 @@
+//! Synthesis of one short chunk on a fixed seed, the case where per-call allocations in
+//! `_infer` show up. Needs the model files, like the ignored tests: point `SUPERTONIC_ASSETS`
+//! at a directory with `onnx/` and `voice_styles/`; without it the bench is skipped.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use test_tonic_mob_lib::bench::{load_text_to_speech, load_voice_style_from_bytes, ChunkSilence, LoadOptions};
+
+const TEXT: &str = "The quick brown fox jumps over the lazy dog.";
+
+fn synthesis(c: &mut Criterion) {
+    let Ok(assets) = std::env::var("SUPERTONIC_ASSETS") else {
+        eprintln!("SUPERTONIC_ASSETS is not set; skipping the synthesis bench");
+        return;
+    };
+    let mut engine = load_text_to_speech(&format!("{}/onnx", assets), false, &LoadOptions::default()).unwrap();
+    let style = load_voice_style_from_bytes(&std::fs::read(format!("{}/voice_styles/F1.json", assets)).unwrap()).unwrap();
+    engine.set_seed(Some(0));
+
+    // The same seed must give the same samples, so a faster build can be checked against a slower one
+    let silence = ChunkSilence::uniform(0.3);
+    let mut synthesize = || engine.call(black_box(TEXT), "en", &style, 5, 1.05, silence).unwrap().0;
+    let first = synthesize();
+    assert_eq!(first, synthesize(), "seeded synthesis should be repeatable");
+
+    c.bench_function("synthesize short chunk, 5 steps", |b| b.iter(&mut synthesize));
+}
+
+criterion_group!(benches, synthesis);
+criterion_main!(benches);
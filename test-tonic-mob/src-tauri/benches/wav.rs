@@ -0,0 +1,24 @@
+//! WAV encoding of a long utterance, which runs over every sample of an audiobook export
+//! and every `synthesize` response.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use test_tonic_mob_lib::bench::encode_wav_to_bytes;
+
+const SAMPLE_RATE: i32 = 44_100;
+
+fn wav(c: &mut Criterion) {
+    // 60 seconds of a full-scale sweep, with some samples past the clamp
+    let audio: Vec<f32> = (0..SAMPLE_RATE as usize * 60)
+        .map(|i| (i as f32 * 0.001).sin() * 1.1)
+        .collect();
+
+    c.bench_function("encode_wav_to_bytes 60s 16-bit", |b| {
+        b.iter(|| encode_wav_to_bytes(black_box(&audio), SAMPLE_RATE, 16, 1).unwrap())
+    });
+    c.bench_function("encode_wav_to_bytes 60s 16-bit stereo", |b| {
+        b.iter(|| encode_wav_to_bytes(black_box(&audio), SAMPLE_RATE, 16, 2).unwrap())
+    });
+}
+
+criterion_group!(benches, wav);
+criterion_main!(benches);
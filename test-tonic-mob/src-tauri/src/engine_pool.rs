@@ -0,0 +1,495 @@
+// ============================================================================
+// Engine Pool - Several TTS engines for concurrent synthesis
+// ============================================================================
+//
+// ORT sessions need `&mut` to run, so each concurrent synthesis needs its own
+// `TextToSpeech`. The pool hands out idle engines and, while below capacity,
+// loads another one on demand; otherwise callers wait for one to be returned.
+//
+
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use crate::tts_helper::{
+    chunk_text_tagged, concat_chunks_with_gaps, preprocess_text, ChunkConfig, ChunkSilence, ChunkTiming, Config,
+    EmptyAfterNormalization, PreprocessOptions, SpeakingRate, StepSchedule, Style, SynthesisMetrics, TextToSpeech,
+    UnicodeProcessor,
+};
+
+/// Creates a fresh engine from the same models as the rest of the pool
+pub type EngineLoader = Box<dyn Fn() -> Result<TextToSpeech, String> + Send + Sync>;
+
+/// A chunk's `(text, samples, duration)`
+pub type SynthesizedChunk = (String, Vec<f32>, f32);
+
+pub struct EnginePool {
+    loader: EngineLoader,
+    /// Configuration shared by every engine in the pool
+    config: Config,
+    /// Indexer and lexicon shared by every engine in the pool, for text checks that
+    /// shouldn't wait for an engine
+    text_processor: UnicodeProcessor,
+    state: Mutex<PoolState>,
+    returned: Condvar,
+}
+
+struct PoolState {
+    idle: Vec<TextToSpeech>,
+    /// Engines alive, whether idle, checked out or still loading
+    size: usize,
+    /// Maximum number of engines, i.e. concurrent syntheses
+    capacity: usize,
+    /// Applied to every engine as it's checked out
+    options: PreprocessOptions,
+    step_schedule: StepSchedule,
+    max_duration_seconds: Option<f32>,
+    /// First output length warning any engine reported, see `check_output_length`
+    output_length_warning: Option<String>,
+}
+
+/// An engine checked out of the pool; returned to it on drop
+pub struct PooledEngine<'a> {
+    pool: &'a EnginePool,
+    engine: Option<TextToSpeech>,
+}
+
+impl EnginePool {
+    /// Create a pool around an already-loaded engine; more are loaded with `loader` as needed
+    pub fn new(engine: TextToSpeech, loader: EngineLoader, capacity: usize) -> Self {
+        EnginePool {
+            loader,
+            config: engine.config().clone(),
+            text_processor: engine.text_processor().clone(),
+            state: Mutex::new(PoolState {
+                idle: vec![engine],
+                size: 1,
+                capacity: capacity.max(1),
+                options: PreprocessOptions::default(),
+                step_schedule: StepSchedule::default(),
+                max_duration_seconds: None,
+                output_length_warning: None,
+            }),
+            returned: Condvar::new(),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Output sample rate shared by every engine in the pool
+    pub fn sample_rate(&self) -> i32 {
+        self.config.ae.sample_rate
+    }
+
+    fn lock_state(&self) -> Result<MutexGuard<'_, PoolState>, String> {
+        self.state.lock().map_err(|e| format!("Lock error: {}", e))
+    }
+
+    /// Check out an engine, loading a new one if all are busy and the pool has room,
+    /// or waiting for one to be returned otherwise
+    pub fn acquire(&self) -> Result<PooledEngine<'_>, String> {
+        let mut state = self.lock_state()?;
+
+        loop {
+            if let Some(mut engine) = state.idle.pop() {
+                engine.set_preprocess_options(state.options.clone());
+                engine.set_step_schedule(state.step_schedule.clone());
+                engine.set_max_duration_seconds(state.max_duration_seconds);
+                return Ok(PooledEngine { pool: self, engine: Some(engine) });
+            }
+
+            if state.size < state.capacity {
+                state.size += 1;
+                drop(state);
+                return self.load_engine();
+            }
+
+            state = self.returned
+                .wait(state)
+                .map_err(|e| format!("Lock error: {}", e))?;
+        }
+    }
+
+    /// Load an extra engine for a slot already counted in `size`
+    fn load_engine(&self) -> Result<PooledEngine<'_>, String> {
+        match (self.loader)() {
+            Ok(mut engine) => {
+                let state = self.lock_state()?;
+                engine.set_preprocess_options(state.options.clone());
+                engine.set_step_schedule(state.step_schedule.clone());
+                engine.set_max_duration_seconds(state.max_duration_seconds);
+                drop(state);
+                Ok(PooledEngine { pool: self, engine: Some(engine) })
+            }
+            Err(e) => {
+                if let Ok(mut state) = self.state.lock() {
+                    state.size -= 1;
+                }
+                self.returned.notify_all();
+                Err(e)
+            }
+        }
+    }
+
+    fn release(&self, engine: TextToSpeech) {
+        if let Ok(mut state) = self.state.lock() {
+            if state.output_length_warning.is_none() {
+                state.output_length_warning = engine.output_length_warning().map(str::to_string);
+            }
+            if state.size > state.capacity {
+                // Pool was shrunk while this engine was busy
+                state.size -= 1;
+            } else {
+                state.idle.push(engine);
+            }
+        }
+        self.returned.notify_all();
+    }
+
+    /// Change how many engines may synthesize at once. Extra engines load lazily on demand;
+    /// surplus idle ones are dropped right away and busy ones once they're returned.
+    pub fn set_capacity(&self, capacity: usize) -> Result<(), String> {
+        let mut state = self.lock_state()?;
+        state.capacity = capacity.max(1);
+        while state.size > state.capacity && state.idle.pop().is_some() {
+            state.size -= 1;
+        }
+        drop(state);
+
+        self.returned.notify_all();
+        Ok(())
+    }
+
+    /// Most engines the pool will hold at once
+    pub fn capacity(&self) -> Result<usize, String> {
+        Ok(self.lock_state()?.capacity)
+    }
+
+    /// Like `TextToSpeech::call_with_timings`, but synthesizes chunks on up to `capacity`
+    /// engines at once and reassembles them in order. Capped by the pool's capacity,
+    /// so `set_synthesis_concurrency` decides how many cores a long text may use.
+    pub fn call_parallel(
+        &self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        rate: SpeakingRate,
+        silence: ChunkSilence,
+    ) -> Result<(Vec<f32>, f32, Vec<ChunkTiming>), String> {
+        let (chunks, starts_paragraph): (Vec<String>, Vec<bool>) =
+            chunk_text_tagged(text, &ChunkConfig::for_language(lang)).into_iter().unzip();
+        let (synthesized, _) = self.synthesize_chunks(chunks, lang, style, total_step, rate, false, &mut |_| {})?;
+        let gaps = silence.with_options(&self.preprocess_options()?).gaps(&starts_paragraph);
+        Ok(concat_chunks_with_gaps(synthesized, &gaps, self.sample_rate()))
+    }
+
+    /// Synthesize already-split chunks in parallel as `call_parallel` does, returning each
+    /// chunk's `(text, samples, duration)` in order without joining them, plus the model
+    /// run times summed over every engine used; the times are only measured when `collect_metrics`
+    /// is set (see `TextToSpeech::set_collect_metrics`).
+    /// `on_chunk_done` gets each chunk's duration as it finishes, in completion order. It runs
+    /// on the calling thread with no pool locks held.
+    #[allow(clippy::too_many_arguments)]
+    pub fn synthesize_chunks(
+        &self,
+        chunks: Vec<String>,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        rate: SpeakingRate,
+        collect_metrics: bool,
+        on_chunk_done: &mut dyn FnMut(f32),
+    ) -> Result<(Vec<SynthesizedChunk>, SynthesisMetrics), String> {
+        let workers = self.capacity()?.min(chunks.len()).max(1);
+
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let results = Mutex::new(vec![None::<(Vec<f32>, f32)>; chunks.len()]);
+        let metrics = Mutex::new(SynthesisMetrics::default());
+
+        // Each worker checks out one engine and keeps taking the next unclaimed chunk,
+        // reporting finished chunks' durations through `done`
+        let work = |done: &mut dyn FnMut(f32)| -> Result<(), String> {
+            let mut engine = self.acquire()?;
+            // Drop times left over from whoever used this engine before
+            engine.take_metrics();
+            engine.set_collect_metrics(collect_metrics);
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= chunks.len() || failed.load(Ordering::Relaxed) {
+                    *metrics.lock().map_err(|e| format!("Lock error: {}", e))? += engine.take_metrics();
+                    return Ok(());
+                }
+
+                // A chunk with nothing to speak contributes no audio, keeping the rest in place
+                let synthesized = match engine.synthesize_chunk(&chunks[i], lang, style, total_step, rate) {
+                    Err(e) if e.is::<EmptyAfterNormalization>() => {
+                        log::warn!("Skipping chunk {}: nothing to speak in {:?}", i + 1, chunks[i]);
+                        Ok((Vec::new(), 0.0))
+                    }
+                    result => result,
+                };
+                match synthesized {
+                    Ok(chunk) => {
+                        let duration = chunk.1;
+                        results.lock().map_err(|e| format!("Lock error: {}", e))?[i] = Some(chunk);
+                        done(duration);
+                    }
+                    Err(e) => {
+                        failed.store(true, Ordering::Relaxed);
+                        return Err(format!("Synthesis failed: {}", e));
+                    }
+                }
+            }
+        };
+
+        if workers == 1 {
+            work(on_chunk_done)?;
+        } else {
+            // Workers send durations back here so `on_chunk_done` runs on this thread only
+            let (sender, receiver) = std::sync::mpsc::channel();
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..workers)
+                    .map(|_| {
+                        let sender = sender.clone();
+                        let work = &work;
+                        scope.spawn(move || work(&mut |duration| {
+                            let _ = sender.send(duration);
+                        }))
+                    })
+                    .collect();
+                // Ends once every worker has finished and dropped its sender
+                drop(sender);
+                for duration in receiver {
+                    on_chunk_done(duration);
+                }
+                handles
+                    .into_iter()
+                    .map(|h| h.join().unwrap_or_else(|_| Err("Synthesis worker panicked".to_string())))
+                    .collect::<Result<Vec<()>, String>>()
+            })?;
+        }
+
+        let results = results.into_inner().map_err(|e| format!("Lock error: {}", e))?;
+        let synthesized = chunks
+            .into_iter()
+            .zip(results)
+            .map(|(text, result)| {
+                let (wav, dur) = result.ok_or("Chunk was not synthesized")?;
+                Ok((text, wav, dur))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok((synthesized, metrics.into_inner().map_err(|e| format!("Lock error: {}", e))?))
+    }
+
+    /// Indexer and lexicon shared by every engine in the pool
+    pub fn text_processor(&self) -> &UnicodeProcessor {
+        &self.text_processor
+    }
+
+    /// Normalize `text` exactly as synthesis would, without checking out an engine
+    pub fn normalize_text(&self, text: &str, lang: &str) -> Result<String, String> {
+        let options = self.preprocess_options()?;
+        preprocess_text(text, lang, &self.text_processor.lexicon, &options).map_err(|e| e.to_string())
+    }
+
+    /// Characters in `text` the model has no token for (after normalization), each listed once
+    pub fn oov_chars(&self, text: &str, lang: &str) -> Result<Vec<char>, String> {
+        Ok(self.text_processor.oov_chars(&self.normalize_text(text, lang)?))
+    }
+
+    pub fn preprocess_options(&self) -> Result<PreprocessOptions, String> {
+        Ok(self.lock_state()?.options.clone())
+    }
+
+    pub fn set_preprocess_options(&self, options: PreprocessOptions) -> Result<(), String> {
+        self.lock_state()?.options = options;
+        Ok(())
+    }
+
+    pub fn step_schedule(&self) -> Result<StepSchedule, String> {
+        Ok(self.lock_state()?.step_schedule.clone())
+    }
+
+    pub fn set_step_schedule(&self, schedule: StepSchedule) -> Result<(), String> {
+        self.lock_state()?.step_schedule = schedule;
+        Ok(())
+    }
+
+    pub fn max_duration_seconds(&self) -> Result<Option<f32>, String> {
+        Ok(self.lock_state()?.max_duration_seconds)
+    }
+
+    pub fn set_max_duration_seconds(&self, max_duration_seconds: Option<f32>) -> Result<(), String> {
+        self.lock_state()?.max_duration_seconds = max_duration_seconds;
+        Ok(())
+    }
+
+    /// Set once an engine's first synthesis found `tts.json`'s sample rate at odds with the models
+    pub fn output_length_warning(&self) -> Result<Option<String>, String> {
+        Ok(self.lock_state()?.output_length_warning.clone())
+    }
+
+    /// Block until every engine has been returned, so no synthesis is in flight
+    pub fn wait_until_idle(&self) -> Result<(), String> {
+        let mut state = self.lock_state()?;
+        while state.idle.len() < state.size {
+            state = self.returned
+                .wait(state)
+                .map_err(|e| format!("Lock error: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Deref for PooledEngine<'_> {
+    type Target = TextToSpeech;
+
+    fn deref(&self) -> &TextToSpeech {
+        self.engine.as_ref().expect("engine present until drop")
+    }
+}
+
+impl DerefMut for PooledEngine<'_> {
+    fn deref_mut(&mut self) -> &mut TextToSpeech {
+        self.engine.as_mut().expect("engine present until drop")
+    }
+}
+
+impl Drop for PooledEngine<'_> {
+    fn drop(&mut self) {
+        if let Some(engine) = self.engine.take() {
+            self.pool.release(engine);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tts_helper::stub;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A pool on stub models that counts how many extra engines it loaded
+    fn stub_pool(capacity: usize) -> (EnginePool, Arc<AtomicUsize>) {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let counter = loads.clone();
+        let loader: EngineLoader = Box::new(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(stub::engine())
+        });
+        (EnginePool::new(stub::engine(), loader, capacity), loads)
+    }
+
+    fn size(pool: &EnginePool) -> usize {
+        pool.lock_state().unwrap().size
+    }
+
+    #[test]
+    fn acquire_loads_up_to_capacity_then_waits_for_a_return() {
+        let (pool, loads) = stub_pool(2);
+        let first = pool.acquire().unwrap();
+        let second = pool.acquire().unwrap();
+        assert_eq!(loads.load(Ordering::Relaxed), 1);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let third = pool.acquire().map(|_| ());
+                sender.send(third).unwrap();
+            });
+            assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err(), "third acquire didn't wait");
+
+            drop(first);
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        });
+        drop(second);
+
+        // The third checkout reused the returned engine rather than loading another
+        assert_eq!(loads.load(Ordering::Relaxed), 1);
+        assert_eq!(size(&pool), 2);
+    }
+
+    #[test]
+    fn shrinking_drops_busy_engines_as_they_are_returned() {
+        let (pool, _) = stub_pool(3);
+        let engines: Vec<_> = (0..3).map(|_| pool.acquire().unwrap()).collect();
+
+        pool.set_capacity(1).unwrap();
+        assert_eq!(pool.capacity().unwrap(), 1);
+        // Every engine is busy, so none can go yet
+        assert_eq!(size(&pool), 3);
+
+        let mut engines = engines.into_iter();
+        drop(engines.next());
+        assert_eq!(size(&pool), 2);
+        drop(engines.next());
+        assert_eq!(size(&pool), 1);
+        drop(engines.next());
+        assert_eq!(size(&pool), 1);
+
+        pool.wait_until_idle().unwrap();
+        assert_eq!(pool.lock_state().unwrap().idle.len(), 1);
+    }
+
+    #[test]
+    fn parallel_chunks_come_back_in_order() {
+        let chunks: Vec<String> = ["One.", "Number two.", "The third one.", "Four!", "And the fifth chunk."]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let rate = SpeakingRate::Speed(1.0);
+
+        let (serial_pool, _) = stub_pool(1);
+        let (serial, _) = serial_pool
+            .synthesize_chunks(chunks.clone(), "en", &stub::style(), 2, rate, false, &mut |_| {})
+            .unwrap();
+
+        // Three workers share the chunks, whether or not each ends up loading its own engine
+        let (pool, _) = stub_pool(3);
+        let mut done = 0;
+        let (parallel, _) = pool
+            .synthesize_chunks(chunks.clone(), "en", &stub::style(), 2, rate, false, &mut |_| done += 1)
+            .unwrap();
+
+        assert_eq!(done, chunks.len());
+        let texts: Vec<&str> = parallel.iter().map(|(text, _, _)| text.as_str()).collect();
+        assert_eq!(texts, chunks);
+        for ((_, wav, duration), (_, serial_wav, serial_duration)) in parallel.iter().zip(&serial) {
+            assert_eq!(duration, serial_duration);
+            assert_eq!(wav, serial_wav);
+        }
+        // Each chunk's length follows its own text
+        let lengths: Vec<usize> = parallel.iter().map(|(_, wav, _)| wav.len()).collect();
+        assert!(lengths[0] < lengths[1] && lengths[1] < lengths[2] && lengths[3] < lengths[4], "{:?}", lengths);
+    }
+
+    #[test]
+    fn parallel_call_matches_a_sequential_call_sample_for_sample() {
+        let seeded = || {
+            let mut engine = stub::engine();
+            engine.set_seed(Some(7));
+            engine
+        };
+        let text = "One.\n\nNumber two. The third one.\n\nFour!\n\nAnd the fifth chunk, which is longer.";
+        let rate = SpeakingRate::Speed(1.0);
+        let silence = ChunkSilence { sentence: 0.2, paragraph: 0.5, samples: None };
+
+        let (serial, serial_duration, serial_timings) =
+            seeded().call_with_timings(text, "en", &stub::style(), 2, rate, silence).unwrap();
+        let pool = EnginePool::new(seeded(), Box::new(move || Ok(seeded())), 3);
+        let (parallel, duration, timings) = pool.call_parallel(text, "en", &stub::style(), 2, rate, silence).unwrap();
+
+        assert!(serial_timings.len() > 2);
+        assert_eq!(parallel, serial);
+        assert_eq!(duration, serial_duration);
+        let spans = |timings: &[ChunkTiming]| -> Vec<(String, f32, f32)> {
+            timings.iter().map(|t| (t.text.clone(), t.start, t.end)).collect()
+        };
+        assert_eq!(spans(&timings), spans(&serial_timings));
+    }
+}
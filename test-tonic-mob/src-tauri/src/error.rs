@@ -0,0 +1,112 @@
+// ============================================================================
+// Command Errors - Typed errors returned to the frontend
+// ============================================================================
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use thiserror::Error;
+
+use crate::tts_helper::AVAILABLE_LANGS;
+
+/// Error returned by Tauri commands. Serializes as `{ kind, message, ... }` so the frontend
+/// can branch on `kind` and still show `message`; variants with data add a field for it.
+#[derive(Debug, Error)]
+pub enum TtsError {
+    #[error("TTS engine not initialized. Still loading or initialization failed.")]
+    NotInitialized,
+    #[error("{count} model files missing: {files}", count = .0.len(), files = .0.join(", "))]
+    ModelsMissing(Vec<String>),
+    #[error("Voice {0} not available")]
+    VoiceNotFound(String),
+    #[error("Invalid language {0}; available: {langs}", langs = AVAILABLE_LANGS.join(", "))]
+    InvalidLanguage(String),
+    /// A request parameter is out of range
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Synthesis failed: {0}")]
+    Synthesis(String),
+    /// Model loading didn't finish within the requested number of seconds
+    #[error("Model loading did not finish within {0}s")]
+    LoadTimeout(u64),
+    /// Stopped early at the caller's request
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("{0}")]
+    Io(String),
+}
+
+impl TtsError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TtsError::NotInitialized => "NotInitialized",
+            TtsError::ModelsMissing(_) => "ModelsMissing",
+            TtsError::VoiceNotFound(_) => "VoiceNotFound",
+            TtsError::InvalidLanguage(_) => "InvalidLanguage",
+            TtsError::InvalidRequest(_) => "InvalidRequest",
+            TtsError::Synthesis(_) => "Synthesis",
+            TtsError::LoadTimeout(_) => "LoadTimeout",
+            TtsError::Cancelled => "Cancelled",
+            TtsError::Io(_) => "Io",
+        }
+    }
+}
+
+impl Serialize for TtsError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("kind", self.kind())?;
+        map.serialize_entry("message", &self.to_string())?;
+        match self {
+            TtsError::ModelsMissing(files) => map.serialize_entry("files", files)?,
+            TtsError::VoiceNotFound(voice) => map.serialize_entry("voice", voice)?,
+            TtsError::InvalidLanguage(language) => map.serialize_entry("language", language)?,
+            TtsError::LoadTimeout(secs) => map.serialize_entry("timeout_secs", secs)?,
+            _ => {}
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serialized(err: TtsError) -> serde_json::Value {
+        serde_json::to_value(err).unwrap()
+    }
+
+    #[test]
+    fn each_variant_serializes_its_kind_and_data() {
+        let json = serialized(TtsError::NotInitialized);
+        assert_eq!(json["kind"], "NotInitialized");
+
+        let json = serialized(TtsError::ModelsMissing(vec!["onnx/vocoder.onnx".to_string()]));
+        assert_eq!(json["kind"], "ModelsMissing");
+        assert_eq!(json["files"], serde_json::json!(["onnx/vocoder.onnx"]));
+
+        let json = serialized(TtsError::VoiceNotFound("M9".to_string()));
+        assert_eq!(json["kind"], "VoiceNotFound");
+        assert_eq!(json["voice"], "M9");
+
+        let json = serialized(TtsError::InvalidLanguage("xx".to_string()));
+        assert_eq!(json["kind"], "InvalidLanguage");
+        assert_eq!(json["language"], "xx");
+
+        let json = serialized(TtsError::InvalidRequest("No model source configured".to_string()));
+        assert_eq!(json["kind"], "InvalidRequest");
+        assert_eq!(json["message"], "Invalid request: No model source configured");
+
+        let json = serialized(TtsError::Synthesis("vocoder failed".to_string()));
+        assert_eq!(json["kind"], "Synthesis");
+        assert_eq!(json["message"], "Synthesis failed: vocoder failed");
+
+        let json = serialized(TtsError::LoadTimeout(30));
+        assert_eq!(json["kind"], "LoadTimeout");
+        assert_eq!(json["timeout_secs"], 30);
+
+        assert_eq!(serialized(TtsError::Cancelled)["kind"], "Cancelled");
+
+        let json = serialized(TtsError::Io("disk full".to_string()));
+        assert_eq!(json["kind"], "Io");
+        assert_eq!(json["message"], "disk full");
+    }
+}
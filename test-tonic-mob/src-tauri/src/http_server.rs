@@ -0,0 +1,215 @@
+// ============================================================================
+// HTTP Server - Localhost synthesis endpoint (`http-server` feature)
+// ============================================================================
+//
+// Lets companion tools use the TTS engine without going through Tauri IPC:
+//
+//   POST /synthesize   body: SynthesizeRequest JSON   ->   200 audio/wav
+//
+// Requests must send `Content-Type: application/json`, and browsers may only call it from a
+// loopback origin, so a web page the user happens to visit can't drive the engine.
+// Errors come back as the same JSON `TtsError` the Tauri commands return.
+// The server binds to 127.0.0.1 only and shares the global engine pool.
+//
+
+use std::sync::{Arc, Mutex};
+
+use log::{error, info};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::error::TtsError;
+use crate::{encode_wav, synthesize_trimmed, SynthesizeRequest};
+
+/// Port of the running server, if started
+static HTTP_PORT: Mutex<Option<u16>> = Mutex::new(None);
+
+/// Threads handling requests; more wait their turn in the server's queue. Synthesis is
+/// limited by the engine pool's capacity anyway, so this only bounds threads and memory.
+const WORKER_THREADS: usize = 4;
+
+/// Start the server on `port` (0 picks a free one) and return the port it's bound to.
+/// Only one server runs per process; later calls return the existing port.
+pub fn start(port: u16) -> Result<u16, TtsError> {
+    let mut running = HTTP_PORT.lock().map_err(|e| TtsError::Io(format!("Lock error: {}", e)))?;
+    if let Some(port) = *running {
+        return Ok(port);
+    }
+
+    let server = Server::http(("127.0.0.1", port))
+        .map_err(|e| TtsError::Io(format!("Failed to bind 127.0.0.1:{}: {}", port, e)))?;
+    let port = server
+        .server_addr()
+        .to_ip()
+        .map(|addr| addr.port())
+        .ok_or_else(|| TtsError::Io("HTTP server is not bound to an IP address".to_string()))?;
+
+    let server = Arc::new(server);
+    for _ in 0..WORKER_THREADS {
+        let server = server.clone();
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                handle_request(request);
+            }
+        });
+    }
+
+    info!("HTTP server listening on http://127.0.0.1:{}", port);
+    *running = Some(port);
+    Ok(port)
+}
+
+fn handle_request(mut request: Request) {
+    let response = if request.url() != "/synthesize" {
+        text_response(404, "Not found")
+    } else if *request.method() != Method::Post {
+        text_response(405, "Method not allowed")
+    } else if header_value(&request, "Origin").is_some_and(|origin| !is_local_origin(origin)) {
+        text_response(403, "Cross-origin requests are not allowed")
+    } else if !header_value(&request, "Content-Type").is_some_and(is_json_content_type) {
+        text_response(415, "Content-Type must be application/json")
+    } else {
+        let mut body = String::new();
+        match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => synthesize(&body),
+            Err(e) => text_response(400, &format!("Failed to read request body: {}", e)),
+        }
+    };
+
+    if let Err(e) = request.respond(response) {
+        error!("Failed to send HTTP response: {}", e);
+    }
+}
+
+fn header_value<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+/// `application/json`, with or without parameters such as `charset`
+fn is_json_content_type(value: &str) -> bool {
+    value
+        .split(';')
+        .next()
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+}
+
+/// Origins served from this machine, e.g. `http://localhost:1420` or `http://127.0.0.1`
+fn is_local_origin(origin: &str) -> bool {
+    let Some(rest) = origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://")) else {
+        return false;
+    };
+    let host = match rest.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => rest,
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+fn synthesize(body: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    let req: SynthesizeRequest = match serde_json::from_str(body) {
+        Ok(req) => req,
+        Err(e) => return text_response(400, &format!("Invalid request: {}", e)),
+    };
+
+    let wav_bytes =
+        synthesize_trimmed(&req).and_then(|(wav, _duration, sample_rate)| encode_wav(&wav, sample_rate, Some(&req)));
+
+    match wav_bytes {
+        Ok(bytes) => Response::from_data(bytes).with_header(header("Content-Type", "audio/wav")),
+        Err(e) => error_response(&e),
+    }
+}
+
+fn error_response(err: &TtsError) -> Response<std::io::Cursor<Vec<u8>>> {
+    let status = match err {
+        TtsError::NotInitialized | TtsError::ModelsMissing(_) | TtsError::LoadTimeout(_) => 503,
+        TtsError::VoiceNotFound(_) | TtsError::InvalidLanguage(_) | TtsError::InvalidRequest(_) => 400,
+        TtsError::Synthesis(_) | TtsError::Cancelled | TtsError::Io(_) => 500,
+    };
+    let body = serde_json::to_vec(err).unwrap_or_else(|_| err.to_string().into_bytes());
+
+    Response::from_data(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn text_response(status: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(message)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "text/plain; charset=utf-8"))
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("static header is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_json_bodies_are_accepted() {
+        assert!(is_json_content_type("application/json"));
+        assert!(is_json_content_type("Application/JSON; charset=utf-8"));
+        assert!(!is_json_content_type("text/plain"));
+        assert!(!is_json_content_type("application/x-www-form-urlencoded"));
+        assert!(!is_json_content_type("multipart/form-data; boundary=x"));
+    }
+
+    #[test]
+    fn only_loopback_origins_are_accepted() {
+        assert!(is_local_origin("http://localhost:1420"));
+        assert!(is_local_origin("http://127.0.0.1"));
+        assert!(is_local_origin("http://[::1]:8080"));
+        assert!(!is_local_origin("https://example.com"));
+        assert!(!is_local_origin("http://localhost.example.com"));
+        assert!(!is_local_origin("http://127.0.0.1.evil.test:80"));
+        assert!(!is_local_origin("null"));
+    }
+
+
+    #[test]
+    fn synthesis_without_an_engine_is_a_503_with_the_json_error() {
+        let _globals = crate::tests::lock_globals();
+        *crate::TTS_ENGINE.write().unwrap() = None;
+
+        let response = synthesize(r#"{"text": "Hello there.", "language": "en", "voice_style": "stub"}"#);
+        assert_eq!(response.status_code().0, 503);
+        let body: serde_json::Value = serde_json::from_reader(response.into_reader()).unwrap();
+        assert_eq!(body["kind"], "NotInitialized");
+    }
+
+    #[test]
+    fn posting_a_request_returns_a_wav() {
+        use std::io::{Read, Write};
+
+        let _globals = crate::tests::lock_globals();
+        crate::tests::install_stub_engine(1);
+        let port = start(0).unwrap();
+
+        let body = r#"{"text": "Hello there.", "language": "en", "voice_style": "stub"}"#;
+        let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(
+            stream,
+            "POST /synthesize HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&response[..split]);
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+        assert!(head.to_ascii_lowercase().contains("content-type: audio/wav"), "{}", head);
+
+        let wav = hound::WavReader::new(std::io::Cursor::new(&response[split + 4..])).unwrap();
+        assert_eq!(wav.spec().sample_rate, crate::tts_helper::stub::SAMPLE_RATE as u32);
+        assert!(wav.duration() > 0);
+    }
+}
@@ -1,31 +1,162 @@
 mod tts_helper;
+mod model_download;
+mod engine_pool;
+mod error;
+#[cfg(feature = "http-server")]
+mod http_server;
+
+/// Internals the benchmarks in `benches/` measure; not a supported API
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::tts_helper::{
+        encode_wav_to_bytes, load_voice_style_from_bytes, preprocess_text, split_sentences, strip_markdown,
+        ChunkSilence, LoadOptions, PreprocessOptions,
+    };
+    #[cfg(not(target_os = "android"))]
+    pub use crate::tts_helper::load_text_to_speech;
+}
 
-use std::sync::{Mutex, OnceLock};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
 use serde::{Deserialize, Serialize};
 use base64::Engine;
-use tauri::Manager;
-use log::{info, error};
-
-use tts_helper::{TextToSpeech, Style};
+use tauri::{Emitter, Manager};
+use tauri::ipc::Channel;
+use log::{info, warn, error};
+
+use tts_helper::{
+    TextToSpeech, Style, PreprocessOptions, IndexerStats, Config, ChunkConfig, ChunkProgress, ChunkSilence, ChunkTiming, WordTiming,
+    PhonemeTiming, LoadOptions, OptimizationLevel, SpeakingRate, StepSchedule, SynthesisMetrics, TrimConfig,
+    AudioPipeline, AudioStage, PcmFormat, ChunkError,
+};
+use model_download::{DownloadProgress, ManifestFile, ModelManifest, ProgressTracker, SpaceReport, VerifyProgress};
+use engine_pool::{EngineLoader, EnginePool};
+use error::TtsError;
 
 #[cfg(not(target_os = "android"))]
 use tts_helper::load_text_to_speech;
 
 #[cfg(target_os = "android")]
-use tts_helper::{load_text_to_speech_from_bytes, ModelBytes};
+use tts_helper::{load_text_to_speech_from_reader, ModelFileReader};
 
 // ============================================================================
 // Global State
 // ============================================================================
 
-static TTS_ENGINE: OnceLock<Mutex<TextToSpeech>> = OnceLock::new();
-static MODELS_DIR: OnceLock<PathBuf> = OnceLock::new();
+// Replaceable so updated models can be loaded without restarting; see `replace_tts_engine`
+static TTS_ENGINE: RwLock<Option<Arc<EnginePool>>> = RwLock::new(None);
+// Number of engines allowed to synthesize at once, kept across engine reloads
+static SYNTHESIS_CONCURRENCY: AtomicUsize = AtomicUsize::new(1);
+// Used by requests that leave out `total_step` or `speed`; see `set_default_total_step`
+static DEFAULT_TOTAL_STEP: AtomicUsize = AtomicUsize::new(5);
+static DEFAULT_SPEED: RwLock<f32> = RwLock::new(1.05);
+// Graph optimization for sessions loaded from now on, see `set_optimization_level`
+static OPTIMIZATION_LEVEL: RwLock<OptimizationLevel> = RwLock::new(OptimizationLevel::Level3);
+// Set by `cancel_export` to stop the running `export_document` at its next sentence
+static EXPORT_CANCELLED: AtomicBool = AtomicBool::new(false);
+// Most `sentence_N.wav` files `save_audio_to_file` keeps; 0 for no limit
+static AUDIO_CACHE_LIMIT: AtomicUsize = AtomicUsize::new(0);
+// Directory models were loaded from; replaceable so a custom directory can be chosen at runtime
+static MODELS_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+// Parsed voice styles by name, for `MODELS_DIR`; see `load_voice_style_for_platform`
+static VOICE_STYLE_CACHE: Mutex<BTreeMap<String, Arc<Style>>> = Mutex::new(BTreeMap::new());
+// Voices whose style file failed to load since the files last changed; see `check_voice_styles`
+static BROKEN_VOICES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+// Voice chosen with `set_active_voice`; `synthesize_chunk` uses it without touching the disk
+static ACTIVE_VOICE: Mutex<Option<(String, Arc<Style>)>> = Mutex::new(None);
 static INIT_ERROR: OnceLock<String> = OnceLock::new();
+// Outcome of the most recent engine load; see `get_load_state`
+static LOAD_STATE: RwLock<LoadState> = RwLock::new(LoadState::Uninitialized);
 
 // Store app handle for resource loading and path resolution
 static APP_HANDLE: OnceLock<tauri::AppHandle> = OnceLock::new();
 
+/// Where the most recent engine load stands, so the UI can tell a load in progress
+/// from models that were never loaded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum LoadState {
+    Uninitialized,
+    Loading,
+    Ready,
+    Failed(String),
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+//
+// Sent to the frontend so it can react to the engine and models changing instead of
+// polling `get_load_state` and `get_model_status`. Events sent during `setup` may arrive
+// before the frontend listens, so it should still check the state once on startup.
+//
+
+/// An engine load finished; payload `EngineReady`
+const EVENT_READY: &str = "tts://ready";
+/// An engine load failed; payload the error message
+const EVENT_LOAD_FAILED: &str = "tts://load-failed";
+/// Downloading or deleting changed the models on disk; payload the new `ModelStatus`
+const EVENT_MODELS_UPDATED: &str = "tts://models-updated";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EngineReady {
+    pub sample_rate: i32,
+    /// Voices now installed, downloaded or bundled
+    pub voice_count: usize,
+}
+
+/// Send `payload` to every window; does nothing before the app handle is stored
+fn emit_event<S: Serialize + Clone>(event: &str, payload: S) {
+    #[cfg(test)]
+    tests::record_event(event, &payload);
+    if let Some(app) = APP_HANDLE.get() {
+        if let Err(e) = app.emit(event, payload) {
+            warn!("Failed to emit {}: {}", event, e);
+        }
+    }
+}
+
+/// Announce the models directory's current status with `EVENT_MODELS_UPDATED`
+fn emit_models_updated() {
+    if let Some(models_dir) = APP_HANDLE.get().and_then(|app| get_models_directory(app).ok()) {
+        emit_event(EVENT_MODELS_UPDATED, check_downloaded_models(&models_dir));
+    }
+}
+
+fn load_state() -> LoadState {
+    LOAD_STATE.read().map(|state| state.clone()).unwrap_or(LoadState::Uninitialized)
+}
+
+fn set_load_state(state: LoadState) {
+    if let Ok(mut current) = LOAD_STATE.write() {
+        *current = state;
+    }
+}
+
+/// `Ready` or `Failed` for the outcome of a load, announced with `EVENT_READY` or
+/// `EVENT_LOAD_FAILED`
+fn finish_load_state<T, E: std::fmt::Display>(result: &Result<T, E>) {
+    match result {
+        Ok(_) => {
+            set_load_state(LoadState::Ready);
+            if let Ok(pool) = get_tts_engine() {
+                emit_event(EVENT_READY, EngineReady {
+                    sample_rate: pool.sample_rate(),
+                    voice_count: installed_voices().len(),
+                });
+            }
+        }
+        Err(e) => {
+            set_load_state(LoadState::Failed(e.to_string()));
+            emit_event(EVENT_LOAD_FAILED, e.to_string());
+        }
+    }
+}
+
 // ============================================================================
 // Model File Definitions
 // ============================================================================
@@ -43,6 +174,60 @@ const MODEL_FILES: &[(&str, &str)] = &[
 /// Voice style files
 const VOICE_STYLES: &[&str] = &["M1", "M2", "M3", "M4", "M5", "F1", "F2", "F3", "F4", "F5"];
 
+/// SHA-256 digests shipped with the app, so a download can be verified even when the server
+/// provides no manifest or checksum table. Update these whenever the model release changes.
+const BUNDLED_CHECKSUMS: &[(&str, &str)] = &[
+    ("voice_styles/M1.json", "a04c823cbda6dd1c7de131ec68fea83bbb70d7f29d61623304eb871e3b83b5a1"),
+    ("voice_styles/M2.json", "7ddd07bf873a3fd67d09ef4e8293b486beb658158b47e371166198e4c6926072"),
+    ("voice_styles/M3.json", "e8e77a56459e4dc8cdfeb88e6f778dc9a0adf22e1184414f4b0e82a5d1edbe72"),
+    ("voice_styles/M4.json", "95322725e4d25d9ed4e7dcccbf0f3726b0e9a2471d876b7942373218dbd30174"),
+    ("voice_styles/M5.json", "be52f82327da63ff18481ce2dd8060c7df432e0168d748745ef3e21b92d706a5"),
+    ("voice_styles/F1.json", "6106950ebeb8a5da29ea22075f605db659cd07dbc288a68292543d9129aa250f"),
+    ("voice_styles/F2.json", "8b97feb16d79ac0447136796708feac5f83dbabe92a5be1168212653c38729ae"),
+    ("voice_styles/F3.json", "7eda5bccb4e6eb7f228fa182462d5fcf982d77628234603599027f0734d70c29"),
+    ("voice_styles/F4.json", "e056fc2bee393edc8bff761eb28f33fb461e8dad828c3b05348a010ac1b7bb79"),
+    ("voice_styles/F5.json", "ce7645ad7e3c13cca04e0d62bf890ef9ac401988005ba8f5e9c9b59257bc6931"),
+];
+
+/// Expected digest of `rel_path` from `BUNDLED_CHECKSUMS`
+fn bundled_checksum(rel_path: &str) -> Option<String> {
+    BUNDLED_CHECKSUMS
+        .iter()
+        .find(|(path, _digest)| *path == rel_path)
+        .map(|(_path, digest)| digest.to_string())
+}
+
+/// Persisted model download base URL, stored in the models directory
+const MODEL_SOURCE_FILE: &str = "model_source.txt";
+
+/// Optimized model graphs cached between launches, under the models directory
+const OPTIMIZED_MODELS_DIR: &str = "onnx/optimized";
+
+/// Number of model files downloaded at the same time
+const DOWNLOAD_CONCURRENCY: usize = 3;
+
+/// Seconds of silence inserted between chunks when a request doesn't specify one
+const DEFAULT_SILENCE_DURATION: f32 = 0.3;
+
+/// Seconds of silence inserted between paragraphs when a request doesn't specify one
+const DEFAULT_PARAGRAPH_SILENCE: f32 = 0.8;
+
+/// Peak that audio is scaled down to when a request asks to prevent clipping
+const ANTI_CLIP_PEAK: f32 = 0.99;
+
+/// Parse every built-in voice style into the cache right after the engine loads,
+/// so the first synthesis with each voice doesn't wait on it
+const PRELOAD_VOICES_ON_INIT: bool = true;
+
+/// Most timed runs one `benchmark_synthesis` call may ask for
+const MAX_BENCHMARK_ITERATIONS: usize = 100;
+
+/// Noise seed for benchmark runs, so every iteration synthesizes the same audio
+const BENCHMARK_SEED: u64 = 0;
+
+/// Optional pronunciation lexicon, shipped alongside the voice styles
+const LEXICON_FILE: &str = "lexicon.json";
+
 // ============================================================================
 // Model Status and Download Support
 // ============================================================================
@@ -54,6 +239,36 @@ pub struct ModelStatus {
     pub missing_files: Vec<String>,
     pub total_files: usize,
     pub downloaded_files: usize,
+    /// Size on disk of each present file, as (relative path, bytes)
+    #[serde(default)]
+    pub file_sizes: Vec<(String, u64)>,
+    /// Bytes on disk so far, counting partial `.part` downloads
+    #[serde(default)]
+    pub downloaded_bytes: u64,
+    /// Size of the complete download, if the manifest lists every file's size
+    #[serde(default)]
+    pub total_expected_bytes: Option<u64>,
+}
+
+/// How much of the model download is done, e.g. for "245 MB of 512 MB downloaded".
+/// The byte counts are `None` when the manifest doesn't list every file's size.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DownloadSummary {
+    pub total_files: usize,
+    pub total_bytes: Option<u64>,
+    /// Bytes on disk, counting partial downloads since those resume where they stopped
+    pub already_downloaded_bytes: Option<u64>,
+    pub remaining_bytes: Option<u64>,
+}
+
+fn download_summary(status: &ModelStatus) -> DownloadSummary {
+    let total_bytes = status.total_expected_bytes;
+    DownloadSummary {
+        total_files: status.total_files,
+        total_bytes,
+        already_downloaded_bytes: total_bytes.map(|_| status.downloaded_bytes),
+        remaining_bytes: total_bytes.map(|total| total.saturating_sub(status.downloaded_bytes)),
+    }
 }
 
 /// Get the models directory path (for downloading to)
@@ -65,40 +280,177 @@ fn get_models_directory(app: &tauri::AppHandle) -> Result<PathBuf, String> {
         .map_err(|e| format!("Failed to resolve app data dir: {}", e))
 }
 
-/// Check which model files exist in the downloaded models directory
-fn check_downloaded_models(models_dir: &PathBuf) -> ModelStatus {
-    let mut missing_files = Vec::new();
-    let mut downloaded_count = 0;
+fn set_models_dir(dir: PathBuf) {
+    if let Ok(mut models_dir) = MODELS_DIR.write() {
+        *models_dir = Some(dir);
+    }
+    clear_voice_style_cache();
+}
 
-    // Check ONNX model files
-    for (file_path, _name) in MODEL_FILES {
-        let full_path = models_dir.join(file_path);
-        if full_path.exists() {
-            downloaded_count += 1;
-        } else {
-            missing_files.push(file_path.to_string());
-        }
+/// Directory the current models were loaded from, if any
+fn current_models_dir() -> Option<PathBuf> {
+    MODELS_DIR.read().ok().and_then(|dir| dir.clone())
+}
+
+/// ONNX model files (not voice styles) absent from `models_dir`
+fn missing_model_files(models_dir: &Path) -> Vec<String> {
+    MODEL_FILES
+        .iter()
+        .filter(|(file_path, _name)| !models_dir.join(file_path).exists())
+        .map(|(file_path, _name)| file_path.to_string())
+        .collect()
+}
+
+/// Manifest built from the compiled-in file lists, used when no `manifest.json` is available
+fn builtin_manifest() -> ModelManifest {
+    let mut files = Vec::new();
+
+    // Add ONNX model files
+    for (file_path, name) in MODEL_FILES {
+        files.push(ManifestFile {
+            name: name.to_string(),
+            path: file_path.to_string(),
+            url: None,
+            size: None,
+            sha256: bundled_checksum(file_path),
+            version: None,
+        });
     }
 
-    // Check voice style files
+    // Add voice style files
     for voice in VOICE_STYLES {
-        let file_path = format!("voice_styles/{}.json", voice);
-        let full_path = models_dir.join(&file_path);
-        if full_path.exists() {
+        let path = format!("voice_styles/{}.json", voice);
+        files.push(ManifestFile {
+            name: voice.to_string(),
+            sha256: bundled_checksum(&path),
+            path,
+            url: None,
+            size: None,
+            version: None,
+        });
+    }
+
+    ModelManifest {
+        version: "builtin".to_string(),
+        files,
+    }
+}
+
+/// Load the model manifest, checking the downloaded copy, then the bundled one,
+/// and falling back to the built-in file list
+fn load_model_manifest(models_dir: &Path) -> ModelManifest {
+    let downloaded = models_dir.join(model_download::MANIFEST_FILE);
+    let bytes = if downloaded.exists() {
+        std::fs::read(&downloaded)
+            .map_err(|e| format!("Failed to read {}: {}", downloaded.display(), e))
+    } else {
+        read_bundled_asset(model_download::MANIFEST_FILE)
+    };
+
+    match bytes.and_then(|b| ModelManifest::from_bytes(&b).map_err(|e| format!("{:#}", e))) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            info!("Using built-in model manifest: {}", e);
+            builtin_manifest()
+        }
+    }
+}
+
+/// Manifest for the current models directory, or the built-in one before the app is set up
+fn current_manifest() -> ModelManifest {
+    match APP_HANDLE.get().map(get_models_directory) {
+        Some(Ok(models_dir)) => load_model_manifest(&models_dir),
+        _ => builtin_manifest(),
+    }
+}
+
+/// Hash the downloaded model files and return those that don't match their expected checksum.
+/// Digests come from the manifest, with the optional checksum table filling in any gaps.
+/// Returns an empty list when no checksums are known.
+fn verify_downloaded_models(models_dir: &Path) -> Result<Vec<String>, String> {
+    verify_downloaded_models_with_progress(models_dir, &mut |_| {})
+}
+
+/// `verify_downloaded_models`, reporting progress as each file is hashed
+fn verify_downloaded_models_with_progress(
+    models_dir: &Path,
+    on_progress: &mut dyn FnMut(&VerifyProgress),
+) -> Result<Vec<String>, String> {
+    let manifest = load_model_manifest(models_dir);
+    let expected = expected_checksums(models_dir, &manifest)?;
+
+    // The ONNX models ship without digests unless the manifest or checksum table has them
+    let unverifiable = files_without_checksum(&manifest, &expected);
+    if !unverifiable.is_empty() {
+        warn!("No checksum known for {}; these files can't be verified", unverifiable.join(", "));
+    }
+    if expected.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    model_download::find_checksum_mismatches_with_progress(models_dir, &manifest.paths(), &expected, on_progress)
+        .map_err(|e| format!("Failed to verify models: {:#}", e))
+}
+
+/// Expected SHA-256 digests by relative path: the manifest's, then the bundled ones, with the
+/// optional downloaded checksum table filling in any gaps
+fn expected_checksums(models_dir: &Path, manifest: &ModelManifest) -> Result<HashMap<String, String>, String> {
+    let checksums_path = models_dir.join(model_download::CHECKSUMS_FILE);
+    let mut expected = if checksums_path.exists() {
+        let bytes = std::fs::read(&checksums_path)
+            .map_err(|e| format!("Failed to read checksum table: {}", e))?;
+        model_download::load_checksums_from_bytes(&bytes)
+            .map_err(|e| format!("Failed to parse checksum table: {}", e))?
+    } else {
+        HashMap::new()
+    };
+    expected.extend(
+        BUNDLED_CHECKSUMS
+            .iter()
+            .map(|(path, digest)| (path.to_string(), digest.to_string())),
+    );
+    expected.extend(manifest.checksums());
+    Ok(expected)
+}
+
+/// Manifest files with no expected digest, in manifest order
+fn files_without_checksum(manifest: &ModelManifest, expected: &HashMap<String, String>) -> Vec<String> {
+    manifest.paths().into_iter().filter(|path| !expected.contains_key(path)).collect()
+}
+
+/// Check which manifest files exist in the downloaded models directory.
+/// In-progress `.part` downloads don't count as present.
+fn check_downloaded_models(models_dir: &Path) -> ModelStatus {
+    let manifest = load_model_manifest(models_dir);
+    let mut missing_files = Vec::new();
+    let mut downloaded_count = 0;
+    let mut file_sizes = Vec::new();
+    let mut downloaded_bytes = 0;
+
+    for file in &manifest.files {
+        let path = models_dir.join(&file.path);
+        if path.exists() {
             downloaded_count += 1;
+            let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            file_sizes.push((file.path.clone(), size));
+            downloaded_bytes += size;
         } else {
-            missing_files.push(file_path);
+            missing_files.push(file.path.clone());
+            if let Ok(partial) = std::fs::metadata(model_download::part_path(&path)) {
+                downloaded_bytes += partial.len();
+            }
         }
     }
 
-    let total_files = MODEL_FILES.len() + VOICE_STYLES.len();
-
     ModelStatus {
         downloaded: missing_files.is_empty(),
         models_dir: models_dir.to_string_lossy().to_string(),
         missing_files,
-        total_files,
+        total_files: manifest.files.len(),
         downloaded_files: downloaded_count,
+        file_sizes,
+        downloaded_bytes,
+        total_expected_bytes: manifest.files.iter().map(|file| file.size).sum(),
     }
 }
 
@@ -107,11 +459,22 @@ fn check_downloaded_models(models_dir: &PathBuf) -> ModelStatus {
 // ============================================================================
 
 #[cfg(not(target_os = "android"))]
-fn init_tts_engine_from_path(onnx_dir: &PathBuf, models_dir: &PathBuf) -> Result<(), String> {
-    if TTS_ENGINE.get().is_some() {
+fn init_tts_engine_from_path(onnx_dir: &Path, models_dir: &Path) -> Result<(), String> {
+    if tts_engine_loaded() {
         return Ok(());
     }
 
+    let (loader_onnx_dir, loader_models_dir) = (onnx_dir.to_path_buf(), models_dir.to_path_buf());
+    replace_tts_engine(
+        Box::new(move || load_engine_from_path(&loader_onnx_dir, &loader_models_dir)),
+        models_dir.to_path_buf(),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "android"))]
+fn load_engine_from_path(onnx_dir: &Path, models_dir: &Path) -> Result<TextToSpeech, String> {
     // Check if the directory exists
     if !onnx_dir.exists() {
         return Err(format!(
@@ -120,13 +483,11 @@ fn init_tts_engine_from_path(onnx_dir: &PathBuf, models_dir: &PathBuf) -> Result
         ));
     }
 
-    let engine = load_text_to_speech(onnx_dir.to_str().unwrap(), false)
-        .map_err(|e| format!("Failed to load TTS engine: {}", e))?;
+    let mut engine = load_text_to_speech(onnx_dir.to_str().unwrap(), false, &load_options())
+        .map_err(|e| format!("Failed to load TTS engine: {:#}", e))?;
+    engine.set_lexicon(load_lexicon_for_platform(models_dir));
 
-    let _ = TTS_ENGINE.set(Mutex::new(engine));
-    let _ = MODELS_DIR.set(models_dir.clone());
-
-    Ok(())
+    Ok(engine)
 }
 
 #[cfg(not(target_os = "android"))]
@@ -162,7 +523,7 @@ fn try_init_tts_desktop(app: &tauri::App) -> Result<(), String> {
     } else {
         // No bundled models either - user needs to download
         info!("No models found. User must download models to: {}", models_dir.display());
-        let _ = MODELS_DIR.set(models_dir);
+        set_models_dir(models_dir);
         Err("TTS models not found. Please download models first.".to_string())
     }
 }
@@ -171,32 +532,52 @@ fn try_init_tts_desktop(app: &tauri::App) -> Result<(), String> {
 // Android Initialization - Uses bytes-based loading
 // ============================================================================
 
+/// Initialize from model bytes, read one file at a time with `read_file` (again for each
+/// extra pooled engine)
 #[cfg(target_os = "android")]
-fn init_tts_engine_from_bytes(model_bytes: ModelBytes) -> Result<(), String> {
-    if TTS_ENGINE.get().is_some() {
+fn init_tts_engine_from_bytes<R>(read_file: R, models_dir: &Path) -> Result<(), String>
+where
+    R: Fn(&str) -> Result<Vec<u8>, String> + Send + Sync + 'static,
+{
+    if tts_engine_loaded() {
         info!("TTS engine already initialized");
         return Ok(());
     }
 
+    let loader_models_dir = models_dir.to_path_buf();
+    replace_tts_engine(
+        Box::new(move || load_engine_from_bytes(&read_file, &loader_models_dir)),
+        models_dir.to_path_buf(),
+    )?;
+    info!("TTS engine initialized successfully!");
+
+    Ok(())
+}
+
+#[cfg(target_os = "android")]
+fn load_engine_from_bytes(read_file: &ModelFileReader<'_>, models_dir: &Path) -> Result<TextToSpeech, String> {
     info!("Initializing ONNX Runtime from bytes...");
-    let engine = load_text_to_speech_from_bytes(model_bytes)
+    let mut engine = load_text_to_speech_from_reader(read_file, &load_options())
         .map_err(|e| {
-            let msg = format!("Failed to load TTS engine: {}", e);
+            let msg = format!("Failed to load TTS engine: {:#}", e);
             error!("{}", msg);
             msg
         })?;
+    engine.set_lexicon(load_lexicon_for_platform(models_dir));
 
-    let _ = TTS_ENGINE.set(Mutex::new(engine));
-    info!("TTS engine initialized successfully!");
+    Ok(engine)
+}
 
-    Ok(())
+/// Read one model file under `models_dir` into memory
+#[cfg(target_os = "android")]
+fn read_downloaded_model_file(models_dir: &Path, rel_path: &str) -> Result<Vec<u8>, String> {
+    let full_path = models_dir.join(rel_path);
+    std::fs::read(&full_path)
+        .map_err(|e| format!("Failed to read {}: {}", full_path.display(), e))
 }
 
 #[cfg(target_os = "android")]
 fn try_init_tts_android(app: &tauri::App) -> Result<(), String> {
-    use tauri_plugin_fs::FsExt;
-    use tauri::path::BaseDirectory;
-
     info!("Android TTS init starting...");
 
     // Store app handle
@@ -204,63 +585,26 @@ fn try_init_tts_android(app: &tauri::App) -> Result<(), String> {
 
     // Get the models directory for downloads
     let models_dir = get_models_directory(app.handle())?;
-    let _ = MODELS_DIR.set(models_dir.clone());
+    set_models_dir(models_dir.clone());
 
     // Check if models are downloaded
     let status = check_downloaded_models(&models_dir);
 
-    // Helper to read from downloaded files
-    let read_downloaded = |rel_path: &str| -> Result<Vec<u8>, String> {
-        let full_path = models_dir.join(rel_path);
-        std::fs::read(&full_path)
-            .map_err(|e| format!("Failed to read {}: {}", full_path.display(), e))
-    };
-
-    // Helper to read from bundled resources
-    let read_resource = |name: &str| -> Result<Vec<u8>, String> {
-        info!("Resolving resource: {}", name);
-        let path = app.path().resolve(name, BaseDirectory::Resource)
-            .map_err(|e| format!("Failed to resolve path {}: {}", name, e))?;
-
-        let bytes = app.fs().read(&path)
-            .map_err(|e| format!("Failed to read {}: {}", name, e))?;
-        info!("Read {} bytes from {}", bytes.len(), name);
-        Ok(bytes)
-    };
-
     // Try downloaded models first
     if status.downloaded {
         info!("Loading from downloaded models at: {}", models_dir.display());
 
-        let model_bytes = ModelBytes {
-            config: read_downloaded("onnx/tts.json")?,
-            unicode_indexer: read_downloaded("onnx/unicode_indexer.json")?,
-            duration_predictor: read_downloaded("onnx/duration_predictor.onnx")?,
-            text_encoder: read_downloaded("onnx/text_encoder.onnx")?,
-            vector_estimator: read_downloaded("onnx/vector_estimator.onnx")?,
-            vocoder: read_downloaded("onnx/vocoder.onnx")?,
-        };
-
-        return init_tts_engine_from_bytes(model_bytes);
+        let dir = models_dir.clone();
+        return init_tts_engine_from_bytes(move |rel_path| read_downloaded_model_file(&dir, rel_path), &models_dir);
     }
 
     info!("No downloaded models, trying bundled resources...");
 
     // Try bundled resources
-    match read_resource("assets/onnx/tts.json") {
-        Ok(config) => {
+    match read_bundled_asset("onnx/tts.json") {
+        Ok(_) => {
             info!("Found bundled resources, loading...");
-
-            let model_bytes = ModelBytes {
-                config,
-                unicode_indexer: read_resource("assets/onnx/unicode_indexer.json")?,
-                duration_predictor: read_resource("assets/onnx/duration_predictor.onnx")?,
-                text_encoder: read_resource("assets/onnx/text_encoder.onnx")?,
-                vector_estimator: read_resource("assets/onnx/vector_estimator.onnx")?,
-                vocoder: read_resource("assets/onnx/vocoder.onnx")?,
-            };
-
-            init_tts_engine_from_bytes(model_bytes)
+            init_tts_engine_from_bytes(read_bundled_asset, &models_dir)
         }
         Err(_) => {
             // No bundled models - user needs to download
@@ -274,9 +618,55 @@ fn try_init_tts_android(app: &tauri::App) -> Result<(), String> {
 // Voice Style Loading - Platform-aware (checks downloaded then bundled)
 // ============================================================================
 
-fn load_voice_style_for_platform(voice_name: &str) -> Result<Style, String> {
+/// Load a voice style, parsing each voice's file only once per models directory
+fn load_voice_style_for_platform(voice_name: &str) -> Result<Arc<Style>, String> {
+    if let Some(style) = VOICE_STYLE_CACHE.lock()
+        .map_err(|e| format!("Lock error: {}", e))?
+        .get(voice_name)
+    {
+        return Ok(style.clone());
+    }
+
+    // Load outside the lock; two threads racing on the same voice just parse it twice
+    let loaded = load_voice_style_uncached(voice_name);
+    if let Ok(mut broken) = BROKEN_VOICES.lock() {
+        match loaded {
+            Ok(_) => broken.remove(voice_name),
+            Err(_) => broken.insert(voice_name.to_string()),
+        };
+    }
+    let style = Arc::new(loaded?);
+    VOICE_STYLE_CACHE.lock()
+        .map_err(|e| format!("Lock error: {}", e))?
+        .insert(voice_name.to_string(), style.clone());
+    Ok(style)
+}
+
+/// Forget cached voice styles, e.g. after the files they came from changed
+fn clear_voice_style_cache() {
+    if let Ok(mut cache) = VOICE_STYLE_CACHE.lock() {
+        cache.clear();
+    }
+    if let Ok(mut broken) = BROKEN_VOICES.lock() {
+        broken.clear();
+    }
+    if let Ok(mut active) = ACTIVE_VOICE.lock() {
+        *active = None;
+    }
+}
+
+/// The active voice's style, if `voice_name` is the active voice
+fn active_voice(voice_name: &str) -> Option<Arc<Style>> {
+    let active = ACTIVE_VOICE.lock().ok()?;
+    match active.as_ref() {
+        Some((name, style)) if name == voice_name => Some(style.clone()),
+        _ => None,
+    }
+}
+
+fn load_voice_style_uncached(voice_name: &str) -> Result<Style, String> {
     // First try downloaded models directory
-    if let Some(models_dir) = MODELS_DIR.get() {
+    if let Some(models_dir) = current_models_dir() {
         let style_path = models_dir
             .join("voice_styles")
             .join(format!("{}.json", voice_name));
@@ -293,8 +683,146 @@ fn load_voice_style_for_platform(voice_name: &str) -> Result<Style, String> {
     load_voice_style_from_bundled(voice_name)
 }
 
+/// Write `style` to `voice_styles/{name}.json` under the models directory and return the path.
+/// Built-in voice names are only overwritten with `force`.
+fn save_voice_style(name: &str, style: &Style, force: bool) -> Result<String, TtsError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+        return Err(TtsError::InvalidRequest(format!(
+            "Voice name {:?} must be non-empty and only letters, digits, '_' or '-'",
+            name
+        )));
+    }
+    if VOICE_STYLES.contains(&name) && !force {
+        return Err(TtsError::InvalidRequest(format!(
+            "{} is a built-in voice; pass force to overwrite it",
+            name
+        )));
+    }
+
+    let models_dir = match current_models_dir() {
+        Some(dir) => dir,
+        None => {
+            let app = APP_HANDLE.get()
+                .ok_or(TtsError::NotInitialized)?;
+            get_models_directory(app).map_err(TtsError::Io)?
+        }
+    };
+    let styles_dir = models_dir.join("voice_styles");
+    std::fs::create_dir_all(&styles_dir)
+        .map_err(|e| TtsError::Io(format!("Failed to create {}: {}", styles_dir.display(), e)))?;
+
+    let bytes = tts_helper::voice_style_to_bytes(style)
+        .map_err(|e| TtsError::InvalidRequest(e.to_string()))?;
+    let path = styles_dir.join(format!("{}.json", name));
+    std::fs::write(&path, bytes)
+        .map_err(|e| TtsError::Io(format!("Failed to write {}: {}", path.display(), e)))?;
+    if let Ok(mut cache) = VOICE_STYLE_CACHE.lock() {
+        cache.remove(name);
+    }
+    if let Ok(mut broken) = BROKEN_VOICES.lock() {
+        broken.remove(name);
+    }
+    if let Ok(mut active) = ACTIVE_VOICE.lock() {
+        if active.as_ref().is_some_and(|(voice, _)| voice == name) {
+            *active = None;
+        }
+    }
+
+    info!("Saved voice style {} to {}", name, path.display());
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Voice style names with a JSON file in `dir/voice_styles`
+fn voices_in_dir(dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir.join("voice_styles")) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+        .collect()
+}
+
 #[cfg(not(target_os = "android"))]
+fn bundled_voices() -> Vec<String> {
+    use tauri::path::BaseDirectory;
+
+    APP_HANDLE.get()
+        .and_then(|app| app.path().resolve("assets", BaseDirectory::Resource).ok())
+        .map(|resource_dir| voices_in_dir(&resource_dir))
+        .unwrap_or_default()
+}
+
+#[cfg(target_os = "android")]
+fn bundled_voices() -> Vec<String> {
+    // APK assets can't be listed, so assume every voice shipped with the app is present
+    VOICE_STYLES.iter().map(|voice| voice.to_string()).collect()
+}
+
+/// Voices `load_voice_style_for_platform` can load: the current models directory plus bundled
+fn installed_voices() -> Vec<String> {
+    let mut voices: BTreeSet<String> = bundled_voices().into_iter().collect();
+    if let Some(models_dir) = current_models_dir() {
+        voices.extend(voices_in_dir(&models_dir));
+    }
+    voices.into_iter().collect()
+}
+
+/// `installed_voices` without those whose style file has failed to load
+fn usable_voices() -> Vec<String> {
+    let broken = BROKEN_VOICES.lock().map(|broken| broken.clone()).unwrap_or_default();
+    installed_voices()
+        .into_iter()
+        .filter(|voice| !broken.contains(voice))
+        .collect()
+}
+
+/// Fail fast with the list of installed voices if `voice_name` isn't one of them
+fn ensure_voice_installed(voice_name: &str) -> Result<(), TtsError> {
+    let installed = installed_voices();
+    if installed.iter().any(|voice| voice == voice_name) {
+        Ok(())
+    } else {
+        info!("Voice {} not available; installed: [{}]", voice_name, installed.join(", "));
+        Err(TtsError::VoiceNotFound(voice_name.to_string()))
+    }
+}
+
+fn ensure_language_valid(language: &str) -> Result<(), TtsError> {
+    if tts_helper::is_valid_lang(language) {
+        Ok(())
+    } else {
+        Err(TtsError::InvalidLanguage(language.to_string()))
+    }
+}
+
 fn load_voice_style_from_bundled(voice_name: &str) -> Result<Style, String> {
+    let bytes = read_bundled_asset(&format!("voice_styles/{}.json", voice_name))
+        .map_err(|e| format!("Failed to read voice style: {}", e))?;
+
+    tts_helper::load_voice_style_from_bytes(&bytes)
+        .map_err(|e| format!("Failed to parse voice style: {}", e))
+}
+
+fn read_bundled_asset(rel_path: &str) -> Result<Vec<u8>, String> {
+    read_bundled_asset_head(rel_path, u64::MAX)
+}
+
+/// Read at most `max_bytes` from the start of a bundled asset, without reading the rest
+fn read_bundled_asset_head(rel_path: &str, max_bytes: u64) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    open_bundled_asset(rel_path)?
+        .take(max_bytes)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read {}: {}", rel_path, e))?;
+    Ok(bytes)
+}
+
+#[cfg(not(target_os = "android"))]
+fn open_bundled_asset(rel_path: &str) -> Result<std::fs::File, String> {
     use tauri::path::BaseDirectory;
 
     let app = APP_HANDLE.get()
@@ -304,410 +832,3416 @@ fn load_voice_style_from_bundled(voice_name: &str) -> Result<Style, String> {
         .resolve("assets", BaseDirectory::Resource)
         .map_err(|e| format!("Failed to resolve resource dir: {}", e))?;
 
-    let style_path = resource_dir
-        .join("voice_styles")
-        .join(format!("{}.json", voice_name));
-
-    if !style_path.exists() {
-        return Err(format!("Voice style not found: {}", style_path.display()));
+    let path = resource_dir.join(rel_path);
+    if !path.exists() {
+        return Err(format!("Bundled asset not found: {}", path.display()));
     }
 
-    let bytes = std::fs::read(&style_path)
-        .map_err(|e| format!("Failed to read voice style: {}", e))?;
-
-    tts_helper::load_voice_style_from_bytes(&bytes)
-        .map_err(|e| format!("Failed to parse voice style: {}", e))
+    std::fs::File::open(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
 }
 
 #[cfg(target_os = "android")]
-fn load_voice_style_from_bundled(voice_name: &str) -> Result<Style, String> {
-    use tauri_plugin_fs::FsExt;
+fn open_bundled_asset(rel_path: &str) -> Result<std::fs::File, String> {
+    use tauri_plugin_fs::{FsExt, OpenOptions};
     use tauri::path::BaseDirectory;
 
     let app = APP_HANDLE.get()
         .ok_or("App handle not initialized")?;
 
     let path = app.path()
-        .resolve(&format!("assets/voice_styles/{}.json", voice_name), BaseDirectory::Resource)
-        .map_err(|e| format!("Failed to resolve voice style path: {}", e))?;
-
-    let bytes = app.fs().read(&path)
-        .map_err(|e| format!("Failed to read voice style: {}", e))?;
+        .resolve(format!("assets/{}", rel_path), BaseDirectory::Resource)
+        .map_err(|e| format!("Failed to resolve path {}: {}", rel_path, e))?;
 
-    tts_helper::load_voice_style_from_bytes(&bytes)
-        .map_err(|e| format!("Failed to parse voice style: {}", e))
+    let mut options = OpenOptions::new();
+    options.read(true);
+    app.fs().open(path, options)
+        .map_err(|e| format!("Failed to read {}: {}", rel_path, e))
 }
 
 // ============================================================================
-// Status Helpers
+// Pronunciation Lexicon - Optional, checks downloaded then bundled
 // ============================================================================
 
-fn get_tts_engine() -> Result<&'static Mutex<TextToSpeech>, String> {
-    // First check if there was an init error
-    if let Some(err) = INIT_ERROR.get() {
-        return Err(format!("TTS initialization failed: {}", err));
-    }
+/// Load the pronunciation lexicon, returning an empty map if none is shipped
+fn load_lexicon_for_platform(models_dir: &Path) -> HashMap<String, String> {
+    let downloaded = models_dir.join(LEXICON_FILE);
+    let bytes = if downloaded.exists() {
+        std::fs::read(&downloaded)
+            .map_err(|e| format!("Failed to read {}: {}", downloaded.display(), e))
+    } else {
+        read_bundled_asset(LEXICON_FILE)
+    };
 
-    TTS_ENGINE.get().ok_or_else(|| {
-        "TTS engine not initialized. Still loading or initialization failed.".to_string()
-    })
+    let bytes = match bytes {
+        Ok(b) => b,
+        Err(e) => {
+            info!("No pronunciation lexicon loaded: {}", e);
+            return HashMap::new();
+        }
+    };
+
+    match tts_helper::load_lexicon_from_bytes(&bytes) {
+        Ok(lexicon) => {
+            info!("Loaded pronunciation lexicon with {} entries", lexicon.len());
+            lexicon
+        }
+        Err(e) => {
+            error!("Failed to parse pronunciation lexicon: {}", e);
+            HashMap::new()
+        }
+    }
 }
 
 // ============================================================================
-// Tauri Commands
+// Model Validation - Trial-load and run the downloaded models
 // ============================================================================
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct SynthesizeRequest {
-    pub text: String,
-    pub language: String,
-    pub voice_style: String,
-    pub total_step: usize,
-    pub speed: f32,
-}
+/// Load the downloaded models into a throwaway engine and run a one-step inference,
+/// so incompatible models are caught before the real engine is initialized
+fn validate_downloaded_models(models_dir: &Path) -> Result<(), String> {
+    #[cfg(not(target_os = "android"))]
+    let mut engine = {
+        let onnx_dir = models_dir.join("onnx");
+        load_text_to_speech(&onnx_dir.to_string_lossy(), false, &load_options())
+            .map_err(|e| format!("Model validation failed: {:#}", e))?
+    };
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct SynthesizeResponse {
-    pub success: bool,
-    pub message: String,
-    pub audio_base64: Option<String>,
-    pub duration: Option<f32>,
-}
+    #[cfg(target_os = "android")]
+    let mut engine = {
+        let read_file = |rel_path: &str| read_downloaded_model_file(models_dir, rel_path);
+        load_text_to_speech_from_reader(&read_file, &load_options())
+            .map_err(|e| format!("Model validation failed: {:#}", e))?
+    };
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct SynthesizeChunkRequest {
-    pub text: String,
-    pub sentence_index: usize,
-    pub language: String,
-    pub voice_style: String,
-    pub total_step: usize,
-    pub speed: f32,
+    let style = load_voice_style_for_platform(VOICE_STYLES[0])?;
+    engine.warmup(&style)
+        .map_err(|e| format!("Model validation failed: {:#}", e))?;
+
+    info!("Validated models at {}", models_dir.display());
+    Ok(())
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct SynthesizeChunkResponse {
-    pub success: bool,
-    pub sentence_index: usize,
-    pub audio_base64: Option<String>,
-    pub duration: Option<f32>,
+/// One bundled model file's result in a `SelfTestReport`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ComponentCheck {
+    pub file: String,
+    pub component: String,
+    pub passed: bool,
     pub error: Option<String>,
 }
 
-#[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
+/// Whether every bundled model file is present and looks like what it should be
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub components: Vec<ComponentCheck>,
 }
 
-#[tauri::command]
-fn synthesize_text(req: SynthesizeRequest) -> Result<SynthesizeResponse, String> {
-    // Get TTS engine
-    let engine = get_tts_engine()?;
-    let mut engine = engine.lock().map_err(|e| format!("Lock error: {}", e))?;
-
-    // Load voice style using platform-aware loader
-    let style = load_voice_style_for_platform(&req.voice_style)?;
+/// Bytes read from each ONNX file to check its header
+const ONNX_HEADER_BYTES: u64 = 16;
+
+/// Reads up to `max_bytes` from the start of one model file, by its path under the models root
+type ModelHeadReader<'a> = dyn Fn(&str, u64) -> Result<Vec<u8>, String> + 'a;
+
+/// Check each file in `MODEL_FILES` without building any sessions: ONNX files by their first
+/// bytes, the JSON files by parsing them. `read_head(path, max_bytes)` reads the start of a file.
+fn self_test_report(read_head: &ModelHeadReader<'_>) -> SelfTestReport {
+    let components: Vec<ComponentCheck> = MODEL_FILES
+        .iter()
+        .map(|&(file, component)| {
+            let result = if file.ends_with(".onnx") {
+                read_head(file, ONNX_HEADER_BYTES).and_then(|header| check_onnx_header(&header))
+            } else {
+                read_head(file, u64::MAX).and_then(|bytes| check_model_json(component, &bytes))
+            };
+            ComponentCheck {
+                file: file.to_string(),
+                component: component.to_string(),
+                passed: result.is_ok(),
+                error: result.err(),
+            }
+        })
+        .collect();
 
-    // Synthesize
-    let (wav, duration) = engine.call(
-        &req.text,
+    SelfTestReport {
+        passed: components.iter().all(|check| check.passed),
+        components,
+    }
+}
+
+/// ONNX files have no magic number; they're a protobuf `ModelProto`, so the first byte
+/// starts a field tag: a known field number with a varint or length-delimited value.
+/// This rejects empty files, HTML error pages and Git LFS pointers saved in their place.
+fn check_onnx_header(header: &[u8]) -> Result<(), String> {
+    // ModelProto's highest field number is 20, so a tag takes at most two varint bytes
+    let tag = match header {
+        [] => return Err("File is empty".to_string()),
+        [first, ..] if first & 0x80 == 0 => *first as u32,
+        [first, second, ..] if second & 0x80 == 0 => (*first & 0x7f) as u32 | (*second as u32) << 7,
+        _ => return Err("Not an ONNX model: header isn't a protobuf field".to_string()),
+    };
+    let (field, wire_type) = (tag >> 3, tag & 0x7);
+    if !(1..=20).contains(&field) || !matches!(wire_type, 0 | 2) {
+        return Err(format!(
+            "Not an ONNX model: starts with bytes {:02x?}",
+            &header[..header.len().min(4)]
+        ));
+    }
+    Ok(())
+}
+
+/// Parse `tts.json` or the unicode indexer and check it has the fields synthesis needs
+fn check_model_json(component: &str, bytes: &[u8]) -> Result<(), String> {
+    match component {
+        "config" => {
+            let config: Config = serde_json::from_slice(bytes)
+                .map_err(|e| format!("Invalid config: {}", e))?;
+            if config.ae.sample_rate <= 0 {
+                return Err(format!("Invalid config: sample_rate is {}", config.ae.sample_rate));
+            }
+            Ok(())
+        }
+        "unicode_indexer" => {
+            let indexer: Vec<i64> = serde_json::from_slice(bytes)
+                .map_err(|e| format!("Invalid unicode indexer: {}", e))?;
+            if indexer.is_empty() {
+                return Err("Invalid unicode indexer: it's empty".to_string());
+            }
+            Ok(())
+        }
+        _ => serde_json::from_slice::<serde_json::Value>(bytes)
+            .map(|_| ())
+            .map_err(|e| format!("Invalid JSON: {}", e)),
+    }
+}
+
+// ============================================================================
+// Status Helpers
+// ============================================================================
+
+fn get_tts_engine() -> Result<Arc<EnginePool>, TtsError> {
+    // First check if there was an init error
+    if let Some(err) = INIT_ERROR.get() {
+        error!("TTS initialization failed: {}", err);
+        return Err(TtsError::NotInitialized);
+    }
+
+    let engine = TTS_ENGINE.read().map_err(|e| TtsError::Io(format!("Lock error: {}", e)))?;
+    engine.clone().ok_or(TtsError::NotInitialized)
+}
+
+fn optimization_level() -> OptimizationLevel {
+    OPTIMIZATION_LEVEL.read().map(|level| *level).unwrap_or_default()
+}
+
+/// Current optimization level, with optimized graphs cached under the app's models
+/// directory (`onnx/optimized`) whichever directory the models themselves load from
+fn load_options() -> LoadOptions {
+    LoadOptions {
+        optimization_level: optimization_level(),
+        optimized_model_dir: APP_HANDLE
+            .get()
+            .and_then(|app| get_models_directory(app).ok())
+            .map(|dir| dir.join(OPTIMIZED_MODELS_DIR)),
+    }
+}
+
+fn tts_engine_loaded() -> bool {
+    TTS_ENGINE.read().map(|engine| engine.is_some()).unwrap_or(false)
+}
+
+/// Load an engine with `loader` and swap in a new pool around it. Holding the write lock,
+/// this first waits for any in-flight synthesis on the old pool to finish; the old sessions
+/// are dropped with it. Preprocessing options and the step schedule carry over. On success
+/// `models_dir` becomes the current models directory, before the load is announced.
+fn replace_tts_engine(loader: EngineLoader, models_dir: PathBuf) -> Result<(), String> {
+    set_load_state(LoadState::Loading);
+    // Load before taking the lock so synthesis on the old engine can continue meanwhile
+    let result = loader().and_then(|engine| install_tts_engine(engine, loader));
+    if result.is_ok() {
+        set_models_dir(models_dir);
+    }
+    finish_load_state(&result);
+    result
+}
+
+/// Swap in a new pool around an already-loaded engine, as `replace_tts_engine` does
+fn install_tts_engine(engine: TextToSpeech, loader: EngineLoader) -> Result<(), String> {
+    let pool = EnginePool::new(engine, loader, SYNTHESIS_CONCURRENCY.load(Ordering::Relaxed));
+
+    let mut slot = TTS_ENGINE.write().map_err(|e| format!("Lock error: {}", e))?;
+    if let Some(old) = slot.as_ref() {
+        old.wait_until_idle()?;
+        pool.set_preprocess_options(old.preprocess_options()?)?;
+        pool.set_step_schedule(old.step_schedule()?)?;
+        pool.set_max_duration_seconds(old.max_duration_seconds()?)?;
+    }
+
+    *slot = Some(Arc::new(pool));
+    Ok(())
+}
+
+/// Run `task` on its own thread and wait at most `timeout` for it. After a timeout the task
+/// keeps running, and its result is dropped as soon as it's done.
+fn run_with_timeout<T, F>(task: F, timeout: Duration) -> Result<T, RecvTimeoutError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(task());
+    });
+    receiver.recv_timeout(timeout)
+}
+
+/// Loader for a models directory laid out as `onnx/...`
+fn engine_loader_for_dir(models_dir: PathBuf) -> EngineLoader {
+    #[cfg(not(target_os = "android"))]
+    {
+        Box::new(move || load_engine_from_path(&models_dir.join("onnx"), &models_dir))
+    }
+
+    #[cfg(target_os = "android")]
+    {
+        Box::new(move || {
+            load_engine_from_bytes(&|rel_path| read_downloaded_model_file(&models_dir, rel_path), &models_dir)
+        })
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Version of the command request and response shapes. Bumped when a change would break a
+/// frontend written against the previous version; fields added with `#[serde(default)]`,
+/// which older frontends can leave out, don't need a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SynthesizeRequest {
+    pub text: String,
+    pub language: String,
+    pub voice_style: String,
+    /// Denoising steps; the `set_default_total_step` default when unset
+    #[serde(default)]
+    pub total_step: Option<usize>,
+    /// Named step count; overrides `total_step` when set
+    #[serde(default)]
+    pub quality: Option<QualityPreset>,
+    /// Speed multiplier; the `set_default_speed` default when unset
+    #[serde(default)]
+    pub speed: Option<f32>,
+    /// Target speaking rate in words per minute; overrides `speed` when both are set
+    #[serde(default)]
+    pub speaking_rate_wpm: Option<f32>,
+    /// Seconds of silence between chunks within a paragraph; defaults to `DEFAULT_SILENCE_DURATION`
+    #[serde(default)]
+    pub silence_duration: Option<f32>,
+    /// Seconds of silence between paragraphs (text separated by a blank line); defaults to
+    /// `DEFAULT_PARAGRAPH_SILENCE`, or `silence_duration` if that's longer
+    #[serde(default)]
+    pub paragraph_silence: Option<f32>,
+    /// WAV bits per sample: 8, 16 or 24; defaults to 16
+    #[serde(default)]
+    pub bit_depth: Option<u16>,
+    /// WAV channels: 1 (the default) or 2. Stereo only copies the mono voice to both sides,
+    /// for players that expect two channels.
+    #[serde(default)]
+    pub channels: Option<u16>,
+    /// Cutoff in Hz of a high-pass filter against low-frequency rumble, e.g. 80; off when unset
+    #[serde(default)]
+    pub highpass_hz: Option<f32>,
+    /// Subtract the utterance's DC offset before encoding
+    #[serde(default)]
+    pub remove_dc: bool,
+    /// Volume change in decibels, applied before clipping prevention; unity when unset
+    #[serde(default)]
+    pub gain_db: Option<f32>,
+    /// Scale the audio down when it would clip, instead of letting encoding clamp it
+    #[serde(default)]
+    pub prevent_clipping: bool,
+    /// Report how long each model took in `SynthesizeResponse::metrics`
+    #[serde(default)]
+    pub collect_metrics: bool,
+    /// Stop after the chunk that reaches this many characters, for quick previews of long texts
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+    /// Trim leading and trailing silence, after the rest of post-processing. Not applied
+    /// to streamed output, which is sent or written before its end is known.
+    #[serde(default)]
+    pub trim_silence: Option<TrimConfig>,
+    /// Post-processing stages run in order after everything above; see `AudioPipeline` for
+    /// the recommended order. Like `trim_silence`, not available when streaming.
+    #[serde(default)]
+    pub pipeline: Option<AudioPipeline>,
+    /// ONNX execution provider to synthesize on, for comparing providers; the loaded engine's
+    /// when unset. See `EXECUTION_PROVIDERS`.
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Leave silence in place of a chunk that fails and report it in
+    /// `SynthesizeResponse::failed_chunks`, rather than failing the whole text. Chunks are
+    /// synthesized one after another on a single engine, with no progress reports.
+    #[serde(default)]
+    pub best_effort: bool,
+}
+
+/// Denoising step counts under names end users understand: Fast = 4, Balanced = 8, High = 16.
+/// More steps sound cleaner and take proportionally longer.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum QualityPreset {
+    Fast,
+    Balanced,
+    High,
+}
+
+impl QualityPreset {
+    pub const ALL: [QualityPreset; 3] = [QualityPreset::Fast, QualityPreset::Balanced, QualityPreset::High];
+
+    pub fn total_step(self) -> usize {
+        match self {
+            QualityPreset::Fast => 4,
+            QualityPreset::Balanced => 8,
+            QualityPreset::High => 16,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            QualityPreset::Fast => "Fast",
+            QualityPreset::Balanced => "Balanced",
+            QualityPreset::High => "High",
+        }
+    }
+}
+
+/// `SynthesizeRequest` with a caller-supplied voice embedding in place of a named voice.
+/// Embeddings are flattened row-major; each length must match the product of its dims.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CustomStyleSynthesizeRequest {
+    pub text: String,
+    pub language: String,
+    /// Denoising steps; the `set_default_total_step` default when unset
+    #[serde(default)]
+    pub total_step: Option<usize>,
+    /// Speed multiplier; the `set_default_speed` default when unset
+    #[serde(default)]
+    pub speed: Option<f32>,
+    #[serde(default)]
+    pub speaking_rate_wpm: Option<f32>,
+    #[serde(default)]
+    pub silence_duration: Option<f32>,
+    pub style_ttl: Vec<f32>,
+    pub ttl_dims: [usize; 3],
+    pub style_dp: Vec<f32>,
+    pub dp_dims: [usize; 3],
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SynthesizeResponse {
+    pub success: bool,
+    pub message: String,
+    pub audio_base64: Option<String>,
+    pub duration: Option<f32>,
+    pub sample_rate: Option<i32>,
+    /// Characters the model has no token for, e.g. "™"; these are likely mispronounced or skipped
+    #[serde(default)]
+    pub oov_chars: Vec<String>,
+    /// Samples beyond ±1.0 that encoding clamped; non-zero means audible distortion is likely
+    #[serde(default)]
+    pub clipped_samples: usize,
+    /// Per-model timings, when the request set `collect_metrics`
+    #[serde(default)]
+    pub metrics: Option<SynthesisMetrics>,
+    /// Text past the request's `max_chars`, or audio past the duration cap, was left out
+    #[serde(default)]
+    pub truncated: bool,
+    /// Chunks a `best_effort` request left as silence, and why
+    #[serde(default)]
+    pub failed_chunks: Vec<ChunkError>,
+    /// `SCHEMA_VERSION` of the backend that answered
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+/// Raw synthesized audio for Web Audio playback: mono, 32-bit float little-endian samples
+/// in [-1.0, 1.0] with no container header, base64-encoded.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PcmResponse {
+    pub sample_rate: i32,
+    pub channels: u16,
+    pub samples_f32_base64: String,
+}
+
+/// WAV audio (base64) plus matching SRT subtitles
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubtitleResponse {
+    pub audio_base64: String,
+    pub srt: String,
+}
+
+/// WAV audio (base64) plus approximate per-word timings, see `tts_helper::word_timings`
+#[derive(Serialize, Debug)]
+pub struct WordTimingResponse {
+    pub audio_base64: String,
+    pub duration: f32,
+    pub sample_rate: i32,
+    pub words: Vec<WordTiming>,
+}
+
+/// WAV audio (base64) plus approximate per-character timings, see `tts_helper::phoneme_timings`
+#[derive(Serialize, Debug)]
+pub struct PhonemeTimingResponse {
+    pub audio_base64: String,
+    pub duration: f32,
+    pub sample_rate: i32,
+    pub phonemes: Vec<PhonemeTiming>,
+}
+
+/// WAV audio (base64) plus per-bucket peaks for drawing its waveform, see
+/// `tts_helper::downsample_peaks`
+#[derive(Serialize, Debug)]
+pub struct WaveformResponse {
+    pub audio_base64: String,
+    pub peaks: Vec<f32>,
+}
+
+/// A chunk exactly as synthesis split the text, and the silence that follows it
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChunkInfo {
+    pub text: String,
+    pub start: f32,
+    pub duration: f32,
+    /// Gap before the next chunk, or before the end of the audio for the last one
+    pub silence_after: f32,
+}
+
+/// WAV audio (base64) plus every chunk it was synthesized from, for debugging where pauses land
+#[derive(Serialize, Debug)]
+pub struct VerboseResponse {
+    pub audio_base64: String,
+    pub duration: f32,
+    pub sample_rate: i32,
+    pub chunks: Vec<ChunkInfo>,
+}
+
+/// One piece of a `synthesize_segments` request, read at its own pace
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SegmentSpec {
+    pub text: String,
+    /// Speed multiplier; the `set_default_speed` default when unset
+    #[serde(default)]
+    pub speed: Option<f32>,
+    /// Seconds of silence before the next segment; defaults to `DEFAULT_SILENCE_DURATION`
+    #[serde(default)]
+    pub silence_after: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SynthesizeSegmentsRequest {
+    pub language: String,
+    pub voice_style: String,
+    /// Denoising steps; the `set_default_total_step` default when unset
+    #[serde(default)]
+    pub total_step: Option<usize>,
+    pub segments: Vec<SegmentSpec>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SynthesizeSegmentsResponse {
+    pub audio_base64: String,
+    pub duration: f32,
+    pub sample_rate: i32,
+    /// Duration of each segment, in request order; 0 for skipped (empty) segments
+    pub segment_durations: Vec<f32>,
+}
+
+/// One line of a `synthesize_dialogue` request, spoken in its own voice and language
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DialogueLine {
+    pub text: String,
+    pub voice_style: String,
+    pub language: String,
+    /// Speed multiplier; the `set_default_speed` default when unset
+    #[serde(default)]
+    pub speed: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SynthesizeDialogueRequest {
+    /// Denoising steps; the `set_default_total_step` default when unset
+    #[serde(default)]
+    pub total_step: Option<usize>,
+    /// Seconds of silence between lines; defaults to `DEFAULT_SILENCE_DURATION`
+    #[serde(default)]
+    pub gap: Option<f32>,
+    pub lines: Vec<DialogueLine>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SynthesizeDialogueResponse {
+    pub audio_base64: String,
+    pub duration: f32,
+    pub sample_rate: i32,
+    /// Duration of each line, in request order; 0 for skipped (empty) lines
+    pub line_durations: Vec<f32>,
+    /// Where each line starts in the audio, in seconds
+    pub line_starts: Vec<f32>,
+}
+
+/// How synthesis will split a text, see `preview_chunks`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChunkPreview {
+    /// Maximum chunk length, in bytes, used for the language
+    pub max_len: usize,
+    pub chunks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SynthesizeChunkRequest {
+    pub text: String,
+    pub sentence_index: usize,
+    pub language: String,
+    pub voice_style: String,
+    /// Denoising steps; the `set_default_total_step` default when unset
+    #[serde(default)]
+    pub total_step: Option<usize>,
+    /// Speed multiplier; the `set_default_speed` default when unset
+    #[serde(default)]
+    pub speed: Option<f32>,
+    /// Volume change in decibels; unity when unset
+    #[serde(default)]
+    pub gain_db: Option<f32>,
+    /// Post-processing stages run in order before `gain_db`
+    #[serde(default)]
+    pub pipeline: Option<AudioPipeline>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SynthesizeChunkResponse {
+    pub success: bool,
+    pub sentence_index: usize,
+    pub audio_base64: Option<String>,
+    pub duration: Option<f32>,
+    pub error: Option<String>,
+    /// `SCHEMA_VERSION` of the backend that answered
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+/// `SCHEMA_VERSION`, so a frontend can check it matches what it was built against before
+/// sending requests
+#[tauri::command]
+fn schema_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+#[tauri::command]
+fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+/// Characters of `text` the model can't pronounce, for feedback alongside the audio.
+/// Best effort: empty if the engine isn't available.
+fn text_oov_chars(text: &str, language: &str) -> Vec<String> {
+    let Ok(pool) = get_tts_engine() else {
+        return Vec::new();
+    };
+
+    let mut oov: Vec<char> = Vec::new();
+    for (part, _) in tts_helper::split_pause_markers(text) {
+        for c in pool.oov_chars(&part, language).unwrap_or_default() {
+            if !oov.contains(&c) {
+                oov.push(c);
+            }
+        }
+    }
+    oov.into_iter().map(String::from).collect()
+}
+
+/// Reject text with nothing to speak (empty, whitespace, only emoji/symbols or pause markers)
+/// up front, rather than running the models on an empty sequence
+fn ensure_text_speakable(text: &str) -> Result<(), TtsError> {
+    let speakable = tts_helper::split_pause_markers(text)
+        .iter()
+        .any(|(part, _)| tts_helper::has_speakable_text(part));
+    if !speakable {
+        return Err(TtsError::InvalidRequest(
+            "Text has nothing to speak: it is empty or only whitespace, symbols or emoji".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Pause between chunks, validated: finite and non-negative
+fn silence_duration(silence: Option<f32>) -> Result<f32, TtsError> {
+    let silence = silence.unwrap_or(DEFAULT_SILENCE_DURATION);
+    if !silence.is_finite() || silence < 0.0 {
+        return Err(TtsError::InvalidRequest(format!(
+            "silence_duration must be a non-negative number of seconds, got {}",
+            silence
+        )));
+    }
+    Ok(silence)
+}
+
+/// WAV bits per sample for the response, validated: 8, 16 or 24
+fn wav_bit_depth(req: &SynthesizeRequest) -> Result<u16, TtsError> {
+    match req.bit_depth {
+        None => Ok(tts_helper::DEFAULT_WAV_BIT_DEPTH),
+        Some(depth @ (8 | 16 | 24)) => Ok(depth),
+        Some(depth) => Err(TtsError::InvalidRequest(format!(
+            "bit_depth must be 8, 16 or 24, got {}",
+            depth
+        ))),
+    }
+}
+
+/// WAV channel count for the response, validated: 1 or 2
+fn wav_channels(req: &SynthesizeRequest) -> Result<u16, TtsError> {
+    match req.channels {
+        None => Ok(tts_helper::DEFAULT_WAV_CHANNELS),
+        Some(channels @ (1 | 2)) => Ok(channels),
+        Some(channels) => Err(TtsError::InvalidRequest(format!(
+            "channels must be 1 or 2, got {}",
+            channels
+        ))),
+    }
+}
+
+/// Encode `wav` as a WAV file in `req`'s bit depth and channel count, or the defaults without one
+fn encode_wav(wav: &[f32], sample_rate: i32, req: Option<&SynthesizeRequest>) -> Result<Vec<u8>, TtsError> {
+    let (bit_depth, channels) = match req {
+        Some(req) => (wav_bit_depth(req)?, wav_channels(req)?),
+        None => (tts_helper::DEFAULT_WAV_BIT_DEPTH, tts_helper::DEFAULT_WAV_CHANNELS),
+    };
+    tts_helper::encode_wav_to_bytes(wav, sample_rate, bit_depth, channels)
+        .map_err(|e| TtsError::Synthesis(format!("WAV encoding failed: {}", e)))
+}
+
+/// `encode_wav`, base64-encoded for a command response
+fn encode_wav_base64(wav: &[f32], sample_rate: i32, req: Option<&SynthesizeRequest>) -> Result<String, TtsError> {
+    Ok(base64::engine::general_purpose::STANDARD.encode(encode_wav(wav, sample_rate, req)?))
+}
+
+/// Reject high-pass cutoffs that aren't a positive number of Hz
+fn ensure_highpass_valid(req: &SynthesizeRequest) -> Result<(), TtsError> {
+    match req.highpass_hz {
+        Some(hz) if !hz.is_finite() || hz <= 0.0 => Err(TtsError::InvalidRequest(format!(
+            "highpass_hz must be a positive number of Hz, got {}",
+            hz
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Reject silence thresholds that aren't a finite level at or below full scale
+fn ensure_trim_valid(req: &SynthesizeRequest) -> Result<(), TtsError> {
+    match req.trim_silence {
+        Some(TrimConfig { threshold_db, .. }) if !threshold_db.is_finite() || threshold_db > 0.0 => {
+            Err(TtsError::InvalidRequest(format!(
+                "trim_silence.threshold_db must be at most 0 dB, got {}",
+                threshold_db
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Execution providers a request's `provider` can name. Sessions are only ever built for the
+/// CPU; another provider needs its own engine, loaded with that provider, before requests
+/// can be routed to it.
+const EXECUTION_PROVIDERS: &[&str] = &["cpu"];
+
+/// Fail clearly for a provider this build can't run, rather than quietly using the CPU
+fn ensure_provider_available(provider: Option<&str>) -> Result<(), TtsError> {
+    match provider {
+        Some(provider) if !EXECUTION_PROVIDERS.contains(&provider.to_lowercase().as_str()) => {
+            Err(TtsError::InvalidRequest(format!(
+                "Execution provider {} isn't available; available: {}",
+                provider,
+                EXECUTION_PROVIDERS.join(", ")
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Reject pipeline stages whose parameters `tts_helper::apply_pipeline` can't use
+fn ensure_pipeline_valid(pipeline: Option<&AudioPipeline>) -> Result<(), TtsError> {
+    let invalid = |message: String| Err(TtsError::InvalidRequest(format!("pipeline: {}", message)));
+    for stage in pipeline.map_or(&[][..], |pipeline| &pipeline.stages) {
+        match *stage {
+            AudioStage::Highpass { cutoff_hz } if !cutoff_hz.is_finite() || cutoff_hz <= 0.0 => {
+                return invalid(format!("highpass cutoff_hz must be a positive number of Hz, got {}", cutoff_hz));
+            }
+            AudioStage::Trim(TrimConfig { threshold_db, .. }) if !threshold_db.is_finite() || threshold_db > 0.0 => {
+                return invalid(format!("trim threshold_db must be at most 0 dB, got {}", threshold_db));
+            }
+            AudioStage::Normalize { peak } if !(peak > 0.0 && peak <= 1.0) => {
+                return invalid(format!("normalize peak must be above 0 and at most 1.0, got {}", peak));
+            }
+            AudioStage::Gain { db } if !db.is_finite() => {
+                return invalid(format!("gain db must be a finite number, got {}", db));
+            }
+            AudioStage::Attenuate { peak } if !(peak > 0.0 && peak <= 1.0) => {
+                return invalid(format!("attenuate peak must be above 0 and at most 1.0, got {}", peak));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Sentence and paragraph pauses, validated like `silence_duration`
+fn chunk_silence(sentence: Option<f32>, paragraph: Option<f32>) -> Result<ChunkSilence, TtsError> {
+    let sentence = silence_duration(sentence)?;
+    let paragraph = paragraph.unwrap_or(DEFAULT_PARAGRAPH_SILENCE.max(sentence));
+    if !paragraph.is_finite() || paragraph < 0.0 {
+        return Err(TtsError::InvalidRequest(format!(
+            "paragraph_silence must be a non-negative number of seconds, got {}",
+            paragraph
+        )));
+    }
+    Ok(ChunkSilence { sentence, paragraph, samples: None })
+}
+
+fn default_total_step() -> usize {
+    DEFAULT_TOTAL_STEP.load(Ordering::Relaxed)
+}
+
+fn default_speed() -> f32 {
+    DEFAULT_SPEED.read().map(|speed| *speed).unwrap_or(1.0)
+}
+
+/// Denoising steps for a request: its quality preset's, or `total_step` without one
+fn total_step(req: &SynthesizeRequest) -> usize {
+    req.quality.map_or_else(
+        || req.total_step.unwrap_or_else(default_total_step),
+        QualityPreset::total_step,
+    )
+}
+
+/// The request's speed multiplier, or the default
+fn request_speed(req: &SynthesizeRequest) -> f32 {
+    req.speed.unwrap_or_else(default_speed)
+}
+
+/// Reject a step count of zero, which would skip denoising entirely
+fn ensure_total_step_valid(total_step: usize) -> Result<(), TtsError> {
+    if total_step == 0 {
+        return Err(TtsError::InvalidRequest("total_step must be at least 1".to_string()));
+    }
+    Ok(())
+}
+
+/// Reject speed multipliers that aren't a positive number
+fn ensure_speed_valid(speed: f32) -> Result<(), TtsError> {
+    if !speed.is_finite() || speed <= 0.0 {
+        return Err(TtsError::InvalidRequest(format!(
+            "speed must be a positive number, got {}",
+            speed
+        )));
+    }
+    Ok(())
+}
+
+/// Check whichever of `speaking_rate_wpm` and `speed` will be used, as `speaking_rate` picks them
+fn ensure_speaking_rate_valid(speed: f32, speaking_rate_wpm: Option<f32>) -> Result<(), TtsError> {
+    match speaking_rate_wpm {
+        Some(wpm) if !wpm.is_finite() || wpm <= 0.0 => Err(TtsError::InvalidRequest(format!(
+            "speaking_rate_wpm must be a positive number, got {}",
+            wpm
+        ))),
+        Some(_) => Ok(()),
+        None => ensure_speed_valid(speed),
+    }
+}
+
+/// WPM wins over the `speed` multiplier when both are set
+fn speaking_rate(speed: f32, speaking_rate_wpm: Option<f32>) -> SpeakingRate {
+    match speaking_rate_wpm {
+        Some(wpm) => SpeakingRate::WordsPerMinute(wpm),
+        None => SpeakingRate::Speed(speed),
+    }
+}
+
+/// Every check the `SynthesizeRequest` commands make before synthesizing, stopping at the
+/// first problem
+fn check_synthesize_request(req: &SynthesizeRequest) -> Result<(), TtsError> {
+    get_tts_engine()?;
+    ensure_language_valid(&req.language)?;
+    ensure_voice_installed(&req.voice_style)?;
+    ensure_text_speakable(&req.text)?;
+    ensure_total_step_valid(total_step(req))?;
+    ensure_speaking_rate_valid(request_speed(req), req.speaking_rate_wpm)?;
+    chunk_silence(req.silence_duration, req.paragraph_silence)?;
+    wav_bit_depth(req)?;
+    wav_channels(req)?;
+    ensure_highpass_valid(req)?;
+    ensure_trim_valid(req)?;
+    ensure_pipeline_valid(req.pipeline.as_ref())?;
+    ensure_provider_available(req.provider.as_deref())
+}
+
+/// Check a synthesis request as `synthesize_text` would, without synthesizing anything:
+/// engine loaded, language, voice, text, step count, speed and output options
+#[tauri::command]
+fn validate_request(req: SynthesizeRequest) -> Result<(), TtsError> {
+    check_synthesize_request(&req)
+}
+
+/// Synthesize a full text and trim it to its duration, returning (samples, duration, sample_rate)
+fn synthesize_trimmed(req: &SynthesizeRequest) -> Result<(Vec<f32>, f32, i32), TtsError> {
+    let (wav, duration, sample_rate, _, _, _) = synthesize_trimmed_with_timings(req, &mut |_| {})?;
+    Ok((wav, duration, sample_rate))
+}
+
+/// `(samples, duration, sample_rate, chunk timings, model run times, whether the engine's
+/// duration cap cut it short)`
+type TimedSynthesis = (Vec<f32>, f32, i32, Vec<ChunkTiming>, SynthesisMetrics, bool);
+
+/// `synthesize_trimmed`, also returning each chunk's text and start/end time and the
+/// model run times. `on_progress` is called as each chunk finishes.
+fn synthesize_trimmed_with_timings(
+    req: &SynthesizeRequest,
+    on_progress: &mut dyn FnMut(ChunkProgress),
+) -> Result<TimedSynthesis, TtsError> {
+    // Validate and load voice style using platform-aware loader, before tying up an engine
+    check_synthesize_request(req)?;
+    let silence = chunk_silence(req.silence_duration, req.paragraph_silence)?;
+    let style = load_voice_style_for_platform(&req.voice_style).map_err(TtsError::Io)?;
+
+    let (text, _) = budgeted_text(req);
+    let (mut wav, _, sample_rate, timings, metrics, truncated) = synthesize_trimmed_with_style(
+        &text,
         &req.language,
         &style,
-        req.total_step,
-        req.speed,
-        0.3,
-    ).map_err(|e| format!("Synthesis failed: {}", e))?;
+        total_step(req),
+        speaking_rate(request_speed(req), req.speaking_rate_wpm),
+        silence,
+        req.collect_metrics,
+        on_progress,
+    )?;
+
+    let (duration, timings) = run_pipeline(&mut wav, sample_rate, &request_pipeline(req), timings);
+
+    Ok((wav, duration, sample_rate, timings, metrics, truncated))
+}
+
+/// Run `pipeline` on `wav` as `tts_helper::apply_pipeline` does, returning the new duration
+/// and `timings` moved past anything its trim stages cut
+fn run_pipeline(
+    wav: &mut Vec<f32>,
+    sample_rate: i32,
+    pipeline: &AudioPipeline,
+    timings: Vec<ChunkTiming>,
+) -> (f32, Vec<ChunkTiming>) {
+    let cut = tts_helper::apply_pipeline(wav, sample_rate, pipeline);
+    shift_timings(wav, sample_rate, cut, timings)
+}
+
+/// `wav`'s duration, and `timings` moved back by the `cut` samples removed from its start
+/// and clamped to the end
+fn shift_timings(wav: &[f32], sample_rate: i32, cut: usize, timings: Vec<ChunkTiming>) -> (f32, Vec<ChunkTiming>) {
+    let cut = cut as f32 / sample_rate as f32;
+    let duration = wav.len() as f32 / sample_rate as f32;
+    let timings = timings
+        .into_iter()
+        .map(|timing| ChunkTiming {
+            start: (timing.start - cut).clamp(0.0, duration),
+            end: (timing.end - cut).clamp(0.0, duration),
+            ..timing
+        })
+        .collect();
+    (duration, timings)
+}
+
+/// The request's text, cut to `max_chars` at a chunk boundary, and whether it was cut
+fn budgeted_text(req: &SynthesizeRequest) -> (String, bool) {
+    match req.max_chars {
+        Some(max_chars) => tts_helper::truncate_to_char_budget(&req.text, &req.language, max_chars),
+        None => (req.text.clone(), false),
+    }
+}
 
-    // Trim to actual duration
-    let actual_len = (engine.sample_rate as f32 * duration) as usize;
-    let wav_trimmed = &wav[..actual_len.min(wav.len())];
+/// Stages for the request's high-pass filter, DC removal, gain and clipping prevention, in
+/// that order. These work on any stretch of audio, so streamed chunks get them one by one.
+fn level_stages(req: &SynthesizeRequest) -> Vec<AudioStage> {
+    let mut stages = Vec::new();
+    if let Some(cutoff_hz) = req.highpass_hz {
+        stages.push(AudioStage::Highpass { cutoff_hz });
+    }
+    if req.remove_dc {
+        stages.push(AudioStage::RemoveDc);
+    }
+    if let Some(db) = req.gain_db {
+        stages.push(AudioStage::Gain { db });
+    }
+    if req.prevent_clipping {
+        stages.push(AudioStage::Attenuate { peak: ANTI_CLIP_PEAK });
+    }
+    stages
+}
+
+/// All of the request's post-processing as one pipeline: `level_stages`, then
+/// `trim_silence`, then the stages of its own `pipeline`
+fn request_pipeline(req: &SynthesizeRequest) -> AudioPipeline {
+    let mut stages = level_stages(req);
+    stages.extend(req.trim_silence.map(AudioStage::Trim));
+    stages.extend(req.pipeline.iter().flat_map(|pipeline| pipeline.stages.iter().copied()));
+    AudioPipeline { stages }
+}
+
+/// Synthesis shared by named and custom voice styles; inputs are already validated.
+/// `on_progress` is called as each chunk finishes, counting chunks across pause markers.
+/// Model run times are only measured when `collect_metrics` is set.
+#[allow(clippy::too_many_arguments)]
+fn synthesize_trimmed_with_style(
+    text: &str,
+    language: &str,
+    style: &Style,
+    total_step: usize,
+    rate: SpeakingRate,
+    silence: ChunkSilence,
+    collect_metrics: bool,
+    on_progress: &mut dyn FnMut(ChunkProgress),
+) -> Result<TimedSynthesis, TtsError> {
+    // Get TTS engine pool
+    let pool = get_tts_engine()?;
+
+    let total_chunks = tts_helper::count_synthesis_chunks(text, language);
+    let mut chunks_done = 0;
+    let mut duration_done = 0.0;
+    let mut metrics = SynthesisMetrics::default();
+
+    // Synthesize, spreading chunks over as many engines as the synthesis concurrency allows.
+    // `[pause:<ms>]` markers split the text and become silence.
+    let chunk_config = ChunkConfig::for_language(language);
+    let (mut wav, mut duration, mut timings) = tts_helper::synthesize_with_pauses(text, pool.sample_rate(), |part| {
+        let (chunks, starts_paragraph): (Vec<String>, Vec<bool>) =
+            tts_helper::chunk_text_tagged(part, &chunk_config).into_iter().unzip();
+        let mut on_chunk_done = |chunk_duration| {
+            chunks_done += 1;
+            duration_done += chunk_duration;
+            on_progress(ChunkProgress { chunks_done, total_chunks, duration_done });
+        };
+        let (synthesized, part_metrics) =
+            pool.synthesize_chunks(chunks, language, style, total_step, rate, collect_metrics, &mut on_chunk_done)?;
+        metrics += part_metrics;
+        let gaps = silence.gaps(&starts_paragraph);
+        Ok(tts_helper::concat_chunks_with_gaps(synthesized, &gaps, pool.sample_rate()))
+    }).map_err(TtsError::Synthesis)?;
+
+    // Chunks were synthesized in parallel, so the cap is applied once they're joined
+    let truncated = match pool.max_duration_seconds().map_err(TtsError::Io)? {
+        Some(cap) => tts_helper::cap_at_chunk_boundary(&mut wav, &mut timings, pool.sample_rate(), cap),
+        None => false,
+    };
+    if truncated {
+        duration = wav.len() as f32 / pool.sample_rate() as f32;
+        warn!("Kept {} chunks to stay within the duration cap", timings.len());
+    }
+
+    // Every chunk is already trimmed to its duration and the gaps are whole samples, so the
+    // joined audio is complete. Trimming again to `duration * sample_rate` could cut the last
+    // sample, since the float duration can round below the samples actually joined.
+    Ok((wav, duration, pool.sample_rate(), timings, metrics, truncated))
+}
+
+#[tauri::command]
+fn synthesize_text(req: SynthesizeRequest) -> Result<SynthesizeResponse, TtsError> {
+    synthesize_text_reporting(&req, &mut |_| {})
+}
+
+/// `synthesize_text` that reports progress over `channel` after each chunk, for long texts.
+/// Runs off the main thread so progress reaches the UI while synthesis is underway.
+#[tauri::command]
+async fn synthesize_text_progress(
+    req: SynthesizeRequest,
+    channel: Channel<ChunkProgress>,
+) -> Result<SynthesizeResponse, TtsError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        synthesize_text_reporting(&req, &mut |progress| {
+            let _ = channel.send(progress);
+        })
+    })
+    .await
+    .map_err(|e| TtsError::Synthesis(format!("Synthesis task failed: {}", e)))?
+}
+
+fn synthesize_text_reporting(
+    req: &SynthesizeRequest,
+    on_progress: &mut dyn FnMut(ChunkProgress),
+) -> Result<SynthesizeResponse, TtsError> {
+    let (wav_trimmed, duration, sample_rate, metrics, capped, failed_chunks) = if req.best_effort {
+        let (wav, duration, sample_rate, metrics, failed_chunks) = synthesize_best_effort(req)?;
+        (wav, duration, sample_rate, metrics, false, failed_chunks)
+    } else {
+        let (wav, duration, sample_rate, _, metrics, capped) = synthesize_trimmed_with_timings(req, on_progress)?;
+        (wav, duration, sample_rate, metrics, capped, Vec::new())
+    };
+
+    let audio_base64 = encode_wav_base64(&wav_trimmed, sample_rate, Some(req))?;
+
+    Ok(SynthesizeResponse {
+        success: true,
+        message: format!("Synthesized {:.2} seconds of audio", duration),
+        audio_base64: Some(audio_base64),
+        duration: Some(duration),
+        sample_rate: Some(sample_rate),
+        oov_chars: text_oov_chars(&req.text, &req.language),
+        clipped_samples: tts_helper::count_clipped_samples(&wav_trimmed),
+        metrics: req.collect_metrics.then_some(metrics),
+        truncated: budgeted_text(req).1 || capped,
+        failed_chunks,
+        schema_version: SCHEMA_VERSION,
+    })
+}
+
+/// `(samples, duration, sample_rate, model run times, chunks left as silence)`
+type BestEffortSynthesis = (Vec<f32>, f32, i32, SynthesisMetrics, Vec<ChunkError>);
+
+/// `synthesize_trimmed_with_timings` for a `best_effort` request: every chunk on one engine
+/// through `TextToSpeech::call_best_effort`. Fails if every chunk between two pause markers fails.
+fn synthesize_best_effort(req: &SynthesizeRequest) -> Result<BestEffortSynthesis, TtsError> {
+    check_synthesize_request(req)?;
+    let silence = chunk_silence(req.silence_duration, req.paragraph_silence)?;
+    let style = load_voice_style_for_platform(&req.voice_style).map_err(TtsError::Io)?;
+    let rate = speaking_rate(request_speed(req), req.speaking_rate_wpm);
+
+    let pool = get_tts_engine()?;
+    let mut engine = pool.acquire().map_err(TtsError::Synthesis)?;
+    engine.take_metrics();
+    engine.set_collect_metrics(req.collect_metrics);
+    let sample_rate = engine.sample_rate;
+
+    // Chunk indices count from the start of the text, across pause markers
+    let mut failed_chunks = Vec::new();
+    let mut chunks_before = 0;
+    let (mut wav, _, _) = tts_helper::synthesize_with_pauses(&budgeted_text(req).0, sample_rate, |part| {
+        let (wav, duration, failed) = engine.call_best_effort(part, &req.language, &style, total_step(req), rate, silence)?;
+        failed_chunks.extend(failed.into_iter().map(|chunk| ChunkError { index: chunk.index + chunks_before, ..chunk }));
+        chunks_before += tts_helper::count_synthesis_chunks(part, &req.language);
+        Ok::<_, anyhow::Error>((wav, duration, Vec::new()))
+    })
+    .map_err(|e| TtsError::Synthesis(format!("{:#}", e)))?;
+    let metrics = engine.take_metrics();
+    engine.set_collect_metrics(false);
+    drop(engine);
+
+    let (duration, _) = run_pipeline(&mut wav, sample_rate, &request_pipeline(req), Vec::new());
+    Ok((wav, duration, sample_rate, metrics, failed_chunks))
+}
+
+/// Synthesize text with a custom voice embedding, e.g. one computed outside the app
+#[tauri::command]
+fn synthesize_with_custom_style(req: CustomStyleSynthesizeRequest) -> Result<SynthesizeResponse, TtsError> {
+    ensure_language_valid(&req.language)?;
+    ensure_text_speakable(&req.text)?;
+    let total_step = req.total_step.unwrap_or_else(default_total_step);
+    let speed = req.speed.unwrap_or_else(default_speed);
+    ensure_total_step_valid(total_step)?;
+    ensure_speaking_rate_valid(speed, req.speaking_rate_wpm)?;
+    let silence = chunk_silence(req.silence_duration, None)?;
+    let style = tts_helper::load_voice_style_from_arrays(
+        req.style_ttl,
+        req.ttl_dims,
+        req.style_dp,
+        req.dp_dims,
+    ).map_err(|e| TtsError::InvalidRequest(format!("Invalid voice style: {}", e)))?;
+
+    let (wav_trimmed, duration, sample_rate, _, _, _) = synthesize_trimmed_with_style(
+        &req.text,
+        &req.language,
+        &style,
+        total_step,
+        speaking_rate(speed, req.speaking_rate_wpm),
+        silence,
+        false,
+        &mut |_| {},
+    )?;
+
+    let audio_base64 = encode_wav_base64(&wav_trimmed, sample_rate, None)?;
+
+    Ok(SynthesizeResponse {
+        success: true,
+        message: format!("Synthesized {:.2} seconds of audio", duration),
+        audio_base64: Some(audio_base64),
+        duration: Some(duration),
+        sample_rate: Some(sample_rate),
+        oov_chars: text_oov_chars(&req.text, &req.language),
+        clipped_samples: tts_helper::count_clipped_samples(&wav_trimmed),
+        metrics: None,
+        truncated: false,
+        failed_chunks: Vec::new(),
+        schema_version: SCHEMA_VERSION,
+    })
+}
+
+/// Save a custom voice embedding (see `synthesize_with_custom_style`) as a named voice,
+/// usable anywhere a built-in voice is. Returns the saved file's path.
+#[tauri::command]
+fn save_custom_voice_style(
+    name: String,
+    style_ttl: Vec<f32>,
+    ttl_dims: [usize; 3],
+    style_dp: Vec<f32>,
+    dp_dims: [usize; 3],
+    force: Option<bool>,
+) -> Result<String, TtsError> {
+    let style = tts_helper::load_voice_style_from_arrays(style_ttl, ttl_dims, style_dp, dp_dims)
+        .map_err(|e| TtsError::InvalidRequest(format!("Invalid voice style: {}", e)))?;
+    save_voice_style(&name, &style, force.unwrap_or(false))
+}
+
+/// Synthesize text along with SRT captions timed to each synthesized chunk
+#[tauri::command]
+fn synthesize_with_subtitles(req: SynthesizeRequest) -> Result<SubtitleResponse, TtsError> {
+    let (wav_trimmed, _duration, sample_rate, timings, _, _) = synthesize_trimmed_with_timings(&req, &mut |_| {})?;
+
+    let audio_base64 = encode_wav_base64(&wav_trimmed, sample_rate, Some(&req))?;
+
+    Ok(SubtitleResponse {
+        audio_base64,
+        srt: tts_helper::format_srt(&timings),
+    })
+}
+
+/// Synthesize text along with per-word timings for karaoke-style highlighting
+#[tauri::command]
+fn synthesize_with_word_timings(req: SynthesizeRequest) -> Result<WordTimingResponse, TtsError> {
+    let (wav_trimmed, duration, sample_rate, timings, _, _) = synthesize_trimmed_with_timings(&req, &mut |_| {})?;
+
+    let audio_base64 = encode_wav_base64(&wav_trimmed, sample_rate, Some(&req))?;
+
+    Ok(WordTimingResponse {
+        audio_base64,
+        duration,
+        sample_rate,
+        words: tts_helper::word_timings(&timings),
+    })
+}
+
+/// Synthesize text along with per-character timings for lip-sync. Language tags are not
+/// included; see `tts_helper::phoneme_timings` for how the times are estimated.
+#[tauri::command]
+fn synthesize_with_phoneme_timings(req: SynthesizeRequest) -> Result<PhonemeTimingResponse, TtsError> {
+    let (wav_trimmed, duration, sample_rate, timings, _, _) = synthesize_trimmed_with_timings(&req, &mut |_| {})?;
+
+    // Each chunk's text as the engine saw it
+    let pool = get_tts_engine()?;
+    let processed = timings
+        .iter()
+        .map(|timing| pool.normalize_text(&timing.text, &req.language))
+        .collect::<Result<Vec<String>, String>>()
+        .map_err(TtsError::Synthesis)?;
+
+    let audio_base64 = encode_wav_base64(&wav_trimmed, sample_rate, Some(&req))?;
+
+    Ok(PhonemeTimingResponse {
+        audio_base64,
+        duration,
+        sample_rate,
+        phonemes: tts_helper::phoneme_timings(&timings, &processed, &req.language),
+    })
+}
+
+/// Most waveform buckets `synthesize_with_waveform` computes; more than any display has pixels
+const MAX_WAVEFORM_BUCKETS: usize = 16_384;
+
+/// Synthesize text along with `buckets` peak amplitudes for drawing a waveform, so the
+/// frontend doesn't have to decode the WAV just to draw it
+#[tauri::command]
+fn synthesize_with_waveform(req: SynthesizeRequest, buckets: usize) -> Result<WaveformResponse, TtsError> {
+    if buckets == 0 || buckets > MAX_WAVEFORM_BUCKETS {
+        return Err(TtsError::InvalidRequest(format!(
+            "buckets must be between 1 and {}, got {}",
+            MAX_WAVEFORM_BUCKETS, buckets
+        )));
+    }
+    let (wav_trimmed, _duration, sample_rate) = synthesize_trimmed(&req)?;
+
+    let audio_base64 = encode_wav_base64(&wav_trimmed, sample_rate, Some(&req))?;
+
+    Ok(WaveformResponse {
+        audio_base64,
+        peaks: tts_helper::downsample_peaks(&wav_trimmed, buckets),
+    })
+}
+
+/// Synthesize text along with each chunk's text, duration and following silence, to see
+/// why a pause fell where it did
+#[tauri::command]
+fn synthesize_verbose(req: SynthesizeRequest) -> Result<VerboseResponse, TtsError> {
+    let (wav_trimmed, duration, sample_rate, timings, _, _) = synthesize_trimmed_with_timings(&req, &mut |_| {})?;
+
+    let audio_base64 = encode_wav_base64(&wav_trimmed, sample_rate, Some(&req))?;
+
+    Ok(VerboseResponse {
+        audio_base64,
+        duration,
+        sample_rate,
+        chunks: chunk_infos(&timings, duration),
+    })
+}
+
+/// Chunk timings as durations and following silences, which with the first chunk's start
+/// add up to `total_duration`
+fn chunk_infos(timings: &[ChunkTiming], total_duration: f32) -> Vec<ChunkInfo> {
+    timings
+        .iter()
+        .enumerate()
+        .map(|(i, timing)| {
+            let next_start = timings.get(i + 1).map_or(total_duration, |next| next.start);
+            ChunkInfo {
+                text: timing.text.clone(),
+                start: timing.start,
+                duration: timing.end - timing.start,
+                silence_after: (next_start - timing.end).max(0.0),
+            }
+        })
+        .collect()
+}
+
+/// Synthesize segments of text, each with its own speed and following pause, into one
+/// audio clip. Segments with nothing to speak are skipped.
+#[tauri::command]
+fn synthesize_segments(req: SynthesizeSegmentsRequest) -> Result<SynthesizeSegmentsResponse, TtsError> {
+    ensure_language_valid(&req.language)?;
+    ensure_voice_installed(&req.voice_style)?;
+    let total_step = req.total_step.unwrap_or_else(default_total_step);
+    ensure_total_step_valid(total_step)?;
+
+    for (i, segment) in req.segments.iter().enumerate() {
+        let speed = segment.speed.unwrap_or_else(default_speed);
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(TtsError::InvalidRequest(format!(
+                "Segment {} speed must be a positive number, got {}",
+                i, speed
+            )));
+        }
+        if let Some(silence) = segment.silence_after {
+            if !silence.is_finite() || silence < 0.0 {
+                return Err(TtsError::InvalidRequest(format!(
+                    "Segment {} silence_after must be a non-negative number of seconds, got {}",
+                    i, silence
+                )));
+            }
+        }
+    }
+
+    let style = load_voice_style_for_platform(&req.voice_style).map_err(TtsError::Io)?;
+    let pool = get_tts_engine()?;
+
+    let mut synthesized = Vec::new();
+    let mut gaps = Vec::new();
+    let mut segment_durations = Vec::with_capacity(req.segments.len());
+
+    for segment in &req.segments {
+        if !tts_helper::has_speakable_text(&segment.text) {
+            segment_durations.push(0.0);
+            continue;
+        }
+
+        let (wav, duration, _) = pool.call_parallel(
+            &segment.text,
+            &req.language,
+            &style,
+            total_step,
+            SpeakingRate::Speed(segment.speed.unwrap_or_else(default_speed)),
+            ChunkSilence::uniform(DEFAULT_SILENCE_DURATION),
+        ).map_err(TtsError::Synthesis)?;
+
+        segment_durations.push(duration);
+        gaps.push(segment.silence_after.unwrap_or(DEFAULT_SILENCE_DURATION));
+        synthesized.push((segment.text.clone(), wav, duration));
+    }
+
+    if synthesized.is_empty() {
+        return Err(TtsError::InvalidRequest("No segment has any text to synthesize".to_string()));
+    }
+
+    let sample_rate = pool.sample_rate();
+    let (wav, duration, _) = tts_helper::concat_chunks_with_gaps(synthesized, &gaps, sample_rate);
+
+    let audio_base64 = encode_wav_base64(&wav, sample_rate, None)?;
+
+    Ok(SynthesizeSegmentsResponse {
+        audio_base64,
+        duration,
+        sample_rate,
+        segment_durations,
+    })
+}
+
+/// Synthesize a conversation: each line in its own voice, language and speed, joined
+/// into one clip with `gap` seconds between lines. Lines with nothing to speak are skipped.
+#[tauri::command]
+fn synthesize_dialogue(req: SynthesizeDialogueRequest) -> Result<SynthesizeDialogueResponse, TtsError> {
+    let gap = req.gap.unwrap_or(DEFAULT_SILENCE_DURATION);
+    if !gap.is_finite() || gap < 0.0 {
+        return Err(TtsError::InvalidRequest(format!(
+            "gap must be a non-negative number of seconds, got {}",
+            gap
+        )));
+    }
+    let total_step = req.total_step.unwrap_or_else(default_total_step);
+    ensure_total_step_valid(total_step)?;
+
+    // Validate every line and load each distinct voice once, before synthesizing anything
+    let mut styles: HashMap<&str, Arc<Style>> = HashMap::new();
+    for (i, line) in req.lines.iter().enumerate() {
+        ensure_language_valid(&line.language)?;
+        let speed = line.speed.unwrap_or_else(default_speed);
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(TtsError::InvalidRequest(format!(
+                "Line {} speed must be a positive number, got {}",
+                i, speed
+            )));
+        }
+        if !styles.contains_key(line.voice_style.as_str()) {
+            ensure_voice_installed(&line.voice_style)?;
+            styles.insert(&line.voice_style, load_voice_style_for_platform(&line.voice_style).map_err(TtsError::Io)?);
+        }
+    }
+
+    let pool = get_tts_engine()?;
+
+    let mut synthesized = Vec::new();
+    let mut spoken = Vec::with_capacity(req.lines.len());
+
+    for line in &req.lines {
+        if !tts_helper::has_speakable_text(&line.text) {
+            spoken.push(None);
+            continue;
+        }
+
+        let (wav, duration, _) = pool.call_parallel(
+            &line.text,
+            &line.language,
+            &styles[line.voice_style.as_str()],
+            total_step,
+            SpeakingRate::Speed(line.speed.unwrap_or_else(default_speed)),
+            ChunkSilence::uniform(DEFAULT_SILENCE_DURATION),
+        ).map_err(TtsError::Synthesis)?;
+
+        spoken.push(Some(synthesized.len()));
+        synthesized.push((line.text.clone(), wav, duration));
+    }
+
+    if synthesized.is_empty() {
+        return Err(TtsError::InvalidRequest("No dialogue line has any text to synthesize".to_string()));
+    }
+
+    let sample_rate = pool.sample_rate();
+    let durations: Vec<f32> = synthesized.iter().map(|(_, _, duration)| *duration).collect();
+    let (wav, duration, spans) = tts_helper::concat_chunks(synthesized, sample_rate, gap);
+
+    // Skipped lines are reported as zero-length, at the point they'd have been spoken
+    let mut line_durations = Vec::with_capacity(spoken.len());
+    let mut line_starts = Vec::with_capacity(spoken.len());
+    let mut position = 0.0;
+    for index in spoken {
+        match index {
+            Some(i) => {
+                line_durations.push(durations[i]);
+                line_starts.push(spans[i].start);
+                position = spans[i].end;
+            }
+            None => {
+                line_durations.push(0.0);
+                line_starts.push(position);
+            }
+        }
+    }
+
+    let audio_base64 = encode_wav_base64(&wav, sample_rate, None)?;
+
+    Ok(SynthesizeDialogueResponse {
+        audio_base64,
+        duration,
+        sample_rate,
+        line_durations,
+        line_starts,
+    })
+}
+
+/// Show how synthesis will split `text` into chunks for `language`, including splits at
+/// `[pause:<ms>]` markers, so awkward boundaries can be spotted before synthesizing
+#[tauri::command]
+fn preview_chunks(text: String, language: String) -> Result<ChunkPreview, TtsError> {
+    ensure_language_valid(&language)?;
+
+    let config = ChunkConfig::for_language(&language);
+    let chunks = tts_helper::split_pause_markers(&text)
+        .into_iter()
+        .filter(|(part, _)| tts_helper::has_speakable_text(part))
+        .flat_map(|(part, _)| tts_helper::chunk_text(&part, &config))
+        .collect();
+
+    Ok(ChunkPreview { max_len: config.max_len, chunks })
+}
+
+/// Show the text exactly as synthesis will see it: normalized, with the current lexicon and
+/// preprocessing options applied and the language tags added. Runs no models.
+#[tauri::command]
+fn preview_normalized_text(text: String, language: String) -> Result<String, TtsError> {
+    ensure_language_valid(&language)?;
+    get_tts_engine()?.normalize_text(&text, &language).map_err(TtsError::InvalidRequest)
+}
+
+/// Stable hex key for caching synthesized audio: a SHA-256 of the text as synthesis will see
+/// it (see `preview_normalized_text`) together with every other parameter that changes the
+/// audio. Text that only differs in spacing or symbols normalization removes, like "Hi!" and
+/// "Hi !", gets the same key; changing any parameter changes it.
+#[tauri::command]
+fn normalization_key(
+    text: String,
+    language: String,
+    voice_style: String,
+    total_step: usize,
+    speed: f32,
+    seed: Option<u64>,
+) -> Result<String, TtsError> {
+    ensure_language_valid(&language)?;
+    let normalized = get_tts_engine()?.normalize_text(&text, &language).map_err(TtsError::InvalidRequest)?;
+
+    // JSON keeps the fields apart, so no two parameter sets serialize the same
+    let fields = serde_json::to_vec(&(normalized, &language, &voice_style, total_step, speed, seed))
+        .map_err(|e| TtsError::InvalidRequest(e.to_string()))?;
+    Ok(model_download::sha256_bytes(&fields))
+}
+
+/// Token IDs the models would see for `text`: normalized, language-tagged and indexed.
+/// Feed them (edited or not) back through `synthesize_token_ids`.
+#[tauri::command]
+fn debug_tokenize(text: String, language: String) -> Result<Vec<i64>, TtsError> {
+    ensure_language_valid(&language)?;
+
+    let pool = get_tts_engine()?;
+    let engine = pool.acquire().map_err(TtsError::Synthesis)?;
+    engine.tokenize(&text, &language)
+        .map_err(|e| TtsError::InvalidRequest(e.to_string()))
+}
+
+/// How many code points the unicode indexer covers and how many of those have no token,
+/// for debugging out-of-vocabulary characters
+#[tauri::command]
+fn get_indexer_stats() -> Result<IndexerStats, TtsError> {
+    Ok(get_tts_engine()?.text_processor().stats())
+}
+
+/// Whether the model has a token for the single character `c`, so the UI can warn about
+/// unsupported characters before synthesis. Checks the character as given, not normalized.
+#[tauri::command]
+fn char_is_supported(c: String) -> Result<bool, TtsError> {
+    let mut chars = c.chars();
+    let (Some(ch), None) = (chars.next(), chars.next()) else {
+        return Err(TtsError::InvalidRequest(format!("Expected a single character, got {:?}", c)));
+    };
+    Ok(get_tts_engine()?.text_processor().token_id(ch) >= 0)
+}
+
+/// Predicted length in seconds of synthesizing `text`, pauses and silence between chunks
+/// included, for showing before synthesis. Runs only the duration predictor.
+#[tauri::command]
+fn estimate_duration(text: String, language: String, voice_style: String, speed: f32) -> Result<f32, TtsError> {
+    ensure_language_valid(&language)?;
+    ensure_voice_installed(&voice_style)?;
+    ensure_text_speakable(&text)?;
+    ensure_speed_valid(speed)?;
+    let silence = chunk_silence(None, None)?;
+    let style = load_voice_style_for_platform(&voice_style).map_err(TtsError::Io)?;
+
+    let pool = get_tts_engine()?;
+    let mut engine = pool.acquire().map_err(TtsError::Synthesis)?;
+
+    // Every pause marker's silence ends up in the output, as in `synthesize_with_pauses`
+    let mut total = 0.0;
+    for (part, pause) in tts_helper::split_pause_markers(&text) {
+        if tts_helper::has_speakable_text(&part) {
+            total += engine.estimate_duration(&part, &language, &style, SpeakingRate::Speed(speed), silence)
+                .map_err(|e| TtsError::Synthesis(e.to_string()))?;
+        }
+        total += pause;
+    }
+    Ok(total)
+}
+
+/// Synthesize a raw token sequence, bypassing text normalization entirely. Language tags
+/// are part of the sequence (see `debug_tokenize`); `language` is only validated.
+/// The sequence is synthesized as one chunk.
+#[tauri::command]
+fn synthesize_token_ids(
+    token_ids: Vec<i64>,
+    language: String,
+    voice_style: String,
+    total_step: usize,
+    speed: f32,
+) -> Result<SynthesizeResponse, TtsError> {
+    ensure_language_valid(&language)?;
+    ensure_voice_installed(&voice_style)?;
+    ensure_total_step_valid(total_step)?;
+    ensure_speed_valid(speed)?;
+    let style = load_voice_style_for_platform(&voice_style).map_err(TtsError::Io)?;
+
+    let pool = get_tts_engine()?;
+    let mut engine = pool.acquire().map_err(TtsError::Synthesis)?;
+    engine.check_token_ids(&token_ids)
+        .map_err(|e| TtsError::InvalidRequest(e.to_string()))?;
+
+    let (wav, duration) = engine.synthesize_ids(&token_ids, &style, total_step, speed)
+        .map_err(|e| TtsError::Synthesis(e.to_string()))?;
+    let sample_rate = engine.sample_rate;
+    drop(engine);
+
+    let audio_base64 = encode_wav_base64(&wav, sample_rate, None)?;
+
+    Ok(SynthesizeResponse {
+        success: true,
+        message: format!("Synthesized {:.2} seconds of audio", duration),
+        audio_base64: Some(audio_base64),
+        duration: Some(duration),
+        sample_rate: Some(sample_rate),
+        oov_chars: Vec::new(),
+        clipped_samples: tts_helper::count_clipped_samples(&wav),
+        metrics: None,
+        truncated: false,
+        failed_chunks: Vec::new(),
+        schema_version: SCHEMA_VERSION,
+    })
+}
+
+/// Synthesize text to raw PCM, skipping the WAV container so the frontend can copy the
+/// samples straight into an `AudioBuffer`. See `PcmResponse` for the exact format.
+#[tauri::command]
+fn synthesize_pcm(req: SynthesizeRequest) -> Result<PcmResponse, TtsError> {
+    let (wav_trimmed, _duration, sample_rate) = synthesize_trimmed(&req)?;
+    let pcm_bytes = tts_helper::encode_pcm_f32le(&wav_trimmed);
+
+    Ok(PcmResponse {
+        sample_rate,
+        channels: 1,
+        samples_f32_base64: base64::engine::general_purpose::STANDARD.encode(&pcm_bytes),
+    })
+}
+
+/// Base64 WAV header announcing `total_samples` mono samples, for a client that streams raw
+/// integer PCM after a single header; see `tts_helper::wav_header_only`. `bit_depth` defaults to 16.
+#[tauri::command]
+fn get_wav_header(sample_rate: i32, total_samples: u64, bit_depth: Option<u16>) -> Result<String, TtsError> {
+    let header = tts_helper::wav_header_only(
+        sample_rate,
+        total_samples,
+        bit_depth.unwrap_or(tts_helper::DEFAULT_WAV_BIT_DEPTH),
+    )
+    .map_err(|e| TtsError::InvalidRequest(e.to_string()))?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(&header))
+}
+
+/// Synthesize a single sentence/chunk - used by the queue system.
+/// Runs off the main thread so up to `set_synthesis_concurrency` chunks synthesize in parallel.
+#[tauri::command]
+async fn synthesize_chunk(req: SynthesizeChunkRequest) -> SynthesizeChunkResponse {
+    let sentence_index = req.sentence_index;
+    tauri::async_runtime::spawn_blocking(move || synthesize_chunk_blocking(req))
+        .await
+        .unwrap_or_else(|e| SynthesizeChunkResponse {
+            success: false,
+            sentence_index,
+            audio_base64: None,
+            duration: None,
+            error: Some(format!("Synthesis task failed: {}", e)),
+            schema_version: SCHEMA_VERSION,
+        })
+}
+
+fn synthesize_chunk_blocking(req: SynthesizeChunkRequest) -> SynthesizeChunkResponse {
+    let result = (|| {
+        // Validate and load voice style, before tying up an engine
+        ensure_language_valid(&req.language)?;
+        ensure_text_speakable(&req.text)?;
+        ensure_total_step_valid(req.total_step.unwrap_or_else(default_total_step))?;
+        ensure_speed_valid(req.speed.unwrap_or_else(default_speed))?;
+        ensure_pipeline_valid(req.pipeline.as_ref())?;
+        let style = match active_voice(&req.voice_style) {
+            Some(style) => style,
+            None => {
+                ensure_voice_installed(&req.voice_style)?;
+                load_voice_style_for_platform(&req.voice_style).map_err(TtsError::Io)?
+            }
+        };
+
+        let pool = get_tts_engine()?;
+        let mut engine = pool.acquire().map_err(TtsError::Synthesis)?;
+        synthesize_chunk_audio(&mut engine, &style, &req)
+    })();
+
+    chunk_response(req.sentence_index, result)
+}
+
+/// Synthesize several independent texts with one engine and one voice style load, e.g. a
+/// queue's worth of sentences. Each text gets its own response, with `sentence_index` its
+/// position in `texts`; a failed text doesn't stop the rest.
+#[tauri::command]
+async fn synthesize_many(
+    texts: Vec<String>,
+    language: String,
+    voice_style: String,
+    total_step: usize,
+    speed: f32,
+) -> Vec<SynthesizeChunkResponse> {
+    let count = texts.len();
+    tauri::async_runtime::spawn_blocking(move || synthesize_many_blocking(texts, language, voice_style, total_step, speed))
+        .await
+        .unwrap_or_else(|e| {
+            (0..count)
+                .map(|i| chunk_response(i, Err(TtsError::Synthesis(format!("Synthesis task failed: {}", e)))))
+                .collect()
+        })
+}
+
+fn synthesize_many_blocking(
+    texts: Vec<String>,
+    language: String,
+    voice_style: String,
+    total_step: usize,
+    speed: f32,
+) -> Vec<SynthesizeChunkResponse> {
+    let setup = (|| {
+        ensure_language_valid(&language)?;
+        ensure_voice_installed(&voice_style)?;
+        ensure_total_step_valid(total_step)?;
+        ensure_speed_valid(speed)?;
+        let style = load_voice_style_for_platform(&voice_style).map_err(TtsError::Io)?;
+        Ok::<_, TtsError>((style, get_tts_engine()?))
+    })();
+    // With bad settings or without a voice or an engine, every text fails the same way
+    let fail_all = |error: String| -> Vec<SynthesizeChunkResponse> {
+        (0..texts.len())
+            .map(|i| chunk_response(i, Err(TtsError::Io(error.clone()))))
+            .collect()
+    };
+    let (style, pool) = match setup {
+        Ok(setup) => setup,
+        Err(e) => return fail_all(e.to_string()),
+    };
+    let mut engine = match pool.acquire() {
+        Ok(engine) => engine,
+        Err(e) => return fail_all(e),
+    };
+
+    texts
+        .into_iter()
+        .enumerate()
+        .map(|(sentence_index, text)| {
+            let req = SynthesizeChunkRequest {
+                text,
+                sentence_index,
+                language: language.clone(),
+                voice_style: voice_style.clone(),
+                total_step: Some(total_step),
+                speed: Some(speed),
+                gain_db: None,
+                pipeline: None,
+            };
+            let result = ensure_text_speakable(&req.text)
+                .and_then(|_| synthesize_chunk_audio(&mut engine, &style, &req));
+            chunk_response(sentence_index, result)
+        })
+        .collect()
+}
+
+/// Synthesize one queued chunk on `engine`, returning base64 WAV and its duration
+fn synthesize_chunk_audio(
+    engine: &mut TextToSpeech,
+    style: &Style,
+    req: &SynthesizeChunkRequest,
+) -> Result<(String, f32), TtsError> {
+    let len = req.text.chars().count();
+    if len > tts_helper::MAX_SINGLE_CALL_CHARS {
+        return Err(TtsError::InvalidRequest(format!(
+            "Chunk is {} characters; split text over {} characters before queueing it",
+            len,
+            tts_helper::MAX_SINGLE_CALL_CHARS
+        )));
+    }
+
+    // Our text is already a chunk, so synthesize it whole rather than re-chunking it.
+    // Pause markers still insert their silence.
+    let total_step = req.total_step.unwrap_or_else(default_total_step);
+    let speed = req.speed.unwrap_or_else(default_speed);
+    let sample_rate = engine.sample_rate;
+    let (mut wav, _, _) = tts_helper::synthesize_with_pauses(&req.text, sample_rate, |part| {
+        let (wav, duration) = engine.call_single(part, &req.language, style, total_step, speed)?;
+        let timing = ChunkTiming { text: part.to_string(), start: 0.0, end: duration };
+        Ok::<_, anyhow::Error>((wav, duration, vec![timing]))
+    })
+    .map_err(|e| TtsError::Synthesis(e.to_string()))?;
+
+    // Gain goes last, after the chunk's own pipeline
+    let mut pipeline = req.pipeline.clone().unwrap_or_default();
+    pipeline.stages.extend(req.gain_db.map(|db| AudioStage::Gain { db }));
+    let (duration, _) = run_pipeline(&mut wav, sample_rate, &pipeline, Vec::new());
+
+    if let Some(gain_db) = req.gain_db {
+        let clipped = tts_helper::count_clipped_samples(&wav);
+        if clipped > 0 {
+            warn!("Sentence {}: {} samples clipped after {} dB gain", req.sentence_index, clipped, gain_db);
+        }
+    }
+
+    let audio_base64 = encode_wav_base64(&wav, sample_rate, None)?;
+
+    Ok((audio_base64, duration))
+}
+
+fn chunk_response(sentence_index: usize, result: Result<(String, f32), TtsError>) -> SynthesizeChunkResponse {
+    match result {
+        Ok((audio_base64, duration)) => SynthesizeChunkResponse {
+            success: true,
+            sentence_index,
+            audio_base64: Some(audio_base64),
+            duration: Some(duration),
+            error: None,
+            schema_version: SCHEMA_VERSION,
+        },
+        Err(e) => SynthesizeChunkResponse {
+            success: false,
+            sentence_index,
+            audio_base64: None,
+            duration: None,
+            error: Some(e.to_string()),
+            schema_version: SCHEMA_VERSION,
+        },
+    }
+}
+
+/// App data directory for temporary synthesized audio files
+fn audio_cache_dir() -> Result<PathBuf, TtsError> {
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
+
+    Ok(get_models_directory(app).map_err(TtsError::Io)?
+        .parent()
+        .ok_or_else(|| TtsError::Io("Cannot get parent directory".to_string()))?
+        .join("audio_cache"))
+}
+
+/// `output_path`, or a file in the audio cache named after the voice and text; its
+/// directory is created if needed
+fn output_file_path(req: &SynthesizeRequest, output_path: Option<String>) -> Result<PathBuf, TtsError> {
+    let file_path = match output_path {
+        Some(path) => PathBuf::from(path),
+        None => audio_cache_dir()?.join(format!(
+            "{}_{}.wav",
+            req.voice_style,
+            tts_helper::sanitize_filename(&req.text, 40)
+        )),
+    };
+
+    if let Some(parent) = file_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| TtsError::Io(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+
+    Ok(file_path)
+}
+
+/// Synthesize text and write it as a WAV file without a base64 round trip, returning its
+/// `file://` URL. Defaults to the audio cache with a name derived from the voice and text.
+#[tauri::command]
+fn synthesize_to_file(req: SynthesizeRequest, output_path: Option<String>) -> Result<String, TtsError> {
+    let file_path = output_file_path(&req, output_path)?;
+
+    let (wav_trimmed, _duration, sample_rate) = synthesize_trimmed(&req)?;
+    tts_helper::write_wav_file(&file_path, &wav_trimmed, sample_rate, wav_bit_depth(&req)?, wav_channels(&req)?)
+        .map_err(|e| TtsError::Io(format!("Failed to write audio file: {}", e)))?;
+
+    Ok(format!("file://{}", file_path.to_string_lossy()))
+}
+
+/// Synthesize text and write it as headerless mono little-endian PCM in `format` (default
+/// `Int16`), returning its `file://` URL; see `tts_helper::write_pcm_file` for the layout.
+/// Defaults to a `.pcm` file in the audio cache named as in `synthesize_to_file`.
+#[tauri::command]
+fn synthesize_to_pcm_file(
+    req: SynthesizeRequest,
+    output_path: Option<String>,
+    format: Option<PcmFormat>,
+) -> Result<String, TtsError> {
+    let file_path = match output_path {
+        Some(path) => output_file_path(&req, Some(path))?,
+        None => output_file_path(&req, None)?.with_extension("pcm"),
+    };
+
+    let (wav_trimmed, _duration, _sample_rate) = synthesize_trimmed(&req)?;
+    tts_helper::write_pcm_file(&file_path, &wav_trimmed, format.unwrap_or(PcmFormat::Int16))
+        .map_err(|e| TtsError::Io(format!("Failed to write audio file: {}", e)))?;
+
+    Ok(format!("file://{}", file_path.to_string_lossy()))
+}
+
+/// Silence kept before and after each clip `synthesize_phrase_clips` writes, so soft
+/// onsets and tails aren't cut off
+const CLIP_PADDING_MS: usize = 50;
+
+/// Synthesize text and write each phrase as its own WAV in the audio cache, e.g. for
+/// flashcards: the audio is split wherever it's quieter than `threshold_db` (default
+/// -60 dB) for at least `min_silence_ms` (default 200 ms). Returns the clips' `file://`
+/// URLs in order, named after the voice and text with the clip number appended.
+#[tauri::command]
+fn synthesize_phrase_clips(
+    req: SynthesizeRequest,
+    min_silence_ms: Option<usize>,
+    threshold_db: Option<f32>,
+) -> Result<Vec<String>, TtsError> {
+    let threshold_db = threshold_db.unwrap_or(TrimConfig::default().threshold_db);
+    if !threshold_db.is_finite() || threshold_db > 0.0 {
+        return Err(TtsError::InvalidRequest(format!(
+            "threshold_db must be at most 0 dB, got {}",
+            threshold_db
+        )));
+    }
+    let base_path = output_file_path(&req, None)?;
+    let bit_depth = wav_bit_depth(&req)?;
+    let channels = wav_channels(&req)?;
+
+    let (wav, _duration, sample_rate) = synthesize_trimmed(&req)?;
+    let segments = tts_helper::segment_by_silence(
+        &wav,
+        sample_rate,
+        min_silence_ms.unwrap_or(200),
+        10f32.powf(threshold_db / 20.0),
+    );
+
+    let padding = CLIP_PADDING_MS * sample_rate as usize / 1000;
+    let stem = base_path.with_extension("");
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, &(start, end))| -> Result<String, TtsError> {
+            let clip = &wav[start.saturating_sub(padding)..(end + padding).min(wav.len())];
+            let file_path = PathBuf::from(format!("{}_{}.wav", stem.to_string_lossy(), i + 1));
+            tts_helper::write_wav_file(&file_path, clip, sample_rate, bit_depth, channels)
+                .map_err(|e| TtsError::Io(format!("Failed to write audio file: {}", e)))?;
+            Ok(format!("file://{}", file_path.to_string_lossy()))
+        })
+        .collect()
+}
+
+/// `synthesize_to_file` for texts too long to hold in memory, e.g. a whole chapter. Chunks are
+/// synthesized a pool's worth at a time and written to the file as they finish, so memory use
+/// stays around one batch of chunks. `remove_dc` and `gain_db` apply per chunk;
+/// `prevent_clipping` needs the whole audio and is rejected.
+#[tauri::command]
+async fn synthesize_to_file_streaming(req: SynthesizeRequest, output_path: Option<String>) -> Result<String, TtsError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let file_path = output_file_path(&req, output_path)?;
+        let duration = synthesize_streaming(&req, &file_path)?;
+        info!("Streamed {:.2}s of audio to {}", duration, file_path.display());
+        Ok(format!("file://{}", file_path.to_string_lossy()))
+    })
+    .await
+    .map_err(|e| TtsError::Synthesis(format!("Synthesis task failed: {}", e)))?
+}
+
+/// Synthesize `req` into a WAV file at `path` batch by batch, with the same chunk silences and
+/// `[pause:<ms>]` handling as `synthesize_trimmed`. Returns the duration written.
+fn synthesize_streaming(req: &SynthesizeRequest, path: &Path) -> Result<f32, TtsError> {
+    check_synthesize_request(req)?;
+    let silence = chunk_silence(req.silence_duration, req.paragraph_silence)?;
+    let bit_depth = wav_bit_depth(req)?;
+    let channels = wav_channels(req)?;
+    if req.prevent_clipping {
+        return Err(TtsError::InvalidRequest(
+            "prevent_clipping needs the whole audio and isn't available when streaming".to_string(),
+        ));
+    }
+    if req.pipeline.is_some() {
+        return Err(TtsError::InvalidRequest(
+            "pipeline needs the whole audio and isn't available when streaming".to_string(),
+        ));
+    }
+    let style = load_voice_style_for_platform(&req.voice_style).map_err(TtsError::Io)?;
+
+    let pool = get_tts_engine()?;
+    let batch_size = pool.capacity().map_err(TtsError::Io)?;
+    let rate = speaking_rate(request_speed(req), req.speaking_rate_wpm);
+    let chunk_config = ChunkConfig::for_language(&req.language);
+    let mut writer = tts_helper::WavStreamWriter::create(path, pool.sample_rate(), bit_depth, channels)
+        .map_err(|e| TtsError::Io(format!("Failed to create audio file: {}", e)))?;
+    let write_error = |e: anyhow::Error| TtsError::Io(format!("Failed to write audio file: {}", e));
+    let chunk_pipeline = AudioPipeline { stages: level_stages(req) };
+
+    // Silence owed before the next chunk: pause markers, then the gap between chunks
+    let mut pending_silence = 0.0f32;
+    for (part, pause) in tts_helper::split_pause_markers(&budgeted_text(req).0) {
+        if tts_helper::has_speakable_text(&part) {
+            let (chunks, starts_paragraph): (Vec<String>, Vec<bool>) =
+                tts_helper::chunk_text_tagged(&part, &chunk_config).into_iter().unzip();
+            let gaps = silence.gaps(&starts_paragraph);
+
+            for (batch_index, batch) in chunks.chunks(batch_size).enumerate() {
+                let (synthesized, _) = pool
+                    .synthesize_chunks(batch.to_vec(), &req.language, &style, total_step(req), rate, false, &mut |_| {})
+                    .map_err(TtsError::Synthesis)?;
+                for (offset, (_, mut wav, _)) in synthesized.into_iter().enumerate() {
+                    let i = batch_index * batch_size + offset;
+                    if i > 0 {
+                        pending_silence += gaps[i - 1];
+                    }
+                    writer.write_silence(pending_silence).map_err(write_error)?;
+                    pending_silence = 0.0;
+
+                    tts_helper::apply_pipeline(&mut wav, pool.sample_rate(), &chunk_pipeline);
+                    writer.write(&wav).map_err(write_error)?;
+                }
+            }
+        }
+        pending_silence += pause;
+    }
+    writer.write_silence(pending_silence).map_err(write_error)?;
+
+    writer.finalize().map_err(write_error)
+}
+
+/// Progress report sent while exporting a document
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportProgress {
+    pub sentences_done: usize,
+    pub sentences_total: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExportResponse {
+    /// `file://` URL of the written WAV
+    pub file_url: String,
+    pub duration: f32,
+}
+
+/// Synthesize a whole document sentence by sentence into one WAV file, reporting progress
+/// over `channel`, so the frontend doesn't need an IPC round trip per sentence. Sentences
+/// are joined with the request's sentence and paragraph silences; the output path defaults
+/// to the audio cache as in `synthesize_to_file`. Stops with a `Cancelled` error after `cancel_export`.
+#[tauri::command]
+async fn export_document(
+    req: SynthesizeRequest,
+    output_path: Option<String>,
+    channel: Channel<ExportProgress>,
+) -> Result<ExportResponse, TtsError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        export_document_blocking(&req, output_path, |progress| {
+            let _ = channel.send(progress);
+        })
+    })
+    .await
+    .map_err(|e| TtsError::Synthesis(format!("Export task failed: {}", e)))?
+}
+
+/// Stop the document export in progress, if any
+#[tauri::command]
+fn cancel_export() {
+    EXPORT_CANCELLED.store(true, Ordering::Relaxed);
+}
+
+fn export_document_blocking(
+    req: &SynthesizeRequest,
+    output_path: Option<String>,
+    on_progress: impl Fn(ExportProgress),
+) -> Result<ExportResponse, TtsError> {
+    check_synthesize_request(req)?;
+    let silence = chunk_silence(req.silence_duration, req.paragraph_silence)?;
+    let bit_depth = wav_bit_depth(req)?;
+    let channels = wav_channels(req)?;
+    let style = load_voice_style_for_platform(&req.voice_style).map_err(TtsError::Io)?;
+    let file_path = output_file_path(req, output_path)?;
+
+    // Sentences with nothing to speak (e.g. a lone "...") are dropped rather than failing the
+    // export. Each is flagged if it starts a paragraph, for the longer pause there.
+    let mut sentences: Vec<String> = Vec::new();
+    let mut starts_paragraph: Vec<bool> = Vec::new();
+    for paragraph in tts_helper::split_paragraphs(&req.text) {
+        let first_in_paragraph = sentences.len();
+        for sentence in split_text_to_sentences(paragraph.to_string(), req.language.clone()) {
+            if tts_helper::has_speakable_text(&sentence) {
+                starts_paragraph.push(sentences.len() == first_in_paragraph && !sentences.is_empty());
+                sentences.push(sentence);
+            }
+        }
+    }
+    let sentences_total = sentences.len();
+
+    EXPORT_CANCELLED.store(false, Ordering::Relaxed);
+    on_progress(ExportProgress { sentences_done: 0, sentences_total });
+
+    let mut chunks = Vec::with_capacity(sentences_total);
+    let mut sample_rate = 0;
+    for (i, sentence) in sentences.into_iter().enumerate() {
+        if EXPORT_CANCELLED.load(Ordering::Relaxed) {
+            info!("Export cancelled after {} of {} sentences", i, sentences_total);
+            return Err(TtsError::Cancelled);
+        }
+
+        let (wav, duration, rate, _, _, _) = synthesize_trimmed_with_style(
+            &sentence,
+            &req.language,
+            &style,
+            total_step(req),
+            speaking_rate(request_speed(req), req.speaking_rate_wpm),
+            silence,
+            false,
+            &mut |_| {},
+        )?;
+        sample_rate = rate;
+        chunks.push((sentence, wav, duration));
+
+        on_progress(ExportProgress { sentences_done: i + 1, sentences_total });
+    }
+
+    let gaps = silence.gaps(&starts_paragraph);
+    let (mut wav, _, timings) = tts_helper::concat_chunks_with_gaps(chunks, &gaps, sample_rate);
+    let (duration, _) = run_pipeline(&mut wav, sample_rate, &request_pipeline(req), timings);
+
+    tts_helper::write_wav_file(&file_path, &wav, sample_rate, bit_depth, channels)
+        .map_err(|e| TtsError::Io(format!("Failed to write audio file: {}", e)))?;
+
+    info!("Exported {:.2}s of audio to {}", duration, file_path.display());
+    Ok(ExportResponse {
+        file_url: format!("file://{}", file_path.to_string_lossy()),
+        duration,
+    })
+}
+
+/// Save audio base64 to a temp file and return the file path
+/// This is needed for the music-notification plugin which plays from URLs
+#[tauri::command]
+fn save_audio_to_file(audio_base64: String, sentence_index: usize) -> Result<String, TtsError> {
+    let audio_dir = audio_cache_dir()?;
+
+    // Create directory if it doesn't exist
+    std::fs::create_dir_all(&audio_dir)
+        .map_err(|e| TtsError::Io(format!("Failed to create audio cache dir: {}", e)))?;
+
+    // Decode base64
+    let audio_bytes = base64::engine::general_purpose::STANDARD
+        .decode(&audio_base64)
+        .map_err(|e| TtsError::InvalidRequest(format!("Failed to decode base64: {}", e)))?;
+
+    // Save to file
+    let file_path = audio_dir.join(format!("sentence_{}.wav", sentence_index));
+    std::fs::write(&file_path, &audio_bytes)
+        .map_err(|e| TtsError::Io(format!("Failed to write audio file: {}", e)))?;
+
+    let limit = AUDIO_CACHE_LIMIT.load(Ordering::Relaxed);
+    if limit > 0 {
+        let removed = prune_sentence_cache(&audio_dir, limit, Some(&file_path)).map_err(TtsError::Io)?;
+        if removed > 0 {
+            info!("Removed {} old cached sentence(s) over the limit of {}", removed, limit);
+        }
+    }
+
+    // Return file:// URL for Android
+    Ok(format!("file://{}", file_path.to_string_lossy()))
+}
+
+/// Keep at most `max_files` cached sentences, evicting older `save_audio_to_file` calls'
+/// files first, so a long session can't fill storage. 0 removes the limit. The cache
+/// doesn't know which sentences the queue still needs: the caller must pick a limit above
+/// how far back it plays, or re-save evicted sentences.
+#[tauri::command]
+fn set_audio_cache_limit(max_files: usize) -> Result<(), TtsError> {
+    AUDIO_CACHE_LIMIT.store(max_files, Ordering::Relaxed);
+    if max_files > 0 {
+        let audio_dir = audio_cache_dir()?;
+        if audio_dir.exists() {
+            prune_sentence_cache(&audio_dir, max_files, None).map_err(TtsError::Io)?;
+        }
+    }
+    Ok(())
+}
+
+/// Delete all but the `max_files` most recently modified `sentence_N.wav` files in `dir`,
+/// returning how many were deleted. `keep` counts as newest, since files written within
+/// the same clock tick share a modification time. Other files (e.g. exports) are left alone.
+fn prune_sentence_cache(dir: &Path, max_files: usize, keep: Option<&Path>) -> Result<usize, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read audio cache: {}", e))?;
+
+    let mut sentences: Vec<(bool, std::time::SystemTime, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| sentence_cache_index(path).is_some())
+        .filter_map(|path| {
+            let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+            Some((keep == Some(path.as_path()), modified, path))
+        })
+        .collect();
+    if sentences.len() <= max_files {
+        return Ok(0);
+    }
+
+    // Newest first; what's left past `max_files` goes
+    sentences.sort_by(|a, b| b.cmp(a));
+    let mut removed = 0;
+    for (_, _, path) in &sentences[max_files..] {
+        match std::fs::remove_file(path) {
+            Ok(()) => removed += 1,
+            Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+    Ok(removed)
+}
+
+/// Sentence number of a `sentence_N.wav` file written by `save_audio_to_file`
+fn sentence_cache_index(path: &Path) -> Option<usize> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("sentence_")?
+        .strip_suffix(".wav")?
+        .parse()
+        .ok()
+}
+
+/// A `sentence_N.wav` file in the audio cache
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CachedAudio {
+    pub sentence_index: usize,
+    /// `file://` URL, as returned by `save_audio_to_file`
+    pub file_url: String,
+    pub size_bytes: u64,
+}
+
+/// List the cached sentences in `dir` by sentence number, ignoring other files
+fn cached_sentences(dir: &Path) -> Result<Vec<CachedAudio>, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read audio cache: {}", e))?;
+
+    let mut cached: Vec<CachedAudio> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let sentence_index = sentence_cache_index(&path)?;
+            let size_bytes = entry.metadata().ok()?.len();
+            Some(CachedAudio {
+                sentence_index,
+                file_url: format!("file://{}", path.to_string_lossy()),
+                size_bytes,
+            })
+        })
+        .collect();
+    cached.sort_by_key(|audio| audio.sentence_index);
+    Ok(cached)
+}
+
+/// List the sentences `save_audio_to_file` has cached, in sentence order, so the frontend
+/// can rebuild its playlist after a restart without synthesizing them again
+#[tauri::command]
+fn list_audio_cache() -> Result<Vec<CachedAudio>, TtsError> {
+    let audio_dir = audio_cache_dir()?;
+    if !audio_dir.exists() {
+        return Ok(Vec::new());
+    }
+    cached_sentences(&audio_dir).map_err(TtsError::Io)
+}
+
+/// Clear audio cache directory
+#[tauri::command]
+fn clear_audio_cache() -> Result<(), TtsError> {
+    let audio_dir = audio_cache_dir()?;
+
+    if audio_dir.exists() {
+        std::fs::remove_dir_all(&audio_dir)
+            .map_err(|e| TtsError::Io(format!("Failed to clear audio cache: {}", e)))?;
+    }
+
+    Ok(())
+}
+
+/// Join the queue's cached `sentence_N.wav` files, in sentence order, into one WAV in the
+/// audio cache, with `gap` seconds (default none) between sentences. Missing sentence numbers
+/// are skipped with a warning. Returns the file's `file://` URL.
+#[tauri::command]
+fn concatenate_audio_cache(output_name: String, gap: Option<f32>) -> Result<String, TtsError> {
+    let gap = gap.unwrap_or(0.0);
+    if !gap.is_finite() || gap < 0.0 {
+        return Err(TtsError::InvalidRequest(format!(
+            "gap must be a non-negative number of seconds, got {}",
+            gap
+        )));
+    }
+
+    let output_name = tts_helper::sanitize_filename(
+        output_name.strip_suffix(".wav").unwrap_or(&output_name),
+        64,
+    );
+    if output_name.is_empty() {
+        return Err(TtsError::InvalidRequest("output_name must not be empty".to_string()));
+    }
+
+    let audio_dir = audio_cache_dir()?;
+    let entries = std::fs::read_dir(&audio_dir)
+        .map_err(|e| TtsError::Io(format!("Failed to read audio cache: {}", e)))?;
+
+    let mut sentences: Vec<(usize, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter_map(|path| Some((sentence_cache_index(&path)?, path)))
+        .collect();
+    sentences.sort_by_key(|(index, _)| *index);
+
+    if sentences.is_empty() {
+        return Err(TtsError::InvalidRequest("Audio cache has no sentence audio to join".to_string()));
+    }
+    let last_index = sentences[sentences.len() - 1].0;
+    if sentences.len() != last_index + 1 {
+        let present: BTreeSet<usize> = sentences.iter().map(|(index, _)| *index).collect();
+        let missing: Vec<String> = (0..=last_index)
+            .filter(|index| !present.contains(index))
+            .map(|index| index.to_string())
+            .collect();
+        warn!("Skipping missing sentences in audio cache: {}", missing.join(", "));
+    }
+
+    let paths: Vec<PathBuf> = sentences.into_iter().map(|(_, path)| path).collect();
+    let (wav, sample_rate, duration) = tts_helper::concat_wav_files(&paths, gap)
+        .map_err(|e| TtsError::Io(format!("Failed to join cached audio: {:#}", e)))?;
+
+    let file_path = audio_dir.join(format!("{}.wav", output_name));
+    tts_helper::write_wav_file(
+        &file_path,
+        &wav,
+        sample_rate,
+        tts_helper::DEFAULT_WAV_BIT_DEPTH,
+        tts_helper::DEFAULT_WAV_CHANNELS,
+    )
+    .map_err(|e| TtsError::Io(format!("Failed to write audio file: {}", e)))?;
+
+    info!("Joined {} cached sentences ({:.2}s) into {}", paths.len(), duration, file_path.display());
+    Ok(format!("file://{}", file_path.to_string_lossy()))
+}
+
+/// Split text into individual sentences for the queue system
+#[tauri::command]
+fn split_text_to_sentences(text: String, _language: String) -> Vec<String> {
+    // Split into actual sentences (not chunks)
+    let sentences = tts_helper::split_sentences(&text);
+    // Filter out empty sentences and trim whitespace
+    sentences
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceGender {
+    Male,
+    Female,
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PreloadVoicesResponse {
+    /// Number of voice styles now cached
+    pub loaded: usize,
+    /// Voices that failed to load, e.g. because their file is missing
+    pub failed: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoiceInfo {
+    pub id: String,
+    pub display_name: String,
+    pub gender: VoiceGender,
+    /// Whether the style JSON is on disk (downloaded or bundled), and hasn't failed to load
+    pub installed: bool,
+}
+
+/// Metadata for a voice id like `M1`, derived from its `M`/`F` prefix and number
+fn voice_info(id: &str, installed: &[String]) -> VoiceInfo {
+    let (gender, label) = match id.chars().next() {
+        Some('M') => (VoiceGender::Male, "Male Voice"),
+        Some('F') => (VoiceGender::Female, "Female Voice"),
+        _ => (VoiceGender::Unknown, "Voice"),
+    };
+
+    VoiceInfo {
+        id: id.to_string(),
+        display_name: format!("{} {}", label, id.get(1..).unwrap_or_default()).trim_end().to_string(),
+        gender,
+        installed: installed.iter().any(|voice| voice == id),
+    }
+}
+
+/// Voice style names actually present on disk (downloaded or bundled), e.g. `["F1", "M1"]`.
+/// Voices whose file has failed to load, e.g. found corrupt by `check_voice_styles`, are left out.
+#[tauri::command]
+fn list_installed_voices() -> Vec<String> {
+    usable_voices()
+}
+
+/// Load `voice_style` once and keep it as the active voice: `synthesize_chunk` requests for
+/// it then skip the installed check and style lookup. Setting another voice replaces it, and
+/// the active voice is dropped whenever the voice files change.
+#[tauri::command]
+fn set_active_voice(voice_style: String) -> Result<(), TtsError> {
+    ensure_voice_installed(&voice_style)?;
+    let style = load_voice_style_for_platform(&voice_style).map_err(TtsError::Io)?;
+    let mut active = ACTIVE_VOICE.lock()
+        .map_err(|e| TtsError::Io(format!("Lock error: {}", e)))?;
+    info!("Active voice is now {}", voice_style);
+    *active = Some((voice_style, style));
+    Ok(())
+}
+
+/// Parse every installed voice's style file, returning `(voice, valid)` for each. Corrupt
+/// voices are then left out of `list_installed_voices` and shown as not installed by
+/// `get_available_voices_v2`, so they can't be picked. Valid ones end up in the style cache.
+#[tauri::command]
+fn check_voice_styles() -> Vec<(String, bool)> {
+    installed_voices()
+        .into_iter()
+        .map(|voice| {
+            let valid = match load_voice_style_for_platform(&voice) {
+                Ok(_) => true,
+                Err(e) => {
+                    warn!("Voice {} is unusable: {}", voice, e);
+                    false
+                }
+            };
+            (voice, valid)
+        })
+        .collect()
+}
+
+/// Display strings like "M1 - Male Voice 1"; prefer `get_available_voices_v2`
+#[tauri::command]
+fn get_available_voices() -> Vec<String> {
+    VOICE_STYLES
+        .iter()
+        .map(|id| format!("{} - {}", id, voice_info(id, &[]).display_name))
+        .collect()
+}
+
+/// Every known voice with its gender and whether it's installed, plus any extra
+/// voices found on disk
+#[tauri::command]
+fn get_available_voices_v2() -> Vec<VoiceInfo> {
+    let mut ids: Vec<String> = VOICE_STYLES.iter().map(|id| id.to_string()).collect();
+    for voice in installed_voices() {
+        if !ids.contains(&voice) {
+            ids.push(voice);
+        }
+    }
+
+    let usable = usable_voices();
+    ids.iter().map(|id| voice_info(id, &usable)).collect()
+}
+
+/// `get_available_voices_v2` narrowed to one gender ("M"/"F" or "male"/"female", any case)
+/// and, with `installed_only`, to voices on disk. Style files don't record a language,
+/// so voices can't be filtered by one.
+#[tauri::command]
+fn filter_voices(gender: Option<String>, installed_only: bool) -> Result<Vec<VoiceInfo>, TtsError> {
+    let gender = match gender.as_deref().map(str::to_lowercase).as_deref() {
+        None => None,
+        Some("m" | "male") => Some(VoiceGender::Male),
+        Some("f" | "female") => Some(VoiceGender::Female),
+        Some(_) => {
+            return Err(TtsError::InvalidRequest(format!(
+                "Unknown gender {:?}; expected M or F",
+                gender.unwrap_or_default()
+            )))
+        }
+    };
+
+    Ok(get_available_voices_v2()
+        .into_iter()
+        .filter(|voice| gender.is_none_or(|gender| voice.gender == gender))
+        .filter(|voice| voice.installed || !installed_only)
+        .collect())
+}
+
+/// Where a voice's style file is found. When both are set the downloaded copy is the one
+/// used, see `load_voice_style_uncached`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoiceSource {
+    pub id: String,
+    pub in_downloaded: bool,
+    pub in_bundled: bool,
+}
+
+/// Every known voice plus any found on disk, with which of `downloaded` and `bundled` has it
+fn voice_sources_from(downloaded: &[String], bundled: &[String]) -> Vec<VoiceSource> {
+    let mut ids: Vec<String> = VOICE_STYLES.iter().map(|id| id.to_string()).collect();
+    for voice in downloaded.iter().chain(bundled) {
+        if !ids.contains(voice) {
+            ids.push(voice.clone());
+        }
+    }
+
+    ids.into_iter()
+        .map(|id| VoiceSource {
+            in_downloaded: downloaded.contains(&id),
+            in_bundled: bundled.contains(&id),
+            id,
+        })
+        .collect()
+}
+
+/// Whether each voice comes from the models directory, the app bundle or both
+#[tauri::command]
+fn voice_sources() -> Vec<VoiceSource> {
+    let downloaded = current_models_dir()
+        .map(|models_dir| voices_in_dir(&models_dir))
+        .unwrap_or_default();
+    voice_sources_from(&downloaded, &bundled_voices())
+}
+
+/// Load every built-in voice style into the style cache, skipping (and reporting) any
+/// that fail to load
+#[tauri::command]
+fn preload_voices() -> PreloadVoicesResponse {
+    let mut loaded = 0;
+    let mut failed = Vec::new();
+
+    for voice in VOICE_STYLES {
+        match load_voice_style_for_platform(voice) {
+            Ok(_) => loaded += 1,
+            Err(e) => {
+                error!("Failed to preload voice {}: {}", voice, e);
+                failed.push(voice.to_string());
+            }
+        }
+    }
+
+    info!("Preloaded {} of {} voice styles", loaded, VOICE_STYLES.len());
+    PreloadVoicesResponse { loaded, failed }
+}
+
+#[tauri::command]
+fn get_available_languages() -> Vec<String> {
+    vec![
+        "en - English".to_string(),
+        "ko - Korean".to_string(),
+        "es - Spanish".to_string(),
+        "pt - Portuguese".to_string(),
+        "fr - French".to_string(),
+    ]
+}
+
+/// Quality presets and their step counts, e.g. `[("Fast", 4), ...]`, for a quality picker
+#[tauri::command]
+fn get_quality_presets() -> Vec<(String, usize)> {
+    QualityPreset::ALL
+        .iter()
+        .map(|preset| (preset.name().to_string(), preset.total_step()))
+        .collect()
+}
+
+/// Bare language codes synthesis accepts, e.g. `["en", "ko", ...]`; `get_available_languages`
+/// has display names
+#[tauri::command]
+fn get_supported_languages() -> Vec<String> {
+    tts_helper::AVAILABLE_LANGS.iter().map(|lang| lang.to_string()).collect()
+}
+
+/// Whether `lang` is a language code synthesis accepts
+#[tauri::command]
+fn validate_language(lang: String) -> bool {
+    tts_helper::is_valid_lang(&lang)
+}
+
+/// Returns the text preprocessing options used by the engine
+#[tauri::command]
+fn get_preprocess_options() -> Result<PreprocessOptions, TtsError> {
+    get_tts_engine()?.preprocess_options().map_err(TtsError::Io)
+}
+
+/// Update the text preprocessing options (e.g. Markdown stripping) for subsequent synthesis.
+/// `paragraph_silence` applies where a command has no paragraph pause of its own, e.g. the
+/// queue's `synthesize_chunk`; `synthesize_text` uses the request's `paragraph_silence`.
+#[tauri::command]
+fn set_preprocess_options(options: PreprocessOptions) -> Result<(), TtsError> {
+    if let Some(silence) = options.paragraph_silence {
+        if !silence.is_finite() || silence < 0.0 {
+            return Err(TtsError::InvalidRequest(format!(
+                "paragraph_silence must be a non-negative number of seconds, got {}",
+                silence
+            )));
+        }
+    }
+    if options.symbol_replacements.iter().any(|(from, _)| from.is_empty()) {
+        return Err(TtsError::InvalidRequest(
+            "symbol_replacements can't replace an empty string".to_string(),
+        ));
+    }
+    get_tts_engine()?.set_preprocess_options(options).map_err(TtsError::Io)
+}
+
+/// Returns the denoising step schedule used by the engine
+#[tauri::command]
+fn get_step_schedule() -> Result<StepSchedule, TtsError> {
+    get_tts_engine()?.step_schedule().map_err(TtsError::Io)
+}
+
+/// Change where denoising steps fall for subsequent synthesis, trading speed for quality.
+/// `linear` (the default) spaces `total_step` steps evenly.
+#[tauri::command]
+fn set_step_schedule(schedule: StepSchedule) -> Result<(), TtsError> {
+    schedule.validate()
+        .map_err(|e| TtsError::InvalidRequest(e.to_string()))?;
+    get_tts_engine()?.set_step_schedule(schedule).map_err(TtsError::Io)
+}
+
+/// Returns the cap on synthesized audio length in seconds, if any
+#[tauri::command]
+fn get_max_duration() -> Result<Option<f32>, TtsError> {
+    get_tts_engine()?.max_duration_seconds().map_err(TtsError::Io)
+}
+
+/// Cap how many seconds of audio synthesis may produce, so runaway input or a bad duration
+/// prediction can't exhaust memory. No single chunk runs longer than the cap, and engine
+/// `call`s stop adding chunks before passing it. `None` (the default) removes the cap.
+#[tauri::command]
+fn set_max_duration(max_duration_seconds: Option<f32>) -> Result<(), TtsError> {
+    if let Some(seconds) = max_duration_seconds {
+        if !seconds.is_finite() || seconds <= 0.0 {
+            return Err(TtsError::InvalidRequest(format!(
+                "max_duration_seconds must be a positive number of seconds, got {}",
+                seconds
+            )));
+        }
+    }
+    get_tts_engine()?.set_max_duration_seconds(max_duration_seconds).map_err(TtsError::Io)
+}
 
-    // Encode as WAV
-    let wav_bytes = tts_helper::encode_wav_to_bytes(wav_trimmed, engine.sample_rate)
-        .map_err(|e| format!("WAV encoding failed: {}", e))?;
+/// Wall-clock synthesis times over a benchmark's iterations. `real_time_factor` is seconds
+/// of audio per second of synthesis, so above 1 is faster than real time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub iterations: usize,
+    pub audio_seconds: f32,
+    pub avg_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub real_time_factor: f64,
+}
 
-    // Encode as base64
-    let audio_base64 = base64::engine::general_purpose::STANDARD.encode(&wav_bytes);
+/// Summarize per-iteration times, each producing `audio_seconds` of audio
+fn bench_result(audio_seconds: f32, times_ms: &[f64]) -> BenchResult {
+    let avg_ms = times_ms.iter().sum::<f64>() / times_ms.len() as f64;
+    BenchResult {
+        iterations: times_ms.len(),
+        audio_seconds,
+        avg_ms,
+        min_ms: times_ms.iter().copied().fold(f64::INFINITY, f64::min),
+        max_ms: times_ms.iter().copied().fold(0.0, f64::max),
+        real_time_factor: audio_seconds as f64 / (avg_ms / 1000.0),
+    }
+}
 
-    Ok(SynthesizeResponse {
-        success: true,
-        message: format!("Synthesized {:.2} seconds of audio", duration),
-        audio_base64: Some(audio_base64),
-        duration: Some(duration),
+/// Synthesize the same text to WAV `iterations` times on one engine after an untimed warm-up
+/// run, discarding the audio, for comparing devices and settings. Each run is one
+/// `tts_helper::synthesize_once`, as a library user would make it, and starts from the same
+/// noise so the runs do identical work.
+#[tauri::command]
+async fn benchmark_synthesis(
+    text: String,
+    voice_style: String,
+    language: String,
+    total_step: usize,
+    speed: f32,
+    iterations: usize,
+) -> Result<BenchResult, TtsError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        benchmark_synthesis_blocking(&text, &voice_style, &language, total_step, speed, iterations)
     })
+    .await
+    .map_err(|e| TtsError::Synthesis(format!("Benchmark task failed: {}", e)))?
+}
+
+fn benchmark_synthesis_blocking(
+    text: &str,
+    voice_style: &str,
+    language: &str,
+    total_step: usize,
+    speed: f32,
+    iterations: usize,
+) -> Result<BenchResult, TtsError> {
+    if iterations == 0 || iterations > MAX_BENCHMARK_ITERATIONS {
+        return Err(TtsError::InvalidRequest(format!(
+            "iterations must be between 1 and {}, got {}",
+            MAX_BENCHMARK_ITERATIONS, iterations
+        )));
+    }
+    let pool = get_tts_engine()?;
+    ensure_language_valid(language)?;
+    ensure_voice_installed(voice_style)?;
+    ensure_text_speakable(text)?;
+    if total_step == 0 {
+        return Err(TtsError::InvalidRequest("total_step must be at least 1".to_string()));
+    }
+    if !speed.is_finite() || speed <= 0.0 {
+        return Err(TtsError::InvalidRequest(format!("speed must be a positive number, got {}", speed)));
+    }
+    let style = load_voice_style_for_platform(voice_style).map_err(TtsError::Io)?;
+
+    let mut engine = pool.acquire().map_err(TtsError::Synthesis)?;
+    engine.set_seed(Some(BENCHMARK_SEED));
+    let mut times_ms = Vec::with_capacity(iterations);
+    let mut audio_seconds = 0.0;
+    // The first run is the warm-up
+    let result = (0..=iterations).try_for_each(|i| {
+        let start = Instant::now();
+        let (_, duration) = tts_helper::synthesize_once(
+            &mut engine,
+            text,
+            language,
+            &style,
+            total_step,
+            speed,
+            ChunkSilence::uniform(DEFAULT_SILENCE_DURATION),
+        )
+        .map_err(|e| TtsError::Synthesis(format!("Synthesis failed: {:#}", e)))?;
+        if i > 0 {
+            times_ms.push(start.elapsed().as_secs_f64() * 1000.0);
+            audio_seconds = duration;
+        }
+        if i == 0 && engine.truncated() {
+            warn!("Benchmark text runs past the duration cap; timing only the audio that fits");
+        }
+        Ok::<(), TtsError>(())
+    });
+    // The engine goes back to the pool, where synthesis should be random again
+    engine.set_seed(None);
+    result?;
+
+    let bench = bench_result(audio_seconds, &times_ms);
+    info!(
+        "Benchmark: {} runs, avg {:.0} ms, {:.2}x real time",
+        bench.iterations, bench.avg_ms, bench.real_time_factor
+    );
+    Ok(bench)
 }
 
-/// Synthesize a single sentence/chunk - used by the queue system
+/// Set how many chunks may be synthesized in parallel. Each extra slot holds its own copy of
+/// the ONNX sessions, loaded the first time it's needed, so memory use grows with `n`.
 #[tauri::command]
-fn synthesize_chunk(req: SynthesizeChunkRequest) -> SynthesizeChunkResponse {
-    // Get TTS engine
-    let engine = match get_tts_engine() {
-        Ok(e) => e,
-        Err(err) => return SynthesizeChunkResponse {
-            success: false,
-            sentence_index: req.sentence_index,
-            audio_base64: None,
-            duration: None,
-            error: Some(err),
-        },
-    };
+fn set_synthesis_concurrency(n: usize) -> Result<(), TtsError> {
+    let n = n.max(1);
+    SYNTHESIS_CONCURRENCY.store(n, Ordering::Relaxed);
 
-    let mut engine = match engine.lock() {
-        Ok(e) => e,
-        Err(e) => return SynthesizeChunkResponse {
-            success: false,
-            sentence_index: req.sentence_index,
-            audio_base64: None,
-            duration: None,
-            error: Some(format!("Lock error: {}", e)),
-        },
-    };
+    if let Ok(pool) = get_tts_engine() {
+        pool.set_capacity(n).map_err(TtsError::Io)?;
+    }
 
-    // Load voice style
-    let style = match load_voice_style_for_platform(&req.voice_style) {
-        Ok(s) => s,
-        Err(e) => return SynthesizeChunkResponse {
-            success: false,
-            sentence_index: req.sentence_index,
-            audio_base64: None,
-            duration: None,
-            error: Some(e),
-        },
-    };
+    info!("Synthesis concurrency set to {}", n);
+    Ok(())
+}
 
-    // Synthesize this single chunk (call uses internal chunking, but our text is already a chunk)
-    let (wav, duration) = match engine.call(
-        &req.text,
-        &req.language,
-        &style,
-        req.total_step,
-        req.speed,
-        0.0, // No silence padding for individual chunks
-    ) {
-        Ok(r) => r,
-        Err(e) => return SynthesizeChunkResponse {
-            success: false,
-            sentence_index: req.sentence_index,
-            audio_base64: None,
-            duration: None,
-            error: Some(format!("Synthesis failed: {}", e)),
-        },
-    };
+/// Set the denoising steps used by synthesis requests that leave out `total_step`, so a
+/// settings screen can control quality for every caller. A request's own value still wins.
+#[tauri::command]
+fn set_default_total_step(n: usize) -> Result<(), TtsError> {
+    if n == 0 {
+        return Err(TtsError::InvalidRequest("total_step must be at least 1".to_string()));
+    }
+    DEFAULT_TOTAL_STEP.store(n, Ordering::Relaxed);
+    info!("Default total_step set to {}", n);
+    Ok(())
+}
 
-    // Trim to actual duration
-    let actual_len = (engine.sample_rate as f32 * duration) as usize;
-    let wav_trimmed = &wav[..actual_len.min(wav.len())];
+/// Set the speed multiplier used by synthesis requests that leave out `speed`
+#[tauri::command]
+fn set_default_speed(f: f32) -> Result<(), TtsError> {
+    if !f.is_finite() || f <= 0.0 {
+        return Err(TtsError::InvalidRequest(format!("speed must be a positive number, got {}", f)));
+    }
+    *DEFAULT_SPEED.write().map_err(|e| TtsError::Io(format!("Lock error: {}", e)))? = f;
+    info!("Default speed set to {}", f);
+    Ok(())
+}
 
-    // Encode as WAV
-    let wav_bytes = match tts_helper::encode_wav_to_bytes(wav_trimmed, engine.sample_rate) {
-        Ok(b) => b,
-        Err(e) => return SynthesizeChunkResponse {
-            success: false,
-            sentence_index: req.sentence_index,
-            audio_base64: None,
-            duration: None,
-            error: Some(format!("WAV encoding failed: {}", e)),
-        },
-    };
+/// Set how much ORT optimizes the model graphs as they load. Lower levels start faster and
+/// synthesize slower; useful on low-end devices and while developing. Takes effect the next
+/// time models load, e.g. on `reload_tts_engine` or as the pool adds an engine.
+#[tauri::command]
+fn set_optimization_level(level: OptimizationLevel) -> Result<(), TtsError> {
+    *OPTIMIZATION_LEVEL.write().map_err(|e| TtsError::Io(format!("Lock error: {}", e)))? = level;
+    info!("Graph optimization level set to {:?}", level);
+    Ok(())
+}
 
-    // Encode as base64
-    let audio_base64 = base64::engine::general_purpose::STANDARD.encode(&wav_bytes);
+/// Load ONNX Runtime from the library at `path` rather than the default location (next to
+/// the app, or `ORT_DYLIB_PATH`), e.g. a GPU-enabled build installed elsewhere. Only works
+/// before any model has loaded: models found at startup load during setup, so an app that
+/// needs this should call it before downloading or initializing models.
+#[tauri::command]
+fn set_onnxruntime_path(path: String) -> Result<(), TtsError> {
+    tts_helper::set_onnxruntime_path(Path::new(&path))
+        .map_err(|e| TtsError::InvalidRequest(format!("{:#}", e)))?;
+    info!("ONNX Runtime loaded from {}", path);
+    Ok(())
+}
 
-    SynthesizeChunkResponse {
-        success: true,
-        sentence_index: req.sentence_index,
-        audio_base64: Some(audio_base64),
-        duration: Some(duration),
-        error: None,
+/// Start the localhost HTTP synthesis endpoint (see `http_server`) and return the bound port.
+/// Pass 0 to pick a free port. Requires the `http-server` feature.
+#[tauri::command]
+fn start_http_server(port: u16) -> Result<u16, TtsError> {
+    #[cfg(feature = "http-server")]
+    {
+        http_server::start(port)
+    }
+
+    #[cfg(not(feature = "http-server"))]
+    {
+        let _ = port;
+        Err(TtsError::Io("HTTP server support not enabled in this build (feature `http-server`)".to_string()))
     }
 }
 
-/// Save audio base64 to a temp file and return the file path
-/// This is needed for the music-notification plugin which plays from URLs
 #[tauri::command]
-fn save_audio_to_file(audio_base64: String, sentence_index: usize) -> Result<String, String> {
-    let app = APP_HANDLE.get()
-        .ok_or("App handle not initialized")?;
+fn get_tts_status() -> String {
+    // Check for initialization error first
+    if let Some(err) = INIT_ERROR.get() {
+        return format!("TTS Engine: ✗ Error\nInitialization failed: {}", err);
+    }
 
-    // Get app data directory for temp audio files
-    let audio_dir = get_models_directory(app)?
-        .parent()
-        .ok_or("Cannot get parent directory")?
-        .join("audio_cache");
+    // A reload keeps the old engine serving until the new one is in, so report that as loaded
+    let pool = get_tts_engine();
+    match load_state() {
+        LoadState::Loading if pool.is_err() => return "TTS Engine: ⏳ Loading models...".to_string(),
+        LoadState::Failed(err) if pool.is_err() => {
+            return format!("TTS Engine: ✗ Error\nLoading models failed: {}", err)
+        }
+        _ => {}
+    }
 
-    // Create directory if it doesn't exist
-    std::fs::create_dir_all(&audio_dir)
-        .map_err(|e| format!("Failed to create audio cache dir: {}", e))?;
+    if let Ok(pool) = pool {
+        let mut status = "TTS Engine: ✓ Loaded and ready\nModels: All 4 ONNX models loaded\nVoices: 10 voice styles available".to_string();
+        if let Ok(Some(warning)) = pool.output_length_warning() {
+            status.push_str(&format!("\nWarning: {}", warning));
+        }
+        status
+    } else {
+        "TTS Engine: ⏳ Not initialized\nModels may need to be downloaded".to_string()
+    }
+}
 
-    // Decode base64
-    let audio_bytes = base64::engine::general_purpose::STANDARD
-        .decode(&audio_base64)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+/// Where the most recent engine load stands: `Uninitialized`, `Loading`, `Ready` or
+/// `Failed` with the reason. During a reload the previous engine keeps serving requests.
+#[tauri::command]
+fn get_load_state() -> LoadState {
+    load_state()
+}
 
-    // Save to file
-    let file_path = audio_dir.join(format!("sentence_{}.wav", sentence_index));
-    std::fs::write(&file_path, &audio_bytes)
-        .map_err(|e| format!("Failed to write audio file: {}", e))?;
+/// Returns the output sample rate of the loaded engine, for setting up Web Audio playback
+#[tauri::command]
+fn get_sample_rate() -> Result<i32, TtsError> {
+    Ok(get_tts_engine()?.sample_rate())
+}
 
-    // Return file:// URL for Android
-    Ok(format!("file://{}", file_path.to_string_lossy()))
+/// Returns the configuration (`tts.json`) of the loaded models, to confirm which variant is in use
+#[tauri::command]
+fn get_engine_config() -> Result<Config, TtsError> {
+    Ok(get_tts_engine()?.config().clone())
 }
 
-/// Clear audio cache directory
+/// Returns the status of downloaded models and the directory path
 #[tauri::command]
-fn clear_audio_cache() -> Result<(), String> {
+fn get_model_status() -> Result<ModelStatus, TtsError> {
     let app = APP_HANDLE.get()
-        .ok_or("App handle not initialized")?;
+        .ok_or(TtsError::NotInitialized)?;
 
-    let audio_dir = get_models_directory(app)?
-        .parent()
-        .ok_or("Cannot get parent directory")?
-        .join("audio_cache");
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    Ok(check_downloaded_models(&models_dir))
+}
 
-    if audio_dir.exists() {
-        std::fs::remove_dir_all(&audio_dir)
-            .map_err(|e| format!("Failed to clear audio cache: {}", e))?;
+/// Total, downloaded and remaining download size, available before a download starts
+#[tauri::command]
+fn get_download_summary() -> Result<DownloadSummary, TtsError> {
+    Ok(download_summary(&get_model_status()?))
+}
+
+/// Delete downloaded model files and voice styles to reclaim storage, returning the fresh status.
+/// Only files on disk are touched: an engine that is already loaded keeps its sessions in
+/// memory (and any in-flight synthesis finishes normally) until the app restarts.
+#[tauri::command]
+fn delete_models() -> Result<ModelStatus, TtsError> {
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
+
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+
+    for sub_dir in ["onnx", "voice_styles"] {
+        let dir = models_dir.join(sub_dir);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)
+                .map_err(|e| TtsError::Io(format!("Failed to delete {}: {}", dir.display(), e)))?;
+        }
+    }
+
+    for file_name in [model_download::MANIFEST_FILE, model_download::CHECKSUMS_FILE] {
+        let path = models_dir.join(file_name);
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .map_err(|e| TtsError::Io(format!("Failed to delete {}: {}", path.display(), e)))?;
+        }
     }
 
+    clear_voice_style_cache();
+    info!("Deleted downloaded models from {}", models_dir.display());
+    emit_models_updated();
+    Ok(check_downloaded_models(&models_dir))
+}
+
+/// Returns list of files that need to be downloaded with their relative paths
+#[tauri::command]
+fn get_download_manifest() -> Vec<String> {
+    current_manifest().paths()
+}
+
+/// Returns the structured model manifest with per-file URLs, sizes, checksums and versions
+#[tauri::command]
+fn get_download_manifest_v2() -> Result<ModelManifest, TtsError> {
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
+
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    Ok(load_model_manifest(&models_dir))
+}
+
+/// Base URL set with `set_model_source`, if any
+fn configured_model_source(models_dir: &Path) -> Option<String> {
+    std::fs::read_to_string(models_dir.join(MODEL_SOURCE_FILE))
+        .ok()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+}
+
+/// Persist the base URL models are downloaded from. `http(s)://` points at a server or CDN,
+/// `file://` at a local mirror directory for fully offline installs.
+#[tauri::command]
+fn set_model_source(base_url: String) -> Result<(), TtsError> {
+    model_download::validate_source_url(&base_url)
+        .map_err(|e| TtsError::InvalidRequest(format!("{:#}", e)))?;
+
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
+
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    std::fs::create_dir_all(&models_dir)
+        .map_err(|e| TtsError::Io(format!("Failed to create {}: {}", models_dir.display(), e)))?;
+    std::fs::write(models_dir.join(MODEL_SOURCE_FILE), base_url.trim())
+        .map_err(|e| TtsError::Io(format!("Failed to save model source: {}", e)))?;
+
+    info!("Model source set to {}", base_url);
     Ok(())
 }
 
-/// Split text into individual sentences for the queue system
+/// Returns the configured model download base URL
 #[tauri::command]
-fn split_text_to_sentences(text: String, _language: String) -> Vec<String> {
-    // Split into actual sentences (not chunks)
-    let sentences = tts_helper::split_sentences(&text);
-    // Filter out empty sentences and trim whitespace
-    sentences
-        .into_iter()
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect()
+fn get_model_source() -> Result<Option<String>, TtsError> {
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
+
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    Ok(configured_model_source(&models_dir))
 }
 
+/// Download every missing manifest file from `{base_url}/{rel_path}` into the models directory,
+/// reporting progress over `channel`. Without `base_url` the source from `set_model_source` is used.
+/// Up to `DOWNLOAD_CONCURRENCY` files download at once; files already on disk are skipped and
+/// interrupted downloads resume from their `.part` file.
+/// Once every file is in place the engine is initialized from them before this returns,
+/// unless one is already loaded.
 #[tauri::command]
-fn get_available_voices() -> Vec<String> {
-    vec![
-        "M1 - Male Voice 1".to_string(),
-        "M2 - Male Voice 2".to_string(),
-        "M3 - Male Voice 3".to_string(),
-        "M4 - Male Voice 4".to_string(),
-        "M5 - Male Voice 5".to_string(),
-        "F1 - Female Voice 1".to_string(),
-        "F2 - Female Voice 2".to_string(),
-        "F3 - Female Voice 3".to_string(),
-        "F4 - Female Voice 4".to_string(),
-        "F5 - Female Voice 5".to_string(),
-    ]
+async fn download_models(base_url: Option<String>, channel: Channel<DownloadProgress>) -> Result<(), TtsError> {
+    download_missing_models(base_url, move |progress| {
+        let _ = channel.send(progress);
+    })
+    .await?;
+    emit_models_updated();
+    init_after_download().await;
+    Ok(())
 }
 
+/// Pick up an interrupted download where it left off. Reports no progress, since this is
+/// typically triggered in the background after a connectivity change; listen for
+/// `EVENT_MODELS_UPDATED` to learn when it's done.
 #[tauri::command]
-fn get_available_languages() -> Vec<String> {
-    vec![
-        "en - English".to_string(),
-        "ko - Korean".to_string(),
-        "es - Spanish".to_string(),
-        "pt - Portuguese".to_string(),
-        "fr - French".to_string(),
-    ]
+async fn resume_download(base_url: Option<String>) -> Result<(), TtsError> {
+    download_missing_models(base_url, |_| {}).await?;
+    emit_models_updated();
+    init_after_download().await;
+    Ok(())
+}
+
+/// Load the engine from freshly downloaded models if startup found none, so the app is
+/// ready without a separate `init_tts_engine_command`. A failure here is only logged:
+/// the download itself succeeded, and the init command reports the error in full.
+async fn init_after_download() {
+    if tts_engine_loaded() {
+        return;
+    }
+
+    let Some(Ok(models_dir)) = APP_HANDLE.get().map(get_models_directory) else {
+        error!("Models downloaded but the models directory could not be found");
+        return;
+    };
+    let loader = engine_loader_for_dir(models_dir.clone());
+    if let Err(e) = tauri::async_runtime::spawn_blocking(move || init_from_downloaded_models(&models_dir, loader)).await {
+        error!("Models downloaded but initialization task failed: {}", e);
+    }
+}
+
+/// The blocking part of `init_after_download`, for models just downloaded into `models_dir`
+fn init_from_downloaded_models(models_dir: &Path, loader: EngineLoader) {
+    if tts_engine_loaded() {
+        return;
+    }
+
+    match init_tts_engine_in(models_dir, false, None, loader) {
+        Ok(_) => info!("TTS engine initialized from downloaded models"),
+        Err(e) => error!("Models downloaded but engine initialization failed: {}", e),
+    }
+}
+
+async fn download_missing_models<F>(base_url: Option<String>, on_progress: F) -> Result<(), TtsError>
+where
+    F: Fn(DownloadProgress) + Send + Sync + 'static,
+{
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
+
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    download_missing_models_in(&models_dir, base_url, on_progress).await
+}
+
+/// Download every manifest file missing from `models_dir`, a few at a time
+async fn download_missing_models_in<F>(
+    models_dir: &Path,
+    base_url: Option<String>,
+    on_progress: F,
+) -> Result<(), TtsError>
+where
+    F: Fn(DownloadProgress) + Send + Sync + 'static,
+{
+    let base_url = resolve_model_source(models_dir, base_url)?;
+    let client = reqwest::Client::new();
+
+    // The manifest and checksum table are optional and tiny, so always refresh them
+    for file_name in [model_download::MANIFEST_FILE, model_download::CHECKSUMS_FILE] {
+        let url = model_download::file_url(&base_url, file_name);
+        let dest = models_dir.join(file_name);
+        if let Err(e) = model_download::download_file(&client, &url, &dest, |_, _| {}).await {
+            info!("No {} available: {:#}", file_name, e);
+        }
+    }
+
+    let manifest = load_model_manifest(models_dir);
+    let space = model_download::check_space(models_dir, &manifest)
+        .map_err(|e| TtsError::Io(format!("{:#}", e)))?;
+    if !space.sufficient {
+        return Err(TtsError::Io(format!(
+            "Not enough storage to download models: need {:.1} MB, only {:.1} MB available",
+            space.required_bytes as f64 / 1_048_576.0,
+            space.available_bytes as f64 / 1_048_576.0
+        )));
+    }
+
+    let files_total = manifest.files.len();
+    let pending: Vec<ManifestFile> = manifest.files
+        .into_iter()
+        .filter(|file| !models_dir.join(&file.path).exists())
+        .collect();
+
+    let tracker = Arc::new(Mutex::new(ProgressTracker::new(&pending, files_total)));
+    let queue = Arc::new(Mutex::new(VecDeque::from(pending)));
+    let on_progress = Arc::new(on_progress);
+
+    let mut workers: Vec<_> = (0..DOWNLOAD_CONCURRENCY)
+        .map(|_| {
+            tauri::async_runtime::spawn(download_worker(
+                client.clone(),
+                base_url.clone(),
+                models_dir.to_path_buf(),
+                queue.clone(),
+                tracker.clone(),
+                on_progress.clone(),
+            ))
+        })
+        .collect();
+
+    // Wait for every worker, bailing out on the first failure. Aborted downloads
+    // keep their `.part` files so a later resume picks up where they stopped.
+    while !workers.is_empty() {
+        let (result, _, remaining) = futures::future::select_all(workers).await;
+        workers = remaining;
+
+        if let Err(msg) = result
+            .map_err(|e| format!("Download task failed: {}", e))
+            .and_then(|r| r)
+        {
+            for worker in &workers {
+                worker.abort();
+            }
+            error!("{}", msg);
+            return Err(TtsError::Io(msg));
+        }
+    }
+
+    let status = check_downloaded_models(models_dir);
+    let snapshot = tracker.lock()
+        .map_err(|e| TtsError::Io(format!("Lock error: {}", e)))?
+        .snapshot();
+    on_progress(DownloadProgress {
+        files_done: status.downloaded_files,
+        ..snapshot
+    });
+
+    if !status.downloaded {
+        return Err(TtsError::Io(format!(
+            "Download incomplete: {} files still missing: {}",
+            status.missing_files.len(),
+            status.missing_files.join(", ")
+        )));
+    }
+
+    Ok(())
 }
 
+/// `base_url`, or the source from `set_model_source` without one, checked to be a usable URL
+fn resolve_model_source(models_dir: &Path, base_url: Option<String>) -> Result<String, TtsError> {
+    let base_url = match base_url {
+        Some(url) => url,
+        None => configured_model_source(models_dir).ok_or_else(|| {
+            TtsError::InvalidRequest("No model source configured. Call set_model_source first.".to_string())
+        })?,
+    };
+    model_download::validate_source_url(&base_url)
+        .map_err(|e| TtsError::InvalidRequest(format!("{:#}", e)))?;
+    Ok(base_url)
+}
+
+/// Download a single manifest file, e.g. one of `get_model_status().missing_files` after a
+/// partial failure, replacing any existing copy. Its checksum is verified when one is known
+/// and a mismatching file is deleted. Paths not in the manifest are rejected.
+/// Without `base_url` the source from `set_model_source` is used.
 #[tauri::command]
-fn get_tts_status() -> String {
-    // Check for initialization error first
-    if let Some(err) = INIT_ERROR.get() {
-        return format!("TTS Engine: ✗ Error\nInitialization failed: {}", err);
+async fn download_model_file(rel_path: String, base_url: Option<String>) -> Result<(), TtsError> {
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
+
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    let manifest = load_model_manifest(&models_dir);
+    let file = manifest.files
+        .iter()
+        .find(|file| file.path == rel_path)
+        .ok_or_else(|| TtsError::InvalidRequest(format!("{} is not in the model manifest", rel_path)))?;
+    let base_url = resolve_model_source(&models_dir, base_url)?;
+
+    info!("Downloading {}", file.path);
+    let dest = models_dir.join(&file.path);
+    let url = file.url(&base_url).map_err(|e| TtsError::InvalidRequest(format!("{:#}", e)))?;
+    model_download::download_file(&reqwest::Client::new(), &url, &dest, |_, _| {})
+        .await
+        .map_err(|e| TtsError::Io(format!("Failed to download {}: {:#}", file.path, e)))?;
+
+    if let Some(expected) = expected_checksums(&models_dir, &manifest).map_err(TtsError::Io)?.remove(&file.path) {
+        let hash_path = dest.clone();
+        let actual = tauri::async_runtime::spawn_blocking(move || model_download::sha256_file(&hash_path))
+            .await
+            .map_err(|e| TtsError::Io(format!("Verification task failed: {}", e)))?
+            .map_err(|e| TtsError::Io(format!("{:#}", e)))?;
+        if actual != expected {
+            let _ = std::fs::remove_file(&dest);
+            return Err(TtsError::Io(format!("Checksum mismatch for {}; file deleted", file.path)));
+        }
     }
 
-    match TTS_ENGINE.get() {
-        Some(_) => "TTS Engine: ✓ Loaded and ready\nModels: All 4 ONNX models loaded\nVoices: 10 voice styles available".to_string(),
-        None => "TTS Engine: ⏳ Not initialized\nModels may need to be downloaded".to_string(),
+    emit_models_updated();
+    Ok(())
+}
+
+/// SHA-256 of a file under the models directory as lowercase hex, for checking a file by
+/// hand without running the startup verification. Paths leaving the models directory are rejected.
+#[tauri::command]
+async fn hash_file(rel_path: String) -> Result<String, TtsError> {
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
+
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    let path = model_download::resolve_model_path(&models_dir, &rel_path)
+        .map_err(|e| TtsError::InvalidRequest(format!("{:#}", e)))?;
+
+    let digest = tauri::async_runtime::spawn_blocking(move || model_download::sha256_file(&path))
+        .await
+        .map_err(|e| TtsError::Io(format!("Hashing task failed: {}", e)))?
+        .map_err(|e| TtsError::Io(format!("{:#}", e)))?;
+    Ok(digest)
+}
+
+/// Download files from the shared queue one at a time until it's empty
+async fn download_worker<F>(
+    client: reqwest::Client,
+    base_url: String,
+    models_dir: PathBuf,
+    queue: Arc<Mutex<VecDeque<ManifestFile>>>,
+    tracker: Arc<Mutex<ProgressTracker>>,
+    on_progress: Arc<F>,
+) -> Result<(), String>
+where
+    F: Fn(DownloadProgress) + Send + Sync + 'static,
+{
+    loop {
+        let next = queue.lock()
+            .map_err(|e| format!("Lock error: {}", e))?
+            .pop_front();
+        let Some(file) = next else {
+            return Ok(());
+        };
+
+        info!("Downloading {}", file.path);
+        let dest = models_dir.join(&file.path);
+        let url = file.url(&base_url).map_err(|e| format!("{:#}", e))?;
+        model_download::download_file(&client, &url, &dest, |bytes, total_bytes| {
+            let progress = match tracker.lock() {
+                Ok(mut tracker) => tracker.update(&file.path, bytes, total_bytes),
+                Err(_) => return,
+            };
+            on_progress(progress);
+        })
+        .await
+        .map_err(|e| format!("Failed to download {}: {:#}", file.path, e))?;
+
+        let progress = tracker.lock()
+            .map_err(|e| format!("Lock error: {}", e))?
+            .finish(&file.path);
+        on_progress(progress);
     }
 }
 
-/// Returns the status of downloaded models and the directory path
+/// Compare the size of the files still to download against the free space on the models volume
 #[tauri::command]
-fn get_model_status() -> Result<ModelStatus, String> {
+fn check_download_space() -> Result<SpaceReport, TtsError> {
     let app = APP_HANDLE.get()
-        .ok_or("App handle not initialized")?;
+        .ok_or(TtsError::NotInitialized)?;
 
-    let models_dir = get_models_directory(app)?;
-    Ok(check_downloaded_models(&models_dir))
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    let manifest = load_model_manifest(&models_dir);
+    model_download::check_space(&models_dir, &manifest)
+        .map_err(|e| TtsError::Io(format!("{:#}", e)))
 }
 
-/// Returns list of files that need to be downloaded with their relative paths
+/// Hash every downloaded model file and return the ones whose SHA-256 doesn't match
+#[tauri::command]
+async fn verify_models() -> Result<Vec<String>, TtsError> {
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
+
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    let mismatched = tauri::async_runtime::spawn_blocking(move || verify_downloaded_models(&models_dir))
+        .await
+        .map_err(|e| TtsError::Io(format!("Verification task failed: {}", e)))?
+        .map_err(TtsError::Io)?;
+    Ok(mismatched)
+}
+
+/// `verify_models`, sending `VerifyProgress` on `channel` while large files are hashed and as
+/// each one finishes. Returns the same mismatches `verify_models` would.
+#[tauri::command]
+async fn verify_models_progress(channel: Channel<VerifyProgress>) -> Result<Vec<String>, TtsError> {
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
+
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    let mismatched = tauri::async_runtime::spawn_blocking(move || {
+        verify_downloaded_models_with_progress(&models_dir, &mut |progress| {
+            let _ = channel.send(progress.clone());
+        })
+    })
+    .await
+    .map_err(|e| TtsError::Io(format!("Verification task failed: {}", e)))?
+    .map_err(TtsError::Io)?;
+    Ok(mismatched)
+}
+
+/// Initialize TTS engine from a user-chosen directory laid out like the models directory
+/// (`onnx/...`, optionally `voice_styles/...`). Voice styles missing there fall back to bundled ones.
+#[tauri::command]
+fn init_tts_engine_from_custom_path(dir: String) -> Result<String, TtsError> {
+    let models_dir = PathBuf::from(&dir);
+    if !models_dir.is_dir() {
+        return Err(TtsError::Io(format!("Not a directory: {}", dir)));
+    }
+
+    let missing = missing_model_files(&models_dir);
+    if !missing.is_empty() {
+        return Err(TtsError::ModelsMissing(missing));
+    }
+
+    // Replaces any engine that's already loaded
+    replace_tts_engine(engine_loader_for_dir(models_dir.clone()), models_dir).map_err(TtsError::Io)?;
+
+    info!("TTS engine initialized from custom directory {}", dir);
+    Ok("TTS engine initialized successfully".to_string())
+}
+
+/// Reload the TTS engine from the current models directory, e.g. after downloading updated
+/// models. Synthesis already in progress finishes on the old engine before the swap.
+#[tauri::command]
+fn reload_tts_engine() -> Result<String, TtsError> {
+    let models_dir = match current_models_dir() {
+        Some(dir) => dir,
+        None => {
+            let app = APP_HANDLE.get()
+                .ok_or(TtsError::NotInitialized)?;
+            get_models_directory(app).map_err(TtsError::Io)?
+        }
+    };
+
+    let missing = missing_model_files(&models_dir);
+    if !missing.is_empty() {
+        return Err(TtsError::ModelsMissing(missing));
+    }
+
+    replace_tts_engine(engine_loader_for_dir(models_dir.clone()), models_dir.clone()).map_err(TtsError::Io)?;
+
+    info!("TTS engine reloaded from {}", models_dir.display());
+    Ok("TTS engine reloaded successfully".to_string())
+}
+
+/// Trial-load every downloaded model and run a tiny inference, naming the model that fails
 #[tauri::command]
-fn get_download_manifest() -> Vec<String> {
-    let mut files = Vec::new();
+async fn validate_models() -> Result<(), TtsError> {
+    let app = APP_HANDLE.get()
+        .ok_or(TtsError::NotInitialized)?;
 
-    // Add ONNX model files
-    for (file_path, _name) in MODEL_FILES {
-        files.push(file_path.to_string());
-    }
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    tauri::async_runtime::spawn_blocking(move || validate_downloaded_models(&models_dir))
+        .await
+        .map_err(|e| TtsError::Io(format!("Validation task failed: {}", e)))?
+        .map_err(TtsError::Io)?;
+    Ok(())
+}
 
-    // Add voice style files
-    for voice in VOICE_STYLES {
-        files.push(format!("voice_styles/{}.json", voice));
+/// Check the bundled model files are present and intact without loading them, so a broken
+/// bundle is reported per file instead of as a confusing ORT error at load time
+#[tauri::command]
+fn self_test() -> Result<SelfTestReport, TtsError> {
+    APP_HANDLE.get().ok_or(TtsError::NotInitialized)?;
+    let report = self_test_report(&read_bundled_asset_head);
+    if !report.passed {
+        for check in report.components.iter().filter(|check| !check.passed) {
+            warn!("Bundled {} failed self-test: {}", check.file, check.error.as_deref().unwrap_or_default());
+        }
     }
-
-    files
+    Ok(report)
 }
 
-/// Initialize TTS engine after models have been downloaded
+/// Initialize TTS engine after models have been downloaded.
+/// Pass `validate: true` to trial-run the models first, at the cost of loading them twice.
+/// With `load_timeout_secs`, gives up with `LoadTimeout` if loading the engine takes longer,
+/// so the UI can offer a retry; an engine finishing after that is discarded.
 #[tauri::command]
-fn init_tts_engine_command() -> Result<String, String> {
+async fn init_tts_engine_command(validate: Option<bool>, load_timeout_secs: Option<u64>) -> Result<String, TtsError> {
+    tauri::async_runtime::spawn_blocking(move || init_tts_engine(validate.unwrap_or(false), load_timeout_secs))
+        .await
+        .map_err(|e| TtsError::Io(format!("Initialization task failed: {}", e)))?
+}
+
+fn init_tts_engine(validate: bool, load_timeout_secs: Option<u64>) -> Result<String, TtsError> {
     let app = APP_HANDLE.get()
-        .ok_or("App handle not initialized")?;
+        .ok_or(TtsError::NotInitialized)?;
 
-    let models_dir = get_models_directory(app)?;
-    let status = check_downloaded_models(&models_dir);
+    let models_dir = get_models_directory(app).map_err(TtsError::Io)?;
+    let loader = engine_loader_for_dir(models_dir.clone());
+    init_tts_engine_in(&models_dir, validate, load_timeout_secs, loader)
+}
+
+/// `init_tts_engine` for the models in `models_dir`, loading them with `loader`
+fn init_tts_engine_in(
+    models_dir: &Path,
+    validate: bool,
+    load_timeout_secs: Option<u64>,
+    loader: EngineLoader,
+) -> Result<String, TtsError> {
+    let status = check_downloaded_models(models_dir);
 
     if !status.downloaded {
-        return Err(format!(
-            "Cannot initialize: {} files still missing",
-            status.missing_files.len()
-        ));
+        return Err(TtsError::ModelsMissing(status.missing_files));
     }
 
-    // Platform-specific initialization from downloaded files
-    #[cfg(not(target_os = "android"))]
-    {
-        let onnx_dir = models_dir.join("onnx");
-        init_tts_engine_from_path(&onnx_dir, &models_dir)?;
+    let mismatched = verify_downloaded_models(models_dir).map_err(TtsError::Io)?;
+    if !mismatched.is_empty() {
+        return Err(TtsError::Io(format!(
+            "Cannot initialize: checksum mismatch for {}. Please delete and re-download these files.",
+            mismatched.join(", ")
+        )));
     }
 
-    #[cfg(target_os = "android")]
-    {
-        let read_downloaded = |rel_path: &str| -> Result<Vec<u8>, String> {
-            let full_path = models_dir.join(rel_path);
-            std::fs::read(&full_path)
-                .map_err(|e| format!("Failed to read {}: {}", full_path.display(), e))
-        };
+    if validate {
+        validate_downloaded_models(models_dir).map_err(TtsError::Io)?;
+    }
 
-        let model_bytes = ModelBytes {
-            config: read_downloaded("onnx/tts.json")?,
-            unicode_indexer: read_downloaded("onnx/unicode_indexer.json")?,
-            duration_predictor: read_downloaded("onnx/duration_predictor.onnx")?,
-            text_encoder: read_downloaded("onnx/text_encoder.onnx")?,
-            vector_estimator: read_downloaded("onnx/vector_estimator.onnx")?,
-            vocoder: read_downloaded("onnx/vocoder.onnx")?,
+    if tts_engine_loaded() {
+        info!("TTS engine already initialized");
+    } else if let Some(secs) = load_timeout_secs {
+        set_load_state(LoadState::Loading);
+        let loaded = run_with_timeout(move || loader().map(|engine| (engine, loader)), Duration::from_secs(secs));
+        let result = match loaded {
+            Ok(loaded) => loaded
+                .and_then(|(engine, loader)| install_tts_engine(engine, loader))
+                .map_err(TtsError::Io),
+            Err(RecvTimeoutError::Timeout) => Err(TtsError::LoadTimeout(secs)),
+            Err(RecvTimeoutError::Disconnected) => Err(TtsError::Io("Model loading panicked".to_string())),
         };
-
-        init_tts_engine_from_bytes(model_bytes)?;
+        if result.is_ok() {
+            set_models_dir(models_dir.to_path_buf());
+        }
+        finish_load_state(&result);
+        result?;
+    } else {
+        replace_tts_engine(loader, models_dir.to_path_buf()).map_err(TtsError::Io)?;
     }
 
     Ok("TTS engine initialized successfully".to_string())
 }
 
+/// Change the log level at runtime, e.g. to "debug" while reproducing a bug. Accepts
+/// off, error, warn, info, debug or trace (any case).
+#[tauri::command]
+fn set_log_level(level: String) -> Result<(), TtsError> {
+    let filter: log::LevelFilter = level.trim().parse().map_err(|_| {
+        TtsError::InvalidRequest(format!(
+            "Unknown log level {:?}; expected off, error, warn, info, debug or trace",
+            level
+        ))
+    })?;
+    log::set_max_level(filter);
+    info!("Log level set to {}", filter);
+    Ok(())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Initialize Android logger
     #[cfg(target_os = "android")]
     {
+        // The logger itself lets everything through so `set_log_level` can raise the
+        // level later; the global max level does the filtering
         android_logger::init_once(
             android_logger::Config::default()
-                .with_max_level(log::LevelFilter::Info)
+                .with_max_level(log::LevelFilter::Trace)
                 .with_tag("TTS_POC"),
         );
+        log::set_max_level(log::LevelFilter::Info);
         info!("Android logger initialized");
     }
 
@@ -736,23 +4270,1283 @@ pub fn run() {
                 }
             }
 
+            if PRELOAD_VOICES_ON_INIT && tts_engine_loaded() {
+                // Also finds corrupt voice files before anyone picks them
+                std::thread::spawn(|| {
+                    preload_voices();
+                    check_voice_styles();
+                });
+            }
+
             info!("Tauri setup complete");
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             greet,
+            schema_version,
             synthesize_text,
+            synthesize_text_progress,
+            validate_request,
             synthesize_chunk,
+            synthesize_many,
+            synthesize_pcm,
+            get_wav_header,
+            synthesize_with_subtitles,
+            synthesize_with_word_timings,
+            synthesize_with_phoneme_timings,
+            synthesize_with_waveform,
+            synthesize_verbose,
+            synthesize_with_custom_style,
+            save_custom_voice_style,
+            synthesize_segments,
+            synthesize_dialogue,
+            preview_normalized_text,
+            normalization_key,
+            preview_chunks,
+            debug_tokenize,
+            get_indexer_stats,
+            char_is_supported,
+            estimate_duration,
+            synthesize_token_ids,
             split_text_to_sentences,
             save_audio_to_file,
+            synthesize_to_file,
+            synthesize_to_pcm_file,
+            synthesize_phrase_clips,
+            synthesize_to_file_streaming,
+            export_document,
+            cancel_export,
             clear_audio_cache,
+            list_audio_cache,
+            set_audio_cache_limit,
+            concatenate_audio_cache,
             get_available_voices,
+            list_installed_voices,
+            check_voice_styles,
+            set_active_voice,
+            get_available_voices_v2,
+            filter_voices,
+            voice_sources,
+            preload_voices,
             get_available_languages,
+            get_supported_languages,
+            get_quality_presets,
+            validate_language,
+            set_log_level,
+            get_preprocess_options,
+            set_preprocess_options,
+            get_step_schedule,
+            set_step_schedule,
+            get_max_duration,
+            set_max_duration,
+            set_synthesis_concurrency,
+            set_default_total_step,
+            set_default_speed,
+            benchmark_synthesis,
+            set_optimization_level,
+            set_onnxruntime_path,
+            start_http_server,
             get_tts_status,
+            get_load_state,
+            get_sample_rate,
+            get_engine_config,
             get_model_status,
+            get_download_summary,
             get_download_manifest,
+            get_download_manifest_v2,
+            delete_models,
+            set_model_source,
+            get_model_source,
+            download_models,
+            download_model_file,
+            hash_file,
+            resume_download,
+            check_download_space,
+            verify_models,
+            verify_models_progress,
+            validate_models,
+            self_test,
+            init_tts_engine_from_custom_path,
+            reload_tts_engine,
             init_tts_engine_command
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_test_report_flags_each_broken_file() {
+        let read_head = |file: &str, _max_bytes: u64| -> Result<Vec<u8>, String> {
+            match file {
+                "onnx/vocoder.onnx" => Ok(b"<html>".to_vec()),
+                "onnx/unicode_indexer.json" => Err("missing".to_string()),
+                f if f.ends_with(".onnx") => Ok(vec![0x08, 0x07]),
+                _ => Ok(b"{}".to_vec()),
+            }
+        };
+        let report = self_test_report(&read_head);
+
+        assert!(!report.passed);
+        let failed: Vec<&str> = report.components.iter().filter(|c| !c.passed).map(|c| c.file.as_str()).collect();
+        assert_eq!(failed, ["onnx/tts.json", "onnx/unicode_indexer.json", "onnx/vocoder.onnx"]);
+    }
+
+    #[test]
+    fn step_and_speed_checks_reject_out_of_range_values() {
+        assert!(ensure_total_step_valid(1).is_ok());
+        assert!(matches!(ensure_total_step_valid(0), Err(TtsError::InvalidRequest(_))));
+
+        assert!(ensure_speed_valid(1.05).is_ok());
+        for speed in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(ensure_speed_valid(speed), Err(TtsError::InvalidRequest(_))), "{}", speed);
+        }
+
+        // WPM replaces the multiplier, so only the one in use is checked
+        assert!(ensure_speaking_rate_valid(0.0, Some(150.0)).is_ok());
+        assert!(matches!(ensure_speaking_rate_valid(1.0, Some(0.0)), Err(TtsError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn missing_model_source_is_an_invalid_request() {
+        let dir = std::env::temp_dir().join("tts_no_model_source");
+        std::fs::create_dir_all(&dir).unwrap();
+        let _ = std::fs::remove_file(dir.join(MODEL_SOURCE_FILE));
+
+        assert!(matches!(resolve_model_source(&dir, None), Err(TtsError::InvalidRequest(_))));
+        assert!(matches!(
+            resolve_model_source(&dir, Some("not a url".to_string())),
+            Err(TtsError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn bundled_checksums_match_the_shipped_voice_styles() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        for voice in VOICE_STYLES {
+            let path = format!("voice_styles/{}.json", voice);
+            let expected = bundled_checksum(&path).unwrap();
+            assert_eq!(model_download::sha256_file(&assets.join(&path)).unwrap(), expected, "{}", path);
+        }
+
+        let manifest = builtin_manifest();
+        for (path, digest) in BUNDLED_CHECKSUMS {
+            assert_eq!(manifest.checksums().get(*path).map(String::as_str), Some(*digest));
+        }
+    }
+
+    #[test]
+    fn text_without_letters_or_digits_is_not_speakable() {
+        for text in ["", "   ", "😀🎉", "[pause:500]"] {
+            assert!(matches!(ensure_text_speakable(text), Err(TtsError::InvalidRequest(_))), "{:?}", text);
+        }
+        assert!(ensure_text_speakable("Hi 😀").is_ok());
+        assert!(ensure_text_speakable("[pause:500] 42").is_ok());
+    }
+
+    /// Serializes tests that swap the global engine, voice or defaults, in any module
+    static GLOBALS: Mutex<()> = Mutex::new(());
+
+    pub(crate) fn lock_globals() -> std::sync::MutexGuard<'static, ()> {
+        GLOBALS.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Every `emit_event` call since the last `take_events`, as (event, payload); tests have
+    /// no app handle to emit through
+    static EVENTS: Mutex<Vec<(String, serde_json::Value)>> = Mutex::new(Vec::new());
+
+    pub(crate) fn record_event<S: Serialize>(event: &str, payload: &S) {
+        let payload = serde_json::to_value(payload).unwrap_or_default();
+        EVENTS.lock().unwrap_or_else(|e| e.into_inner()).push((event.to_string(), payload));
+    }
+
+    fn take_events() -> Vec<(String, serde_json::Value)> {
+        std::mem::take(&mut *EVENTS.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// A models directory holding only a "stub" voice, matching `tts_helper::stub::style()`
+    fn stub_models_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join("tts_stub_models");
+        std::fs::create_dir_all(dir.join("voice_styles")).unwrap();
+        let component = r#"{"data": [[[0.0]]], "dims": [1, 1, 1], "type": "float32"}"#;
+        std::fs::write(
+            dir.join("voice_styles/stub.json"),
+            format!(r#"{{"style_ttl": {}, "style_dp": {}}}"#, component, component),
+        )
+        .unwrap();
+        dir
+    }
+
+    /// Install a pool of stub engines with `capacity` engines, with "stub" as the active
+    /// voice and the only installed one
+    pub(crate) fn install_stub_engine(capacity: usize) {
+        SYNTHESIS_CONCURRENCY.store(capacity, Ordering::Relaxed);
+        *TTS_ENGINE.write().unwrap() = None;
+        install_tts_engine(tts_helper::stub::engine(), Box::new(|| Ok(tts_helper::stub::engine()))).unwrap();
+        set_models_dir(stub_models_dir());
+        *ACTIVE_VOICE.lock().unwrap() = Some(("stub".to_string(), Arc::new(tts_helper::stub::style())));
+    }
+
+    fn chunk_request(text: &str, sentence_index: usize) -> SynthesizeChunkRequest {
+        SynthesizeChunkRequest {
+            text: text.to_string(),
+            sentence_index,
+            language: "en".to_string(),
+            voice_style: "stub".to_string(),
+            total_step: Some(2),
+            speed: Some(1.0),
+            gain_db: None,
+            pipeline: None,
+        }
+    }
+
+    #[test]
+    fn overlapping_chunk_syntheses_each_get_their_own_audio() {
+        let _globals = lock_globals();
+        install_stub_engine(2);
+
+        let short = chunk_request("Hi there.", 0);
+        let long = chunk_request("This sentence is quite a bit longer.", 1);
+        // One at a time first, for reference
+        let serial_short = synthesize_chunk_blocking(chunk_request(&short.text, 0));
+        let serial_long = synthesize_chunk_blocking(chunk_request(&long.text, 1));
+
+        let (first, second) = std::thread::scope(|scope| {
+            let first = scope.spawn(|| synthesize_chunk_blocking(short));
+            let second = scope.spawn(|| synthesize_chunk_blocking(long));
+            (first.join().unwrap(), second.join().unwrap())
+        });
+
+        for (response, serial, index) in [(first, serial_short, 0), (second, serial_long, 1)] {
+            assert!(response.success, "{:?}", response.error);
+            assert_eq!(response.sentence_index, index);
+            assert_eq!(response.audio_base64, serial.audio_base64);
+            assert_eq!(response.duration, serial.duration);
+        }
+    }
+
+    #[test]
+    fn omitted_steps_and_speed_fall_back_to_the_stored_defaults() {
+        let _globals = lock_globals();
+        let (engine, steps) = tts_helper::stub::counting_engine();
+        *TTS_ENGINE.write().unwrap() = None;
+        SYNTHESIS_CONCURRENCY.store(1, Ordering::Relaxed);
+        install_tts_engine(engine, Box::new(|| Ok(tts_helper::stub::engine()))).unwrap();
+
+        let request = |fields: &str| -> CustomStyleSynthesizeRequest {
+            serde_json::from_str(&format!(
+                r#"{{"text": "Hello there.", "language": "en", {}
+                    "style_ttl": [0], "ttl_dims": [1, 1, 1], "style_dp": [0], "dp_dims": [1, 1, 1]}}"#,
+                fields
+            ))
+            .unwrap()
+        };
+
+        set_default_total_step(3).unwrap();
+        set_default_speed(2.0).unwrap();
+        let defaulted = synthesize_with_custom_style(request("")).unwrap().duration.unwrap();
+        assert_eq!(steps.swap(0, Ordering::Relaxed), 3);
+
+        // Explicit values still win
+        let explicit = synthesize_with_custom_style(request(r#""total_step": 7, "speed": 1.0,"#)).unwrap().duration.unwrap();
+        assert_eq!(steps.swap(0, Ordering::Relaxed), 7);
+        assert!((explicit / defaulted - 2.0).abs() < 0.1, "{} vs {}", explicit, defaulted);
+
+        set_default_total_step(5).unwrap();
+        set_default_speed(1.05).unwrap();
+    }
+
+    fn synthesize_request(fields: &str) -> SynthesizeRequest {
+        let mut req = serde_json::json!({"text": "Hello there.", "language": "en", "voice_style": "stub"});
+        let overrides: serde_json::Value = serde_json::from_str(fields).unwrap();
+        for (key, value) in overrides.as_object().unwrap() {
+            req[key] = value.clone();
+        }
+        serde_json::from_value(req).unwrap()
+    }
+
+    #[test]
+    fn validate_request_reports_the_first_problem() {
+        let _globals = lock_globals();
+        *TTS_ENGINE.write().unwrap() = None;
+        assert!(matches!(validate_request(synthesize_request("{}")), Err(TtsError::NotInitialized)));
+
+        install_stub_engine(1);
+        assert!(validate_request(synthesize_request("{}")).is_ok());
+
+        let err = validate_request(synthesize_request(r#"{"language": "xx"}"#)).unwrap_err();
+        assert!(matches!(&err, TtsError::InvalidLanguage(lang) if lang == "xx"), "{:?}", err);
+
+        let err = validate_request(synthesize_request(r#"{"voice_style": "Z9"}"#)).unwrap_err();
+        assert!(matches!(&err, TtsError::VoiceNotFound(voice) if voice == "Z9"), "{:?}", err);
+
+        let err = validate_request(synthesize_request(r#"{"text": "  "}"#)).unwrap_err();
+        assert!(matches!(err, TtsError::InvalidRequest(_)), "{:?}", err);
+
+        let err = validate_request(synthesize_request(r#"{"total_step": 0}"#)).unwrap_err();
+        assert!(matches!(&err, TtsError::InvalidRequest(msg) if msg.contains("total_step")), "{:?}", err);
+
+        for speed in ["0", "-1.5"] {
+            let err = validate_request(synthesize_request(&format!(r#"{{"speed": {}}}"#, speed))).unwrap_err();
+            assert!(matches!(&err, TtsError::InvalidRequest(msg) if msg.contains("speed")), "{:?}", err);
+        }
+
+        // The first problem wins
+        let err = validate_request(synthesize_request(r#"{"language": "xx", "total_step": 0}"#)).unwrap_err();
+        assert!(matches!(err, TtsError::InvalidLanguage(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn chunk_with_bad_steps_or_speed_fails_without_waiting_for_an_engine() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+        let pool = get_tts_engine().unwrap();
+        // The only engine is busy, so reaching `acquire` would block
+        let _busy = pool.acquire().unwrap();
+
+        for (total_step, speed, problem) in [(Some(0), Some(1.0), "total_step"), (Some(2), Some(0.0), "speed")] {
+            let mut req = chunk_request("Hello there.", 3);
+            req.total_step = total_step;
+            req.speed = speed;
+            let response = run_with_timeout(move || synthesize_chunk_blocking(req), Duration::from_secs(5))
+                .expect("waited for an engine");
+            assert!(!response.success);
+            assert_eq!(response.sentence_index, 3);
+            assert!(response.error.as_deref().unwrap_or_default().contains(problem), "{:?}", response.error);
+        }
+    }
+
+    #[test]
+    fn reload_waits_for_in_flight_synthesis_and_keeps_the_pool_options() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+        let old = get_tts_engine().unwrap();
+        let options = PreprocessOptions { keep_emoji: true, ..PreprocessOptions::default() };
+        old.set_preprocess_options(options).unwrap();
+        old.set_max_duration_seconds(Some(30.0)).unwrap();
+        assert!(synthesize_chunk_blocking(chunk_request("Before the reload.", 0)).success);
+
+        let busy = old.acquire().unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let reload = std::thread::spawn(move || {
+            let reloaded = replace_tts_engine(Box::new(|| Ok(tts_helper::stub::engine())), stub_models_dir());
+            sender.send(reloaded).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err(), "reload didn't wait for the busy engine");
+
+        drop(busy);
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        reload.join().unwrap();
+
+        let new = get_tts_engine().unwrap();
+        assert!(!Arc::ptr_eq(&old, &new));
+        assert!(new.preprocess_options().unwrap().keep_emoji);
+        assert_eq!(new.max_duration_seconds().unwrap(), Some(30.0));
+        assert!(synthesize_chunk_blocking(chunk_request("After the reload.", 1)).success);
+    }
+
+    /// A finished download: every file its manifest lists is in place and matches
+    fn downloaded_models_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join("tts_downloaded_models");
+        std::fs::create_dir_all(dir.join("onnx")).unwrap();
+        std::fs::write(dir.join("onnx/model.onnx"), b"abc").unwrap();
+        std::fs::write(
+            dir.join(model_download::MANIFEST_FILE),
+            r#"{"version": "1", "files": [{"name": "model", "path": "onnx/model.onnx", "sha256":
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"}]}"#,
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn completed_download_initializes_the_engine() {
+        let _globals = lock_globals();
+        *TTS_ENGINE.write().unwrap() = None;
+        set_load_state(LoadState::Uninitialized);
+        assert!(get_tts_status().contains("Not initialized"));
+
+        let dir = downloaded_models_dir();
+        init_from_downloaded_models(&dir, Box::new(|| Ok(tts_helper::stub::engine())));
+        assert!(get_tts_status().contains("Loaded and ready"), "{}", get_tts_status());
+        assert_eq!(load_state(), LoadState::Ready);
+        assert_eq!(current_models_dir(), Some(dir));
+    }
+
+    #[test]
+    fn load_state_goes_through_loading_to_ready_or_failed() {
+        let _globals = lock_globals();
+        *TTS_ENGINE.write().unwrap() = None;
+        set_load_state(LoadState::Uninitialized);
+
+        // The loader waits for the go-ahead, so the state can be checked mid-load
+        let (go, wait) = std::sync::mpsc::channel::<()>();
+        let wait = Mutex::new(wait);
+        let load = std::thread::spawn(move || {
+            let loader: EngineLoader = Box::new(move || {
+                wait.lock().unwrap().recv().map_err(|e| e.to_string())?;
+                Ok(tts_helper::stub::engine())
+            });
+            replace_tts_engine(loader, stub_models_dir())
+        });
+        while load_state() == LoadState::Uninitialized {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(load_state(), LoadState::Loading);
+        assert!(get_tts_status().contains("Loading"));
+
+        go.send(()).unwrap();
+        load.join().unwrap().unwrap();
+        assert_eq!(load_state(), LoadState::Ready);
+
+        let failed = replace_tts_engine(Box::new(|| Err("vocoder.onnx is corrupt".to_string())), stub_models_dir());
+        assert!(failed.is_err());
+        assert_eq!(load_state(), LoadState::Failed("vocoder.onnx is corrupt".to_string()));
+        // The engine already loaded keeps serving
+        assert!(tts_engine_loaded());
+    }
+
+    #[test]
+    fn files_without_a_digest_are_listed_for_the_warning() {
+        let dir = std::env::temp_dir().join("tts_unverifiable_models");
+        std::fs::create_dir_all(&dir).unwrap();
+        let _ = std::fs::remove_file(dir.join(model_download::CHECKSUMS_FILE));
+
+        let manifest = builtin_manifest();
+        let expected = expected_checksums(&dir, &manifest).unwrap();
+        let unverifiable = files_without_checksum(&manifest, &expected);
+        // The bundled table covers the voice styles only
+        assert!(unverifiable.contains(&"onnx/vocoder.onnx".to_string()), "{:?}", unverifiable);
+        assert!(!unverifiable.iter().any(|path| path.starts_with("voice_styles/")), "{:?}", unverifiable);
+    }
+
+    /// Write a mirror with a manifest listing `count` small files and return its directory
+    fn model_mirror(name: &str, count: usize) -> PathBuf {
+        let mirror = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&mirror);
+        let mut files = Vec::new();
+        for i in 0..count {
+            let path = format!("onnx/part_{}.onnx", i);
+            std::fs::create_dir_all(mirror.join("onnx")).unwrap();
+            std::fs::write(mirror.join(&path), vec![i as u8; 1000 + i]).unwrap();
+            files.push(format!(r#"{{"name": "part_{}", "path": "{}", "size": {}}}"#, i, path, 1000 + i));
+        }
+        std::fs::write(
+            mirror.join(model_download::MANIFEST_FILE),
+            format!(r#"{{"version": "1", "files": [{}]}}"#, files.join(", ")),
+        )
+        .unwrap();
+        mirror
+    }
+
+    /// Serve files from `root` over HTTP on a local port, 404 for anything missing
+    fn serve_dir(root: PathBuf) -> String {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let root = root.clone();
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+                    let mut header = String::new();
+                    while reader.read_line(&mut header).unwrap() > 2 {
+                        header.clear();
+                    }
+
+                    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+                    let (status, body) = match std::fs::read(root.join(path.trim_start_matches('/'))) {
+                        Ok(body) => ("200 OK", body),
+                        Err(_) => ("404 Not Found", Vec::new()),
+                    };
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        status,
+                        body.len()
+                    );
+                    let _ = stream.write_all(&body);
+                });
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn parallel_download_fetches_every_file_and_counts_them_all() {
+        let mirror = model_mirror("tts_http_mirror", 7);
+        let url = serve_dir(mirror.clone());
+        let dir = std::env::temp_dir().join("tts_http_download");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        tauri::async_runtime::block_on(download_missing_models_in(&dir, Some(url), move |progress| {
+            seen.lock().unwrap().push(progress);
+        }))
+        .unwrap();
+
+        for i in 0..7 {
+            let path = format!("onnx/part_{}.onnx", i);
+            assert_eq!(std::fs::read(dir.join(&path)).unwrap(), std::fs::read(mirror.join(&path)).unwrap());
+        }
+        let reports = reports.lock().unwrap();
+        let last = reports.last().unwrap();
+        assert_eq!((last.files_done, last.files_total), (7, 7));
+        assert_eq!(last.overall_bytes, (0..7).map(|i| 1000 + i).sum::<u64>());
+        assert!(reports.windows(2).all(|w| w[0].files_done <= w[1].files_done));
+    }
+
+    #[test]
+    fn file_source_copies_the_mirror_into_the_models_directory() {
+        let mirror = model_mirror("tts_file_mirror", 3);
+        let source = reqwest::Url::from_directory_path(&mirror).unwrap().to_string();
+        let dir = std::env::temp_dir().join("tts_file_download");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        tauri::async_runtime::block_on(download_missing_models_in(&dir, Some(source), |_| {})).unwrap();
+
+        for path in ["manifest.json", "onnx/part_0.onnx", "onnx/part_1.onnx", "onnx/part_2.onnx"] {
+            let copied = std::fs::read(dir.join(path)).unwrap();
+            assert_eq!(copied, std::fs::read(mirror.join(path)).unwrap(), "{}", path);
+        }
+        assert!(check_downloaded_models(&dir).downloaded);
+    }
+
+    #[test]
+    fn pcm_response_holds_four_bytes_per_trimmed_sample() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+
+        let req = synthesize_request("{}");
+        let (trimmed, _duration, sample_rate) = synthesize_trimmed(&req).unwrap();
+        let pcm = synthesize_pcm(req).unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(&pcm.samples_f32_base64).unwrap();
+
+        assert!(!trimmed.is_empty());
+        assert_eq!(bytes.len(), 4 * trimmed.len());
+        assert_eq!((pcm.sample_rate, pcm.channels), (sample_rate, 1));
+        assert_eq!(f32::from_le_bytes(bytes[..4].try_into().unwrap()), trimmed[0]);
+    }
+
+    #[test]
+    fn synthesize_to_file_writes_a_wav_of_the_synthesized_length() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+
+        let dir = std::env::temp_dir().join("tts_to_file");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("nested/hello.wav");
+        let req = synthesize_request("{}");
+        let (trimmed, _duration, sample_rate) = synthesize_trimmed(&req).unwrap();
+
+        let url = synthesize_to_file(req, Some(path.to_string_lossy().to_string())).unwrap();
+        assert_eq!(url, format!("file://{}", path.to_string_lossy()));
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().sample_rate, sample_rate as u32);
+        assert_eq!(reader.duration() as usize, trimmed.len());
+    }
+
+    #[test]
+    fn unknown_voice_fails_before_an_engine_is_taken() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+        assert_eq!(list_installed_voices(), vec!["stub".to_string()]);
+
+        // The pool's only engine stays busy, so reaching for one would block this test
+        let pool = get_tts_engine().unwrap();
+        let _busy = pool.acquire().unwrap();
+        let err = synthesize_pcm(synthesize_request(r#"{"voice_style": "Z9"}"#)).unwrap_err();
+        assert!(matches!(&err, TtsError::VoiceNotFound(voice) if voice == "Z9"), "{:?}", err);
+        assert_eq!(err.to_string(), "Voice Z9 not available");
+    }
+
+    #[test]
+    fn voice_metadata_has_gender_and_installed_state_from_disk() {
+        let _globals = lock_globals();
+        let dir = std::env::temp_dir().join("tts_voice_metadata");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("voice_styles")).unwrap();
+        std::fs::write(dir.join("voice_styles/M1.json"), "{}").unwrap();
+        set_models_dir(dir);
+        BROKEN_VOICES.lock().unwrap().clear();
+
+        let voices = get_available_voices_v2();
+        let voice = |id: &str| voices.iter().find(|v| v.id == id).unwrap();
+        assert_eq!(voice("M1").gender, VoiceGender::Male);
+        assert_eq!(voice("M1").display_name, "Male Voice 1");
+        assert!(voice("M1").installed);
+        assert_eq!(voice("M2").gender, VoiceGender::Male);
+        assert!(!voice("M2").installed);
+        assert_eq!(voice("F3").gender, VoiceGender::Female);
+        assert!(!voice("F3").installed);
+    }
+
+    #[test]
+    fn longer_silence_adds_its_difference_between_each_pair_of_chunks() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+
+        // Three sentences too long to share a chunk
+        let sentence = "This sentence keeps going with plenty of words so it fills most of a chunk by itself ".repeat(2);
+        let text = format!("{0}one. {0}two. {0}three.", sentence);
+        assert_eq!(tts_helper::count_synthesis_chunks(&text, "en"), 3);
+        let duration = |silence: f32| {
+            let fields = serde_json::json!({"text": text, "silence_duration": silence}).to_string();
+            synthesize_text(synthesize_request(&fields)).unwrap().duration.unwrap()
+        };
+
+        let extra = duration(1.3) - duration(0.3);
+        assert!((extra - 2.0).abs() < 0.01, "{}", extra);
+
+        let err = synthesize_text(synthesize_request(r#"{"silence_duration": -1.0}"#)).unwrap_err();
+        assert!(matches!(err, TtsError::InvalidRequest(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn pause_marker_inserts_that_much_silence() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+
+        let longest_silence = |text: &str| {
+            let fields = serde_json::json!({"text": text}).to_string();
+            let (wav, _, sample_rate) = synthesize_trimmed(&synthesize_request(&fields)).unwrap();
+            let longest = wav.split(|&s| s != 0.0).map(<[f32]>::len).max().unwrap_or(0);
+            longest as f32 / sample_rate as f32
+        };
+
+        let pause = longest_silence("Hello there. [pause:1000] Goodbye now.");
+        assert!((pause - 1.0).abs() < 0.01, "{}", pause);
+        // Invalid markers are dropped, leaving only the usual gap between sentences
+        assert!(longest_silence("Hello there. [pause:-5] Goodbye now.") < 0.5);
+        assert_eq!(tts_helper::split_pause_markers("a [pause:abc] b"), vec![("a   b".to_string(), 0.0)]);
+    }
+
+    #[test]
+    fn unsupported_character_is_reported_as_out_of_vocabulary() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+
+        // The stub vocabulary ends at Hangul, so a private-use character is unknown
+        let fields = serde_json::json!({"text": "Hello \u{E000} there."}).to_string();
+        let response = synthesize_text(synthesize_request(&fields)).unwrap();
+        assert_eq!(response.oov_chars, vec!["\u{E000}".to_string()]);
+
+        assert!(synthesize_text(synthesize_request("{}")).unwrap().oov_chars.is_empty());
+    }
+
+    #[test]
+    fn saved_voice_style_loads_back_to_the_same_arrays() {
+        let _globals = lock_globals();
+        let dir = std::env::temp_dir().join("tts_saved_voices");
+        let _ = std::fs::remove_dir_all(&dir);
+        set_models_dir(dir.clone());
+
+        let ttl: Vec<f32> = (0..12).map(|i| i as f32 * 0.25 - 1.0).collect();
+        let dp: Vec<f32> = (0..6).map(|i| -(i as f32) / 3.0).collect();
+        let style = tts_helper::load_voice_style_from_arrays(ttl, [1, 3, 4], dp, [1, 2, 3]).unwrap();
+
+        let path = save_voice_style("my_blend", &style, false).unwrap();
+        assert_eq!(PathBuf::from(&path), dir.join("voice_styles/my_blend.json"));
+        let loaded = tts_helper::load_voice_style_from_bytes(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(loaded.ttl, style.ttl);
+        assert_eq!(loaded.dp, style.dp);
+
+        // Built-in names need force
+        assert!(matches!(save_voice_style("M1", &style, false), Err(TtsError::InvalidRequest(_))));
+        assert!(save_voice_style("M1", &style, true).is_ok());
+    }
+
+    #[test]
+    fn second_synthesis_with_a_voice_uses_the_cached_style() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+        clear_voice_style_cache();
+
+        let dir = std::env::temp_dir().join("tts_cached_voice");
+        std::fs::create_dir_all(dir.join("voice_styles")).unwrap();
+        let style_path = dir.join("voice_styles/cached.json");
+        std::fs::copy(stub_models_dir().join("voice_styles/stub.json"), &style_path).unwrap();
+        set_models_dir(dir);
+
+        let req = || synthesize_request(r#"{"voice_style": "cached"}"#);
+        synthesize_text(req()).unwrap();
+        // Still installed, but no longer parseable: only the cache can serve it now
+        std::fs::write(&style_path, "not json").unwrap();
+        synthesize_text(req()).unwrap();
+
+        clear_voice_style_cache();
+        assert!(matches!(synthesize_text(req()), Err(TtsError::Io(_))));
+    }
+
+    #[test]
+    fn preloading_caches_every_installed_voice() {
+        let _globals = lock_globals();
+        clear_voice_style_cache();
+
+        let dir = std::env::temp_dir().join("tts_preloaded_voices");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("voice_styles")).unwrap();
+        let style = stub_models_dir().join("voice_styles/stub.json");
+        for voice in ["M1", "F2"] {
+            std::fs::copy(&style, dir.join(format!("voice_styles/{}.json", voice))).unwrap();
+        }
+        set_models_dir(dir);
+
+        let report = preload_voices();
+        assert_eq!(report.loaded, 2);
+        assert_eq!(report.failed.len(), VOICE_STYLES.len() - 2);
+        assert!(!report.failed.iter().any(|voice| voice == "M1" || voice == "F2"));
+
+        let cached: Vec<String> = VOICE_STYLE_CACHE.lock().unwrap().keys().cloned().collect();
+        assert_eq!(cached, installed_voices());
+    }
+
+    #[test]
+    fn exported_document_lasts_as_long_as_its_sentences_and_the_gap() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+
+        let sentence_duration = |text: &str| {
+            let fields = serde_json::json!({"text": text}).to_string();
+            synthesize_trimmed(&synthesize_request(&fields)).unwrap().1
+        };
+        let expected = sentence_duration("Hello there.") + 0.3 + sentence_duration("This is the second sentence.");
+
+        let path = std::env::temp_dir().join("tts_export/document.wav");
+        let fields = r#"{"text": "Hello there. This is the second sentence.", "silence_duration": 0.3}"#;
+        let progress = Mutex::new(Vec::new());
+        let output_path = Some(path.to_string_lossy().to_string());
+        let export = export_document_blocking(&synthesize_request(fields), output_path, |p| {
+            progress.lock().unwrap().push((p.sentences_done, p.sentences_total));
+        })
+        .unwrap();
+
+        assert!((export.duration - expected).abs() < 0.01, "{} vs {}", export.duration, expected);
+        let reader = hound::WavReader::open(&path).unwrap();
+        let file_duration = reader.duration() as f32 / reader.spec().sample_rate as f32;
+        assert!((file_duration - expected).abs() < 0.01, "{} vs {}", file_duration, expected);
+        assert_eq!(progress.into_inner().unwrap(), vec![(0, 2), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn three_chunks_report_progress_three_times_in_order() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+
+        let fields = serde_json::json!({"text": "First paragraph.\n\nSecond one here.\n\nAnd the third."}).to_string();
+        let mut events = Vec::new();
+        let response = synthesize_text_reporting(&synthesize_request(&fields), &mut |p| events.push(p)).unwrap();
+
+        let counts: Vec<(usize, usize)> = events.iter().map(|p| (p.chunks_done, p.total_chunks)).collect();
+        assert_eq!(counts, vec![(1, 3), (2, 3), (3, 3)]);
+        assert!(events.windows(2).all(|pair| pair[0].duration_done < pair[1].duration_done));
+        // Speech only: the silence between chunks isn't counted
+        assert!(events[2].duration_done < response.duration.unwrap());
+    }
+
+    #[test]
+    fn language_validation_matches_the_synthesis_check() {
+        assert!(validate_language("en".to_string()));
+        assert!(!validate_language("de".to_string()));
+        for lang in get_supported_languages() {
+            assert!(tts_helper::is_valid_lang(&lang), "{}", lang);
+            assert!(validate_language(lang));
+        }
+        assert!(!get_supported_languages().contains(&"de".to_string()));
+    }
+
+    #[test]
+    fn model_status_reports_each_file_size_and_partial_downloads() {
+        let dir = std::env::temp_dir().join("tts_model_sizes");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("onnx")).unwrap();
+        std::fs::write(
+            dir.join(model_download::MANIFEST_FILE),
+            r#"{"version": "1", "files": [
+                {"name": "encoder", "path": "onnx/encoder.onnx", "size": 1234},
+                {"name": "vocoder", "path": "onnx/vocoder.onnx", "size": 5000}]}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("onnx/encoder.onnx"), vec![0u8; 1234]).unwrap();
+        std::fs::write(dir.join("onnx/vocoder.onnx.part"), vec![0u8; 100]).unwrap();
+
+        let status = check_downloaded_models(&dir);
+        assert_eq!(status.file_sizes, vec![("onnx/encoder.onnx".to_string(), 1234)]);
+        assert_eq!(status.downloaded_bytes, 1234 + 100);
+        assert_eq!(status.total_expected_bytes, Some(6234));
+        assert_eq!(status.missing_files, vec!["onnx/vocoder.onnx".to_string()]);
+    }
+
+    #[test]
+    fn slow_model_load_gives_up_after_the_timeout() {
+        let _globals = lock_globals();
+        *TTS_ENGINE.write().unwrap() = None;
+        let dir = downloaded_models_dir();
+
+        let slow: EngineLoader = Box::new(|| {
+            std::thread::sleep(Duration::from_secs(3));
+            Ok(tts_helper::stub::engine())
+        });
+        let started = Instant::now();
+        let err = init_tts_engine_in(&dir, false, Some(1), slow).unwrap_err();
+        assert!(matches!(err, TtsError::LoadTimeout(1)), "{:?}", err);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(!tts_engine_loaded());
+        assert!(matches!(load_state(), LoadState::Failed(_)));
+
+        // A load within the limit goes ahead as usual
+        init_tts_engine_in(&dir, false, Some(5), Box::new(|| Ok(tts_helper::stub::engine()))).unwrap();
+        assert!(tts_engine_loaded());
+    }
+
+    #[test]
+    fn log_level_can_be_raised_at_runtime() {
+        let _globals = lock_globals();
+        let previous = log::max_level();
+
+        set_log_level("debug".to_string()).unwrap();
+        assert_eq!(log::max_level(), log::LevelFilter::Debug);
+        set_log_level(" TRACE ".to_string()).unwrap();
+        assert_eq!(log::max_level(), log::LevelFilter::Trace);
+
+        let err = set_log_level("verbose".to_string()).unwrap_err();
+        assert!(matches!(err, TtsError::InvalidRequest(_)), "{:?}", err);
+        assert_eq!(log::max_level(), log::LevelFilter::Trace);
+
+        log::set_max_level(previous);
+    }
+
+    #[test]
+    fn collect_metrics_times_every_model_run() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+
+        let req = synthesize_request(r#"{"collect_metrics": true}"#);
+        let metrics = synthesize_text(req).unwrap().metrics.unwrap();
+        assert!(metrics.chunks > 0);
+        for ms in [metrics.dp_ms, metrics.text_enc_ms, metrics.denoise_ms, metrics.vocoder_ms] {
+            assert!(ms > 0.0, "{:?}", metrics);
+        }
+
+        // Off by default: nothing is reported, and the engine isn't left timing runs
+        assert!(synthesize_text(synthesize_request("{}")).unwrap().metrics.is_none());
+        let pool = get_tts_engine().unwrap();
+        let mut engine = pool.acquire().unwrap();
+        engine.take_metrics();
+        engine.synthesize_chunk("Hello there.", "en", &tts_helper::stub::style(), 2, SpeakingRate::Speed(1.0)).unwrap();
+        assert_eq!(engine.take_metrics().dp_ms, 0.0);
+    }
+
+    #[test]
+    fn filter_voices_narrows_by_gender_and_install_state() {
+        let _globals = lock_globals();
+        let dir = std::env::temp_dir().join("tts_filter_voices");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("voice_styles")).unwrap();
+        std::fs::write(dir.join("voice_styles/F2.json"), "{}").unwrap();
+        std::fs::write(dir.join("voice_styles/M1.json"), "{}").unwrap();
+        set_models_dir(dir);
+        BROKEN_VOICES.lock().unwrap().clear();
+        let ids = |voices: Vec<VoiceInfo>| voices.into_iter().map(|voice| voice.id).collect::<Vec<_>>();
+
+        assert_eq!(ids(filter_voices(Some("female".to_string()), false).unwrap()), ["F1", "F2", "F3", "F4", "F5"]);
+        assert_eq!(ids(filter_voices(Some("M".to_string()), false).unwrap()), ["M1", "M2", "M3", "M4", "M5"]);
+        assert_eq!(ids(filter_voices(None, true).unwrap()), ["M1", "F2"]);
+        assert_eq!(ids(filter_voices(Some("f".to_string()), true).unwrap()), ["F2"]);
+        assert_eq!(filter_voices(None, false).unwrap().len(), VOICE_STYLES.len());
+        assert!(matches!(filter_voices(Some("x".to_string()), false), Err(TtsError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn max_chars_stops_synthesis_early_and_flags_it() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+        let text = "A paragraph to preview.\n\n".repeat(10);
+
+        let full = synthesize_text(synthesize_request(&format!(r#"{{"text": {:?}}}"#, text))).unwrap();
+        let req = synthesize_request(&format!(r#"{{"text": {:?}, "max_chars": 30}}"#, text));
+        let preview = synthesize_text(req).unwrap();
+
+        assert!(!full.truncated);
+        assert!(preview.truncated);
+        let (preview, full) = (preview.duration.unwrap(), full.duration.unwrap());
+        assert!(preview < full / 3.0, "{} vs {}", preview, full);
+    }
+
+    #[test]
+    fn voice_sources_tell_downloaded_and_bundled_voices_apart() {
+        let source = |sources: &[VoiceSource], id: &str| {
+            let source = sources.iter().find(|source| source.id == id).unwrap();
+            (source.in_downloaded, source.in_bundled)
+        };
+        let downloaded = ["F1".to_string(), "custom".to_string()];
+        let bundled = ["F1".to_string(), "M1".to_string()];
+
+        let sources = voice_sources_from(&downloaded, &bundled);
+        assert_eq!(source(&sources, "custom"), (true, false));
+        assert_eq!(source(&sources, "F1"), (true, true));
+        assert_eq!(source(&sources, "M1"), (false, true));
+        assert_eq!(source(&sources, "F2"), (false, false));
+        assert_eq!(sources.len(), VOICE_STYLES.len() + 1);
+
+        // The command reads the models directory; no app bundle is available in tests
+        let _globals = lock_globals();
+        let dir = std::env::temp_dir().join("tts_voice_sources");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("voice_styles")).unwrap();
+        std::fs::write(dir.join("voice_styles/M3.json"), "{}").unwrap();
+        set_models_dir(dir);
+        assert_eq!(source(&voice_sources(), "M3"), (true, false));
+    }
+
+    #[test]
+    fn sentence_cache_keeps_only_the_most_recent_files() {
+        let dir = std::env::temp_dir().join("tts_sentence_cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let start = std::time::SystemTime::now() - std::time::Duration::from_secs(100);
+        for i in 0..6 {
+            let file = std::fs::File::create(dir.join(format!("sentence_{}.wav", i))).unwrap();
+            file.set_modified(start + std::time::Duration::from_secs(i)).unwrap();
+        }
+        std::fs::write(dir.join("export.wav"), "").unwrap();
+        let remaining = || {
+            let mut indices: Vec<usize> = std::fs::read_dir(&dir)
+                .unwrap()
+                .filter_map(|entry| sentence_cache_index(&entry.unwrap().path()))
+                .collect();
+            indices.sort();
+            indices
+        };
+
+        assert_eq!(prune_sentence_cache(&dir, 4, None), Ok(2));
+        assert_eq!(remaining(), [2, 3, 4, 5]);
+        assert!(dir.join("export.wav").exists());
+
+        // The file just written survives even when it isn't the newest by timestamp
+        assert_eq!(prune_sentence_cache(&dir, 2, Some(&dir.join("sentence_2.wav"))), Ok(2));
+        assert_eq!(remaining(), [2, 5]);
+        assert_eq!(prune_sentence_cache(&dir, 2, None), Ok(0));
+
+        assert_eq!(sentence_cache_index(Path::new("/cache/sentence_12.wav")), Some(12));
+        assert_eq!(sentence_cache_index(Path::new("/cache/sentence_x.wav")), None);
+        assert_eq!(sentence_cache_index(Path::new("/cache/sentence_3.mp3")), None);
+    }
+
+    #[test]
+    fn download_summary_counts_what_is_left() {
+        let dir = std::env::temp_dir().join("tts_download_summary");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("onnx")).unwrap();
+        let manifest = |vocoder_size: &str| {
+            format!(
+                r#"{{"version": "1", "files": [
+                    {{"name": "encoder", "path": "onnx/encoder.onnx", "size": 1234}},
+                    {{"name": "vocoder", "path": "onnx/vocoder.onnx"{}}}]}}"#,
+                vocoder_size
+            )
+        };
+        std::fs::write(dir.join(model_download::MANIFEST_FILE), manifest(r#", "size": 5000"#)).unwrap();
+        std::fs::write(dir.join("onnx/encoder.onnx"), vec![0u8; 1234]).unwrap();
+        std::fs::write(dir.join("onnx/vocoder.onnx.part"), vec![0u8; 100]).unwrap();
+
+        let summary = download_summary(&check_downloaded_models(&dir));
+        assert_eq!(
+            summary,
+            DownloadSummary {
+                total_files: 2,
+                total_bytes: Some(6234),
+                already_downloaded_bytes: Some(1334),
+                remaining_bytes: Some(6234 - 1334),
+            }
+        );
+
+        // Without every file's size only the file count is known
+        std::fs::write(dir.join(model_download::MANIFEST_FILE), manifest("")).unwrap();
+        let summary = download_summary(&check_downloaded_models(&dir));
+        assert_eq!((summary.total_files, summary.total_bytes, summary.remaining_bytes), (2, None, None));
+        assert_eq!(summary.already_downloaded_bytes, None);
+    }
+
+    #[test]
+    fn verbose_chunks_and_silences_add_up_to_the_duration() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+
+        let text = "First paragraph here.\n\nSecond one, a little longer.\n\nAnd a third.";
+        let req = synthesize_request(&format!(r#"{{"text": {:?}, "silence_duration": 0.4}}"#, text));
+        let response = synthesize_verbose(req).unwrap();
+
+        let texts: Vec<&str> = response.chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(texts, ["First paragraph here.", "Second one, a little longer.", "And a third."]);
+        assert!(response.chunks.iter().all(|chunk| chunk.duration > 0.0));
+        assert!(response.chunks[..2].iter().all(|chunk| chunk.silence_after > 0.3), "{:?}", response.chunks);
+
+        let total = response.chunks[0].start
+            + response.chunks.iter().map(|chunk| chunk.duration + chunk.silence_after).sum::<f32>();
+        assert!((total - response.duration).abs() < 1e-3, "{} vs {}", total, response.duration);
+    }
+
+    #[test]
+    fn optimization_level_applies_to_later_loads() {
+        let _globals = lock_globals();
+        assert_eq!(load_options().optimization_level, OptimizationLevel::Level3);
+
+        let level: OptimizationLevel = serde_json::from_str(r#""Disable""#).unwrap();
+        set_optimization_level(level).unwrap();
+        assert_eq!(load_options().optimization_level, OptimizationLevel::Disable);
+
+        set_optimization_level(OptimizationLevel::default()).unwrap();
+        assert_eq!(optimization_level(), OptimizationLevel::Level3);
+    }
+
+    #[test]
+    fn benchmark_reports_positive_times_and_real_time_factor() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+
+        let bench = benchmark_synthesis_blocking("Hello there.", "stub", "en", 2, 1.0, 3).unwrap();
+        assert_eq!(bench.iterations, 3);
+        assert!(bench.audio_seconds > 0.0);
+        assert!(bench.min_ms > 0.0 && bench.min_ms <= bench.avg_ms && bench.avg_ms <= bench.max_ms, "{:?}", bench);
+        assert!(bench.real_time_factor > 0.0);
+
+        for iterations in [0, MAX_BENCHMARK_ITERATIONS + 1] {
+            let err = benchmark_synthesis_blocking("Hello there.", "stub", "en", 2, 1.0, iterations).unwrap_err();
+            assert!(matches!(err, TtsError::InvalidRequest(_)), "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn cached_sentences_are_listed_in_sentence_order() {
+        let dir = std::env::temp_dir().join("tts_cached_sentences");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // As `save_audio_to_file` names them, plus files the listing should ignore
+        std::fs::write(dir.join("sentence_10.wav"), vec![0u8; 64]).unwrap();
+        std::fs::write(dir.join("sentence_2.wav"), vec![0u8; 48]).unwrap();
+        std::fs::write(dir.join("export.wav"), "").unwrap();
+        std::fs::write(dir.join("sentence_3.wav.tmp"), "").unwrap();
+
+        let cached = cached_sentences(&dir).unwrap();
+        assert_eq!(
+            cached,
+            vec![
+                CachedAudio {
+                    sentence_index: 2,
+                    file_url: format!("file://{}", dir.join("sentence_2.wav").display()),
+                    size_bytes: 48,
+                },
+                CachedAudio {
+                    sentence_index: 10,
+                    file_url: format!("file://{}", dir.join("sentence_10.wav").display()),
+                    size_bytes: 64,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn init_announces_ready_once_and_failures_as_load_failed() {
+        let _globals = lock_globals();
+        *TTS_ENGINE.write().unwrap() = None;
+        let dir = downloaded_models_dir();
+        take_events();
+
+        init_tts_engine_in(&dir, false, Some(5), Box::new(|| Ok(tts_helper::stub::engine()))).unwrap();
+        // Already loaded, so nothing new to announce
+        init_tts_engine_in(&dir, false, Some(5), Box::new(|| Ok(tts_helper::stub::engine()))).unwrap();
+        let ready = EngineReady { sample_rate: tts_helper::stub::SAMPLE_RATE, voice_count: installed_voices().len() };
+        assert_eq!(take_events(), [(EVENT_READY.to_string(), serde_json::to_value(ready).unwrap())]);
+
+        *TTS_ENGINE.write().unwrap() = None;
+        let err = init_tts_engine_in(&dir, false, Some(5), Box::new(|| Err("corrupt model".to_string()))).unwrap_err();
+        assert_eq!(take_events(), [(EVENT_LOAD_FAILED.to_string(), serde_json::json!(err.to_string()))]);
+    }
+
+    #[test]
+    fn requests_with_only_the_original_fields_still_deserialize() {
+        let req: SynthesizeRequest = serde_json::from_str(
+            r#"{"text": "Hello.", "language": "en", "voice_style": "F1", "total_step": 5, "speed": 1.05}"#,
+        )
+        .unwrap();
+        assert_eq!((req.text.as_str(), req.total_step, req.speed), ("Hello.", Some(5), Some(1.05)));
+        assert!(!req.collect_metrics && req.max_chars.is_none() && req.silence_duration.is_none());
+
+        let chunk: SynthesizeChunkRequest = serde_json::from_str(
+            r#"{"text": "Hello.", "sentence_index": 3, "language": "en", "voice_style": "F1",
+                "total_step": 5, "speed": 1.05}"#,
+        )
+        .unwrap();
+        assert_eq!(chunk.sentence_index, 3);
+
+        // A response from before schema versions reads as version 0
+        let response: SynthesizeResponse = serde_json::from_str(
+            r#"{"success": true, "message": "ok", "audio_base64": null, "duration": 1.5}"#,
+        )
+        .unwrap();
+        assert_eq!(response.schema_version, 0);
+        assert_eq!(schema_version(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn corrupt_voice_style_is_reported_and_hidden() {
+        let _globals = lock_globals();
+        let dir = std::env::temp_dir().join("tts_corrupt_voice");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("voice_styles")).unwrap();
+        std::fs::copy(stub_models_dir().join("voice_styles/stub.json"), dir.join("voice_styles/F1.json")).unwrap();
+        std::fs::write(dir.join("voice_styles/M1.json"), r#"{"style_ttl": "#).unwrap();
+        set_models_dir(dir);
+        BROKEN_VOICES.lock().unwrap().clear();
+
+        assert_eq!(check_voice_styles(), [("F1".to_string(), true), ("M1".to_string(), false)]);
+        assert_eq!(list_installed_voices(), ["F1"]);
+        let installed = |id: &str| {
+            get_available_voices_v2().into_iter().find(|voice| voice.id == id).unwrap().installed
+        };
+        assert!(installed("F1"));
+        assert!(!installed("M1"));
+        BROKEN_VOICES.lock().unwrap().clear();
+    }
+
+    #[test]
+    fn active_voice_synthesizes_without_reading_its_style_file() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+        set_active_voice("stub".to_string()).unwrap();
+
+        // With the file and the style cache gone, only the active voice can supply the style
+        VOICE_STYLE_CACHE.lock().unwrap().clear();
+        std::fs::remove_file(stub_models_dir().join("voice_styles/stub.json")).unwrap();
+        for i in 0..2 {
+            let response = synthesize_chunk_blocking(chunk_request("Hello there.", i));
+            assert!(response.success, "{:?}", response.error);
+        }
+
+        // Another voice isn't active, so it's looked up and found missing
+        let response = synthesize_chunk_blocking(SynthesizeChunkRequest {
+            voice_style: "F1".to_string(),
+            ..chunk_request("Hello there.", 2)
+        });
+        assert!(!response.success);
+        assert!(set_active_voice("F1".to_string()).is_err());
+    }
+
+    #[test]
+    fn normalization_key_ignores_spacing_but_not_parameters() {
+        let _globals = lock_globals();
+        install_stub_engine(1);
+        let key = |text: &str, voice: &str, speed: f32, seed: Option<u64>| {
+            normalization_key(text.to_string(), "en".to_string(), voice.to_string(), 5, speed, seed).unwrap()
+        };
+
+        let base = key("Hi!", "F1", 1.0, None);
+        assert_eq!(base.len(), 64);
+        assert_eq!(key("Hi !", "F1", 1.0, None), base);
+        assert_eq!(key("  Hi!  ", "F1", 1.0, None), base);
+
+        assert_ne!(key("Hello!", "F1", 1.0, None), base);
+        assert_ne!(key("Hi!", "M1", 1.0, None), base);
+        assert_ne!(key("Hi!", "F1", 1.1, None), base);
+        assert_ne!(key("Hi!", "F1", 1.0, Some(7)), base);
+    }
+
+    #[test]
+    fn unavailable_provider_is_rejected_by_name() {
+        let _globals = lock_globals();
+        for provider in [None, Some("cpu"), Some("CPU")] {
+            assert!(ensure_provider_available(provider).is_ok(), "{:?}", provider);
+        }
+        let err = ensure_provider_available(Some("coreml")).unwrap_err();
+        assert!(
+            matches!(&err, TtsError::InvalidRequest(msg) if msg.contains("coreml") && msg.contains("cpu")),
+            "{:?}",
+            err
+        );
+
+        // The request fails up front instead of quietly running on the CPU
+        install_stub_engine(1);
+        let err = synthesize_text(synthesize_request(r#"{"provider": "coreml"}"#)).unwrap_err();
+        assert!(matches!(&err, TtsError::InvalidRequest(msg) if msg.contains("coreml")), "{:?}", err);
+        assert!(synthesize_text(synthesize_request(r#"{"provider": "cpu"}"#)).is_ok());
+    }
+
+    #[test]
+    fn paragraph_silence_defaults_to_the_longer_pause() {
+        let silence = chunk_silence(Some(0.2), None).unwrap();
+        assert_eq!((silence.sentence, silence.paragraph), (0.2, DEFAULT_PARAGRAPH_SILENCE));
+
+        // A sentence pause longer than the default paragraph pause carries over to paragraphs
+        let silence = chunk_silence(Some(1.5), None).unwrap();
+        assert_eq!((silence.sentence, silence.paragraph), (1.5, 1.5));
+
+        // An explicit paragraph pause is used as is, even when shorter
+        let silence = chunk_silence(Some(0.5), Some(0.1)).unwrap();
+        assert_eq!((silence.sentence, silence.paragraph), (0.5, 0.1));
+
+        for paragraph in [-0.5, f32::NAN, f32::INFINITY] {
+            let err = chunk_silence(None, Some(paragraph)).unwrap_err();
+            assert!(
+                matches!(&err, TtsError::InvalidRequest(msg) if msg.contains("paragraph_silence")),
+                "{:?}",
+                err
+            );
+        }
+        assert!(chunk_silence(Some(-1.0), Some(1.0)).is_err());
+    }
+
+    #[test]
+    fn pipeline_stages_with_unusable_parameters_are_rejected() {
+        let pipeline = |stages: Vec<AudioStage>| AudioPipeline { stages };
+        assert!(ensure_pipeline_valid(None).is_ok());
+        assert!(ensure_pipeline_valid(Some(&pipeline(vec![
+            AudioStage::RemoveDc,
+            AudioStage::Highpass { cutoff_hz: 80.0 },
+            AudioStage::Trim(TrimConfig::default()),
+            AudioStage::Normalize { peak: 1.0 },
+            AudioStage::Gain { db: -6.0 },
+        ])))
+        .is_ok());
+
+        let bad_stages = [
+            (AudioStage::Highpass { cutoff_hz: 0.0 }, "highpass"),
+            (AudioStage::Trim(TrimConfig { threshold_db: 3.0, ..TrimConfig::default() }), "trim"),
+            (AudioStage::Normalize { peak: 1.5 }, "normalize"),
+            (AudioStage::Gain { db: f32::NAN }, "gain"),
+            (AudioStage::Attenuate { peak: 0.0 }, "attenuate"),
+        ];
+        for (stage, name) in bad_stages {
+            // A bad stage is caught wherever it sits in the list
+            let err = ensure_pipeline_valid(Some(&pipeline(vec![AudioStage::RemoveDc, stage]))).unwrap_err();
+            assert!(
+                matches!(&err, TtsError::InvalidRequest(msg) if msg.starts_with("pipeline:") && msg.contains(name)),
+                "{:?}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn timings_move_back_by_the_trimmed_start() {
+        let timing = |start, end| ChunkTiming { text: "x".to_string(), start, end };
+        // 2 seconds left after cutting 0.5 seconds at 100 Hz
+        let wav = vec![0.0; 200];
+        let (duration, timings) =
+            shift_timings(&wav, 100, 50, vec![timing(0.2, 1.0), timing(1.0, 2.0), timing(2.0, 2.8)]);
+
+        assert_eq!(duration, 2.0);
+        let spans: Vec<(f32, f32)> = timings.iter().map(|t| (t.start, t.end)).collect();
+        // Clamped at the start of the audio and at its new end
+        assert_eq!(spans, vec![(0.0, 0.5), (0.5, 1.5), (1.5, 2.0)]);
+    }
+}
@@ -0,0 +1,828 @@
+// ============================================================================
+// Model Download Module - Fetching model files over HTTP
+// ============================================================================
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use anyhow::{Result, Context, anyhow, bail};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Structured list of model files, downloaded alongside the models or bundled with the app
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Optional table of expected SHA-256 digests, keyed by manifest-relative path
+pub const CHECKSUMS_FILE: &str = "checksums.json";
+
+/// Suffix for in-progress downloads; these never count as present files
+pub const PART_SUFFIX: &str = ".part";
+
+/// Minimum number of bytes between two progress reports for the same file
+const PROGRESS_INTERVAL_BYTES: u64 = 256 * 1024;
+
+/// Minimum number of bytes hashed between two verification progress reports; hashing runs
+/// far faster than downloading, so reports are spaced further apart
+const VERIFY_PROGRESS_INTERVAL_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Progress report sent to the frontend while downloading models
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DownloadProgress {
+    /// Relative path of the file currently downloading
+    pub file: String,
+    /// Bytes received so far for `file`
+    pub bytes: u64,
+    /// Size of `file` if the server reported one
+    pub total_bytes: Option<u64>,
+    /// Number of manifest files completed (including ones already on disk)
+    pub files_done: usize,
+    pub files_total: usize,
+    /// Bytes received so far across every file being downloaded
+    pub overall_bytes: u64,
+    /// Combined size of every file being downloaded, once all sizes are known
+    pub overall_total_bytes: Option<u64>,
+}
+
+/// Aggregates progress from concurrent downloads into `DownloadProgress` reports
+pub struct ProgressTracker {
+    /// Pending file -> (bytes received, total size if known)
+    files: HashMap<String, (u64, Option<u64>)>,
+    files_done: usize,
+    files_total: usize,
+}
+
+impl ProgressTracker {
+    /// Track `pending` out of a manifest of `files_total` files; the rest count as done
+    pub fn new(pending: &[ManifestFile], files_total: usize) -> Self {
+        ProgressTracker {
+            files: pending
+                .iter()
+                .map(|f| (f.path.clone(), (0, f.size)))
+                .collect(),
+            files_done: files_total.saturating_sub(pending.len()),
+            files_total,
+        }
+    }
+
+    /// Record the bytes received for `file` and return the aggregated report
+    pub fn update(&mut self, file: &str, bytes: u64, total_bytes: Option<u64>) -> DownloadProgress {
+        let entry = self.files.entry(file.to_string()).or_insert((0, None));
+        entry.0 = bytes;
+        if total_bytes.is_some() {
+            entry.1 = total_bytes;
+        }
+        self.report(file)
+    }
+
+    /// Mark `file` as complete and return the aggregated report
+    pub fn finish(&mut self, file: &str) -> DownloadProgress {
+        self.files_done = (self.files_done + 1).min(self.files_total);
+        self.report(file)
+    }
+
+    /// Aggregated report not tied to any particular file
+    pub fn snapshot(&self) -> DownloadProgress {
+        self.report("")
+    }
+
+    fn report(&self, file: &str) -> DownloadProgress {
+        let (bytes, total_bytes) = self.files.get(file).copied().unwrap_or((0, None));
+        DownloadProgress {
+            file: file.to_string(),
+            bytes,
+            total_bytes,
+            files_done: self.files_done,
+            files_total: self.files_total,
+            overall_bytes: self.files.values().map(|(received, _)| received).sum(),
+            overall_total_bytes: self.files.values().map(|(_, total)| *total).sum(),
+        }
+    }
+}
+
+/// URL schemes a model source may use; `file` copies from a local mirror
+const SUPPORTED_SCHEMES: &[&str] = &["http", "https", "file"];
+
+/// Check that a model source URL parses and uses a supported scheme
+pub fn validate_source_url(base_url: &str) -> Result<()> {
+    let url = reqwest::Url::parse(base_url)
+        .with_context(|| format!("Invalid model source URL: {}", base_url))?;
+
+    if !SUPPORTED_SCHEMES.contains(&url.scheme()) {
+        bail!(
+            "Unsupported model source scheme '{}': expected one of {}",
+            url.scheme(),
+            SUPPORTED_SCHEMES.join(", ")
+        );
+    }
+
+    if url.scheme() == "file" && url.to_file_path().is_err() {
+        bail!("Model source {} is not a valid local path", base_url);
+    }
+
+    Ok(())
+}
+
+/// Join a base URL and a manifest-relative path
+pub fn file_url(base_url: &str, rel_path: &str) -> String {
+    format!("{}/{}", base_url.trim_end_matches('/'), rel_path.trim_start_matches('/'))
+}
+
+/// Path of the temporary file a download is written to before it's complete
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(PART_SUFFIX);
+    dest.with_file_name(name)
+}
+
+/// Download `url` to `dest`, creating parent directories.
+/// Data is written to `<dest>.part` and renamed into place only once complete, so an
+/// interrupted download never looks like a finished file. If a `.part` file already
+/// exists the download resumes from its size with a `Range` request; servers that
+/// ignore the range get a fresh download.
+/// `on_progress(bytes_received, total_bytes)` is called periodically and once at the end.
+pub async fn download_file<F>(
+    client: &reqwest::Client,
+    url: &str,
+    dest: &Path,
+    mut on_progress: F,
+) -> Result<u64>
+where
+    F: FnMut(u64, Option<u64>),
+{
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+
+    if let Ok(file_url) = reqwest::Url::parse(url) {
+        if file_url.scheme() == "file" {
+            return copy_local_file(&file_url, dest, on_progress);
+        }
+    }
+
+    let part = part_path(dest);
+    let existing = std::fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+
+    let mut request = client.get(url);
+    if existing > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+    }
+
+    let mut response = request
+        .send()
+        .await
+        .with_context(|| format!("Request to {} failed", url))?;
+
+    if existing > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file is stale or already complete; start over
+        std::fs::remove_file(&part)
+            .with_context(|| format!("Failed to remove {}", part.display()))?;
+        response = client
+            .get(url)
+            .send()
+            .await
+            .with_context(|| format!("Request to {} failed", url))?;
+    }
+
+    let mut response = response
+        .error_for_status()
+        .with_context(|| format!("Request to {} failed", url))?;
+
+    let resuming = existing > 0 && response.status() == StatusCode::PARTIAL_CONTENT;
+    let mut file = if resuming {
+        OpenOptions::new().append(true).open(&part)
+    } else {
+        File::create(&part)
+    }
+    .with_context(|| format!("Failed to open {}", part.display()))?;
+
+    let mut received: u64 = if resuming { existing } else { 0 };
+    let total_bytes = response.content_length().map(|len| len + received);
+    let mut last_reported = received;
+    on_progress(received, total_bytes);
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .with_context(|| format!("Connection lost while downloading {}", url))?
+    {
+        file.write_all(&chunk)
+            .with_context(|| format!("Failed to write {}", part.display()))?;
+        received += chunk.len() as u64;
+
+        if received - last_reported >= PROGRESS_INTERVAL_BYTES {
+            on_progress(received, total_bytes);
+            last_reported = received;
+        }
+    }
+
+    file.flush()?;
+    drop(file);
+
+    if let Some(total) = total_bytes {
+        if received != total {
+            bail!("Incomplete download of {}: got {} of {} bytes", url, received, total);
+        }
+    }
+
+    std::fs::rename(&part, dest)
+        .with_context(|| format!("Failed to move {} into place", part.display()))?;
+
+    on_progress(received, total_bytes);
+    Ok(received)
+}
+
+/// Copy a `file://` source into place for offline installs, via the same `.part` rename
+fn copy_local_file<F>(src_url: &reqwest::Url, dest: &Path, mut on_progress: F) -> Result<u64>
+where
+    F: FnMut(u64, Option<u64>),
+{
+    let src = src_url
+        .to_file_path()
+        .map_err(|_| anyhow!("Invalid file URL: {}", src_url))?;
+    let total_bytes = std::fs::metadata(&src)
+        .with_context(|| format!("Failed to read {}", src.display()))?
+        .len();
+    on_progress(0, Some(total_bytes));
+
+    let part = part_path(dest);
+    let copied = std::fs::copy(&src, &part)
+        .with_context(|| format!("Failed to copy {}", src.display()))?;
+    std::fs::rename(&part, dest)
+        .with_context(|| format!("Failed to move {} into place", part.display()))?;
+
+    on_progress(copied, Some(total_bytes));
+    Ok(copied)
+}
+
+// ============================================================================
+// Model Manifest
+// ============================================================================
+
+/// One downloadable model file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestFile {
+    /// Logical name, e.g. `vocoder` or the voice style id
+    pub name: String,
+    /// Path relative to the models directory, e.g. `onnx/vocoder.onnx`
+    pub path: String,
+    /// Absolute download URL; defaults to `{base_url}/{path}`
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// Expected lowercase hex SHA-256 digest
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// Every file that makes up a complete model download
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModelManifest {
+    pub version: String,
+    pub files: Vec<ManifestFile>,
+}
+
+impl ModelManifest {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let manifest: ModelManifest = serde_json::from_slice(bytes)
+            .context("Failed to parse model manifest")?;
+        if manifest.files.is_empty() {
+            bail!("Model manifest lists no files");
+        }
+        for file in &manifest.files {
+            resolve_model_path(Path::new(""), &file.path)
+                .context("Model manifest lists an unsafe path")?;
+        }
+        Ok(manifest)
+    }
+
+    /// Relative paths of every file, in manifest order
+    pub fn paths(&self) -> Vec<String> {
+        self.files.iter().map(|f| f.path.clone()).collect()
+    }
+
+    /// Expected digests for the files that declare one
+    pub fn checksums(&self) -> HashMap<String, String> {
+        self.files
+            .iter()
+            .filter_map(|f| {
+                f.sha256
+                    .as_ref()
+                    .map(|digest| (f.path.clone(), digest.trim().to_lowercase()))
+            })
+            .collect()
+    }
+}
+
+impl ManifestFile {
+    /// Download URL for this file, preferring an explicit `url` over `base_url`.
+    /// An explicit `url` must use a supported scheme, and may only point at a local
+    /// `file://` path when the manifest itself came from a local mirror.
+    pub fn url(&self, base_url: &str) -> Result<String> {
+        let Some(url) = &self.url else {
+            return Ok(file_url(base_url, &self.path));
+        };
+
+        validate_source_url(url).with_context(|| format!("Bad URL for {}", self.path))?;
+        let is_local = |u: &str| reqwest::Url::parse(u).is_ok_and(|u| u.scheme() == "file");
+        if is_local(url) && !is_local(base_url) {
+            bail!("A remote manifest may not point {} at the local file {}", self.path, url);
+        }
+        Ok(url.clone())
+    }
+}
+
+// ============================================================================
+// Disk Space
+// ============================================================================
+
+/// Whether the volume holding the models directory has room for a download
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpaceReport {
+    pub required_bytes: u64,
+    pub available_bytes: u64,
+    pub sufficient: bool,
+}
+
+/// Bytes still needed to finish downloading the manifest into `models_dir`.
+/// Files already on disk need nothing and partial `.part` files only need their remainder,
+/// since they're renamed in place on the same volume. Files without a size count as zero.
+pub fn required_download_bytes(models_dir: &Path, manifest: &ModelManifest) -> u64 {
+    manifest
+        .files
+        .iter()
+        .filter(|f| !models_dir.join(&f.path).exists())
+        .map(|f| {
+            let size = f.size.unwrap_or(0);
+            let partial = std::fs::metadata(part_path(&models_dir.join(&f.path)))
+                .map(|m| m.len())
+                .unwrap_or(0);
+            size.saturating_sub(partial)
+        })
+        .sum()
+}
+
+/// Free space on the volume containing `path`, which may not exist yet
+pub fn available_space(path: &Path) -> Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .with_context(|| format!("No existing parent directory for {}", path.display()))?;
+
+    fs2::available_space(existing)
+        .with_context(|| format!("Failed to query free space for {}", existing.display()))
+}
+
+/// Compare the remaining download size against the free space for `models_dir`
+pub fn check_space(models_dir: &Path, manifest: &ModelManifest) -> Result<SpaceReport> {
+    let required_bytes = required_download_bytes(models_dir, manifest);
+    let available_bytes = available_space(models_dir)?;
+
+    Ok(SpaceReport {
+        required_bytes,
+        available_bytes,
+        sufficient: available_bytes >= required_bytes,
+    })
+}
+
+// ============================================================================
+// Checksum Verification
+// ============================================================================
+
+/// Parse a checksum table (`{"onnx/vocoder.onnx": "<sha256 hex>", ...}`)
+pub fn load_checksums_from_bytes(bytes: &[u8]) -> Result<HashMap<String, String>> {
+    let raw: HashMap<String, String> = serde_json::from_slice(bytes)?;
+    Ok(raw
+        .into_iter()
+        .map(|(path, digest)| (path, digest.trim().to_lowercase()))
+        .collect())
+}
+
+/// Compute the SHA-256 of a file as a lowercase hex string
+pub fn sha256_file(path: &Path) -> Result<String> {
+    sha256_file_with_progress(path, &mut |_| {})
+}
+
+/// `sha256_file`, calling `on_bytes` with the number of bytes hashed so far after each read
+pub fn sha256_file_with_progress(path: &Path, on_bytes: &mut dyn FnMut(u64)) -> Result<String> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut hashed = 0u64;
+
+    loop {
+        let n = reader.read(&mut buf)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        hashed += n as u64;
+        on_bytes(hashed);
+    }
+
+    Ok(hex_digest(&hasher.finalize()))
+}
+
+/// Join a models-relative path onto `models_dir`, rejecting absolute paths and `..`
+/// so the result can't point outside the models directory
+pub fn resolve_model_path(models_dir: &Path, rel_path: &str) -> Result<PathBuf> {
+    let rel = Path::new(rel_path);
+    if rel_path.is_empty() || !rel.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        bail!("{:?} is not a path inside the models directory", rel_path);
+    }
+    Ok(models_dir.join(rel))
+}
+
+/// SHA-256 of data already in memory, as `sha256_file` would give for the same bytes in a file
+pub fn sha256_bytes(bytes: &[u8]) -> String {
+    hex_digest(&Sha256::digest(bytes))
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Progress through `find_checksum_mismatches_with_progress`. Byte counts cover every file
+/// being verified, so one progress bar can show the whole check.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VerifyProgress {
+    /// Relative path of the file currently being hashed
+    pub file: String,
+    pub bytes_hashed: u64,
+    pub total_bytes: u64,
+    /// Files completely hashed so far
+    pub files_done: usize,
+}
+
+/// Hash each present file that has an expected checksum and return the ones that don't match.
+/// Missing files and files without an entry in `expected` are skipped. Progress is reported
+/// about every `VERIFY_PROGRESS_INTERVAL_BYTES` and once as each file finishes. A file removed
+/// after the check started fails the whole verification with an error naming it.
+pub fn find_checksum_mismatches_with_progress(
+    models_dir: &Path,
+    files: &[String],
+    expected: &HashMap<String, String>,
+    on_progress: &mut dyn FnMut(&VerifyProgress),
+) -> Result<Vec<String>> {
+    // Sized up front so progress can be reported against the total
+    let to_verify: Vec<(&String, &String, PathBuf, u64)> = files
+        .iter()
+        .filter_map(|rel_path| {
+            let expected_digest = expected.get(rel_path)?;
+            let full_path = models_dir.join(rel_path);
+            let size = std::fs::metadata(&full_path).ok()?.len();
+            Some((rel_path, expected_digest, full_path, size))
+        })
+        .collect();
+
+    let mut progress = VerifyProgress {
+        file: String::new(),
+        bytes_hashed: 0,
+        total_bytes: to_verify.iter().map(|(_, _, _, size)| size).sum(),
+        files_done: 0,
+    };
+    let mut mismatched = Vec::new();
+
+    for (rel_path, expected_digest, full_path, _) in to_verify {
+        progress.file = rel_path.clone();
+        let file_start = progress.bytes_hashed;
+        let mut last_reported = file_start;
+        let digest = sha256_file_with_progress(&full_path, &mut |hashed| {
+            progress.bytes_hashed = file_start + hashed;
+            if progress.bytes_hashed - last_reported >= VERIFY_PROGRESS_INTERVAL_BYTES {
+                last_reported = progress.bytes_hashed;
+                on_progress(&progress);
+            }
+        });
+        let digest = match digest {
+            Ok(digest) => digest,
+            Err(_) if !full_path.exists() => bail!("{} was removed while models were being verified", rel_path),
+            Err(e) => return Err(e),
+        };
+
+        if &digest != expected_digest {
+            mismatched.push(rel_path.clone());
+        }
+        progress.files_done += 1;
+        on_progress(&progress);
+    }
+
+    Ok(mismatched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A fresh, empty directory under the system temp dir for one test
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("model_download_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn sha256_matches_known_digest() {
+        assert_eq!(sha256_bytes(b"abc"), ABC_SHA256);
+
+        let dir = scratch_dir("sha256");
+        let path = dir.join("abc.bin");
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(sha256_file(&path).unwrap(), ABC_SHA256);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn manifest_rejects_paths_outside_the_models_directory() {
+        let manifest = ModelManifest::from_bytes(
+            br#"{"version": "1", "files": [{"name": "vocoder", "path": "onnx/vocoder.onnx", "sha256": "ABC"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.paths(), vec!["onnx/vocoder.onnx"]);
+        assert_eq!(manifest.checksums()["onnx/vocoder.onnx"], "abc");
+
+        for path in ["../escape.onnx", "/etc/passwd", "onnx/../../escape.onnx", ""] {
+            let json = format!(r#"{{"version": "1", "files": [{{"name": "x", "path": "{}"}}]}}"#, path);
+            assert!(ModelManifest::from_bytes(json.as_bytes()).is_err(), "accepted {:?}", path);
+        }
+        assert!(ModelManifest::from_bytes(br#"{"version": "1", "files": []}"#).is_err());
+    }
+
+    #[test]
+    fn one_corrupted_byte_is_a_checksum_mismatch() {
+        let dir = scratch_dir("corrupt");
+        std::fs::create_dir_all(dir.join("onnx")).unwrap();
+        std::fs::write(dir.join("onnx/good.onnx"), b"abc").unwrap();
+        std::fs::write(dir.join("onnx/bad.onnx"), b"abd").unwrap();
+
+        let files = vec!["onnx/good.onnx".to_string(), "onnx/bad.onnx".to_string()];
+        let expected: HashMap<String, String> =
+            files.iter().map(|f| (f.clone(), ABC_SHA256.to_string())).collect();
+        let mut reports = 0;
+        let mismatches =
+            find_checksum_mismatches_with_progress(&dir, &files, &expected, &mut |_| reports += 1).unwrap();
+
+        assert_eq!(mismatches, vec!["onnx/bad.onnx"]);
+        assert!(reports > 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn required_bytes_is_the_manifest_sum_less_what_is_on_disk() {
+        let manifest = ModelManifest::from_bytes(
+            br#"{"version": "1", "files": [
+                {"name": "a", "path": "onnx/a.onnx", "size": 100},
+                {"name": "b", "path": "onnx/b.onnx", "size": 250},
+                {"name": "c", "path": "voice_styles/c.json", "size": 50}
+            ]}"#,
+        )
+        .unwrap();
+        let dir = scratch_dir("required");
+        assert_eq!(required_download_bytes(&dir, &manifest), 400);
+
+        std::fs::create_dir_all(dir.join("onnx")).unwrap();
+        std::fs::write(dir.join("onnx/a.onnx"), vec![0u8; 100]).unwrap();
+        std::fs::write(part_path(&dir.join("onnx/b.onnx")), vec![0u8; 50]).unwrap();
+        assert_eq!(required_download_bytes(&dir, &manifest), 200 + 50);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+
+    /// How `serve` answers a request with a `Range: bytes=<start>-` header
+    #[derive(Clone, Copy)]
+    enum RangeReply {
+        /// 206 with the rest of the body
+        Partial,
+        /// 200 with the whole body, as servers without range support do
+        Whole,
+        /// 416, as for a partial file bigger than the current file
+        NotSatisfiable,
+    }
+
+    /// Serve `body` over HTTP on localhost, returning its URL and the start of every `Range`
+    /// requested (`None` for a request without one). With `cut_after`, each response still
+    /// announces its full length but the connection drops after that many bytes.
+    fn serve(
+        body: Vec<u8>,
+        range_reply: RangeReply,
+        cut_after: Option<usize>,
+    ) -> (String, Arc<Mutex<Vec<Option<usize>>>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/onnx/model.onnx", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut head = Vec::new();
+                let mut byte = [0u8];
+                while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                    head.push(byte[0]);
+                }
+                let range_start = String::from_utf8_lossy(&head).lines().find_map(|line| {
+                    let value = line.to_ascii_lowercase().strip_prefix("range: bytes=")?.to_string();
+                    value.trim_end_matches('-').parse::<usize>().ok()
+                });
+                seen.lock().unwrap().push(range_start);
+
+                let (status, content) = match (range_start, range_reply) {
+                    (Some(start), RangeReply::Partial) => ("206 Partial Content", &body[start..]),
+                    (Some(_), RangeReply::NotSatisfiable) => ("416 Range Not Satisfiable", &body[..0]),
+                    _ => ("200 OK", &body[..]),
+                };
+                let sent = cut_after.map_or(content.len(), |cut| cut.min(content.len()));
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    content.len()
+                );
+                let _ = stream.write_all(&content[..sent]);
+            }
+        });
+        (url, requests)
+    }
+
+    fn model_bytes() -> Vec<u8> {
+        (0..1000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn interrupted_download_keeps_its_part_file_and_resumes_from_it() {
+        let dir = scratch_dir("resume");
+        let dest = dir.join("onnx/model.onnx");
+        let client = reqwest::Client::new();
+
+        let (url, _) = serve(model_bytes(), RangeReply::Partial, Some(400));
+        assert!(download_file(&client, &url, &dest, |_, _| {}).await.is_err());
+        assert!(!dest.exists(), "incomplete download was moved into place");
+        assert_eq!(std::fs::metadata(part_path(&dest)).unwrap().len(), 400);
+
+        let (url, requests) = serve(model_bytes(), RangeReply::Partial, None);
+        let mut last_progress = None;
+        let received = download_file(&client, &url, &dest, |bytes, total| last_progress = Some((bytes, total)))
+            .await
+            .unwrap();
+
+        assert_eq!(*requests.lock().unwrap(), vec![Some(400)]);
+        assert_eq!(received, 1000);
+        assert_eq!(last_progress, Some((1000, Some(1000))));
+        assert_eq!(std::fs::read(&dest).unwrap(), model_bytes());
+        assert!(!part_path(&dest).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn server_ignoring_the_range_gets_a_fresh_download() {
+        let dir = scratch_dir("no_range");
+        let dest = dir.join("onnx/model.onnx");
+        std::fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        std::fs::write(part_path(&dest), b"stale").unwrap();
+
+        let (url, requests) = serve(model_bytes(), RangeReply::Whole, None);
+        download_file(&reqwest::Client::new(), &url, &dest, |_, _| {}).await.unwrap();
+
+        assert_eq!(*requests.lock().unwrap(), vec![Some(5)]);
+        assert_eq!(std::fs::read(&dest).unwrap(), model_bytes());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unsatisfiable_range_discards_the_part_file_and_starts_over() {
+        let dir = scratch_dir("range_416");
+        let dest = dir.join("onnx/model.onnx");
+        std::fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        std::fs::write(part_path(&dest), vec![0u8; 2000]).unwrap();
+
+        let (url, requests) = serve(model_bytes(), RangeReply::NotSatisfiable, None);
+        download_file(&reqwest::Client::new(), &url, &dest, |_, _| {}).await.unwrap();
+
+        assert_eq!(*requests.lock().unwrap(), vec![Some(2000), None]);
+        assert_eq!(std::fs::read(&dest).unwrap(), model_bytes());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn explicit_urls_are_checked_against_the_manifest_source() {
+        let file = |url: &str| ManifestFile {
+            name: "vocoder".to_string(),
+            path: "onnx/vocoder.onnx".to_string(),
+            url: Some(url.to_string()).filter(|u| !u.is_empty()),
+            size: None,
+            sha256: None,
+            version: None,
+        };
+
+        let cdn = "https://cdn.example.com/models/";
+        assert_eq!(file("").url(cdn).unwrap(), "https://cdn.example.com/models/onnx/vocoder.onnx");
+        assert_eq!(file("https://other.example.com/v.onnx").url(cdn).unwrap(), "https://other.example.com/v.onnx");
+        assert!(file("ftp://cdn.example.com/v.onnx").url(cdn).is_err());
+        // A remote manifest can't make the app read local files; a local mirror can
+        assert!(file("file:///etc/passwd").url(cdn).is_err());
+        assert!(file("file:///mnt/mirror/v.onnx").url("file:///mnt/mirror").is_ok());
+    }
+
+    #[tokio::test]
+    async fn file_source_is_copied_into_place() {
+        let dir = scratch_dir("file_copy");
+        let src = dir.join("mirror/onnx/vocoder.onnx");
+        std::fs::create_dir_all(src.parent().unwrap()).unwrap();
+        std::fs::write(&src, model_bytes()).unwrap();
+
+        let url = reqwest::Url::from_file_path(&src).unwrap().to_string();
+        let dest = dir.join("models/onnx/vocoder.onnx");
+        let client = reqwest::Client::new();
+        let mut reports = Vec::new();
+        let copied = download_file(&client, &url, &dest, |bytes, total| reports.push((bytes, total)))
+            .await
+            .unwrap();
+
+        assert_eq!(copied, 1000);
+        assert_eq!(std::fs::read(&dest).unwrap(), model_bytes());
+        assert!(!part_path(&dest).exists());
+        assert_eq!(reports.last(), Some(&(1000, Some(1000))));
+        let missing = format!("{}.missing", url);
+        assert!(download_file(&client, &missing, &dest, |_, _| {}).await.is_err());
+    }
+
+    #[test]
+    fn verify_progress_covers_every_byte_and_file() {
+        let dir = scratch_dir("verify_progress");
+        std::fs::create_dir_all(dir.join("onnx")).unwrap();
+        let big = vec![7u8; 2 * VERIFY_PROGRESS_INTERVAL_BYTES as usize + 1];
+        std::fs::write(dir.join("onnx/big.onnx"), &big).unwrap();
+        std::fs::write(dir.join("onnx/small.onnx"), b"abc").unwrap();
+
+        let files: Vec<String> = ["onnx/big.onnx", "onnx/small.onnx", "onnx/missing.onnx"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let mut expected: HashMap<String, String> =
+            files.iter().map(|f| (f.clone(), ABC_SHA256.to_string())).collect();
+        expected.insert("onnx/big.onnx".to_string(), sha256_bytes(&big));
+
+        let mut reports = Vec::new();
+        let mismatches =
+            find_checksum_mismatches_with_progress(&dir, &files, &expected, &mut |p| reports.push(p.clone())).unwrap();
+        assert!(mismatches.is_empty(), "{:?}", mismatches);
+
+        // The missing file is left out of the total
+        let total = big.len() as u64 + 3;
+        assert!(reports.iter().all(|p| p.total_bytes == total));
+        assert!(reports.windows(2).all(|w| w[0].bytes_hashed <= w[1].bytes_hashed));
+
+        // Two interval reports while hashing the big file, then one as each file finishes
+        let big_reports: Vec<_> = reports.iter().filter(|p| p.file == "onnx/big.onnx").collect();
+        assert_eq!(big_reports.len(), 3, "{:?}", reports);
+        assert!(big_reports[..2].iter().all(|p| p.files_done == 0));
+        assert_eq!(big_reports[2].bytes_hashed, big.len() as u64);
+        assert_eq!(big_reports[2].files_done, 1);
+
+        let last = reports.last().unwrap();
+        assert_eq!(last.file, "onnx/small.onnx");
+        assert_eq!((last.bytes_hashed, last.files_done), (total, 2));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn progress_tracker_sums_concurrent_downloads() {
+        let file = |path: &str, size| ManifestFile {
+            name: path.to_string(),
+            path: path.to_string(),
+            url: None,
+            size,
+            sha256: None,
+            version: None,
+        };
+        // Two of four files still to download; the other two are already present
+        let pending = vec![file("onnx/a.onnx", Some(100)), file("onnx/b.onnx", None)];
+        let mut tracker = ProgressTracker::new(&pending, 4);
+
+        let start = tracker.snapshot();
+        assert_eq!((start.files_done, start.files_total), (2, 4));
+        assert_eq!((start.overall_bytes, start.overall_total_bytes), (0, None));
+
+        tracker.update("onnx/a.onnx", 40, None);
+        let report = tracker.update("onnx/b.onnx", 30, Some(50));
+        assert_eq!((report.file.as_str(), report.bytes, report.total_bytes), ("onnx/b.onnx", 30, Some(50)));
+        assert_eq!((report.overall_bytes, report.overall_total_bytes), (70, Some(150)));
+
+        // Each finished file counts once, never past the total
+        assert_eq!(tracker.finish("onnx/a.onnx").files_done, 3);
+        assert_eq!(tracker.finish("onnx/b.onnx").files_done, 4);
+        assert_eq!(tracker.finish("onnx/b.onnx").files_done, 4);
+    }
+}
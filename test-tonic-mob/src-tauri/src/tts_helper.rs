@@ -2,15 +2,16 @@
 // TTS Helper Module - All utility functions and structures
 // ============================================================================
 
-use ndarray::{Array, Array3};
+use ndarray::Array3;
 use serde::{Deserialize, Serialize};
-use serde_json;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 use anyhow::{Result, Context, bail};
 use unicode_normalization::UnicodeNormalization;
 use hound::{WavWriter, WavSpec, SampleFormat};
+use rand::{rngs::StdRng, SeedableRng};
 use rand_distr::{Distribution, Normal};
 use regex::Regex;
 
@@ -21,6 +22,62 @@ pub fn is_valid_lang(lang: &str) -> bool {
     AVAILABLE_LANGS.contains(&lang)
 }
 
+/// A stretch of text in one script and the language it's read in
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptSegment {
+    pub text: String,
+    pub lang: String,
+}
+
+/// Split text into runs by script so embedded words can be read in their own language,
+/// e.g. the Hangul in "The word 안녕 means hello" as Korean. Hangul is `ko`; Latin is
+/// `default_lang`, or `en` when that is `ko`. Other scripts can't be told apart among the
+/// supported languages and stay in `default_lang`. Digits, punctuation and spaces join the
+/// run they follow (the first run, at the start).
+pub fn segment_by_script(text: &str, default_lang: &str) -> Vec<ScriptSegment> {
+    let latin_lang = if default_lang == "ko" { "en" } else { default_lang };
+    let mut segments: Vec<ScriptSegment> = Vec::new();
+
+    for c in text.chars() {
+        let lang = if is_hangul(c) {
+            Some("ko")
+        } else if c.is_alphabetic() {
+            Some(if is_latin(c) { latin_lang } else { default_lang })
+        } else {
+            None
+        };
+
+        match (segments.last_mut(), lang) {
+            (Some(last), Some(lang)) if last.lang != lang => segments.push(ScriptSegment {
+                text: c.to_string(),
+                lang: lang.to_string(),
+            }),
+            (Some(last), _) => last.text.push(c),
+            (None, lang) => segments.push(ScriptSegment {
+                text: c.to_string(),
+                lang: lang.unwrap_or(default_lang).to_string(),
+            }),
+        }
+    }
+
+    // Leading neutral characters took the default language; give them to the first real run
+    if segments.len() > 1 && !segments[0].text.chars().any(char::is_alphabetic) {
+        let lead = segments.remove(0);
+        segments[0].text.insert_str(0, &lead.text);
+    }
+    segments
+}
+
+fn is_hangul(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{A960}'..='\u{A97F}'
+        | '\u{AC00}'..='\u{D7A3}' | '\u{D7B0}'..='\u{D7FF}')
+}
+
+fn is_latin(c: char) -> bool {
+    c.is_ascii_alphabetic() || matches!(c, '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}')
+}
+
 // ============================================================================
 // Configuration Structures
 // ============================================================================
@@ -44,10 +101,11 @@ pub struct TTLConfig {
 }
 
 /// Load configuration from JSON file
+#[cfg(not(target_os = "android"))]
 pub fn load_cfgs<P: AsRef<Path>>(onnx_dir: P) -> Result<Config> {
     let cfg_path = onnx_dir.as_ref().join("tts.json");
     let file = File::open(cfg_path)?;
-    let reader = BufReader::new(file);
+    let reader = std::io::BufReader::new(file);
     let cfgs: Config = serde_json::from_reader(reader)?;
     Ok(cfgs)
 }
@@ -74,22 +132,46 @@ pub struct StyleComponent {
 // Unicode Text Processor
 // ============================================================================
 
+/// Size and coverage of a `UnicodeProcessor`'s indexer
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct IndexerStats {
+    /// Code points the indexer has an entry for, i.e. 0 up to `size - 1`
+    pub size: usize,
+    /// Largest token ID, or -1 for an indexer with no tokens
+    pub max_index: i64,
+    /// Entries indexed as -1: code points in range the model has no token for
+    pub negative_count: usize,
+}
+
+/// Token IDs (one row per text, padded), text mask and out-of-vocabulary characters
+pub type IndexedText = (Vec<Vec<i64>>, Array3<f32>, Vec<char>);
+
+#[derive(Clone)]
 pub struct UnicodeProcessor {
     pub indexer: Vec<i64>,
+    pub lexicon: HashMap<String, String>,
+    pub options: PreprocessOptions,
 }
 
 impl UnicodeProcessor {
+    #[cfg(not(target_os = "android"))]
     pub fn new<P: AsRef<Path>>(unicode_indexer_json_path: P) -> Result<Self> {
         let file = File::open(unicode_indexer_json_path)?;
-        let reader = BufReader::new(file);
+        let reader = std::io::BufReader::new(file);
         let indexer: Vec<i64> = serde_json::from_reader(reader)?;
-        Ok(UnicodeProcessor { indexer })
+        Ok(UnicodeProcessor {
+            indexer,
+            lexicon: HashMap::new(),
+            options: PreprocessOptions::default(),
+        })
     }
 
-    pub fn call(&self, text_list: &[String], lang_list: &[String]) -> Result<(Vec<Vec<i64>>, Array3<f32>)> {
+    /// Normalize and index `text_list`, returning (token IDs, text mask, out-of-vocabulary
+    /// characters). OOV characters are those indexed as -1, each listed once.
+    pub fn call(&self, text_list: &[String], lang_list: &[String]) -> Result<IndexedText> {
         let mut processed_texts: Vec<String> = Vec::new();
         for (text, lang) in text_list.iter().zip(lang_list.iter()) {
-            processed_texts.push(preprocess_text(text, lang)?);
+            processed_texts.push(preprocess_text(text, lang, &self.lexicon, &self.options)?);
         }
 
         let text_ids_lengths: Vec<usize> = processed_texts
@@ -100,14 +182,14 @@ impl UnicodeProcessor {
         let max_len = *text_ids_lengths.iter().max().unwrap_or(&0);
 
         let mut text_ids = Vec::new();
+        let mut oov = Vec::new();
         for text in &processed_texts {
             let mut row = vec![0i64; max_len];
-            let unicode_vals = text_to_unicode_values(text);
-            for (j, &val) in unicode_vals.iter().enumerate() {
-                if val < self.indexer.len() {
-                    row[j] = self.indexer[val];
-                } else {
-                    row[j] = -1;
+            for (j, c) in text.chars().enumerate() {
+                row[j] = self.token_id(c);
+                // Only allocates once an unknown character actually turns up
+                if row[j] == -1 && !oov.contains(&c) {
+                    oov.push(c);
                 }
             }
             text_ids.push(row);
@@ -115,17 +197,306 @@ impl UnicodeProcessor {
 
         let text_mask = get_text_mask(&text_ids_lengths);
 
-        Ok((text_ids, text_mask))
+        Ok((text_ids, text_mask, oov))
+    }
+
+    /// Token ID for a character, or -1 if the model has no token for it
+    pub fn token_id(&self, c: char) -> i64 {
+        self.indexer.get(c as usize).copied().unwrap_or(-1)
+    }
+
+    pub fn stats(&self) -> IndexerStats {
+        IndexerStats {
+            size: self.indexer.len(),
+            max_index: self.indexer.iter().copied().max().unwrap_or(-1),
+            negative_count: self.indexer.iter().filter(|&&id| id < 0).count(),
+        }
+    }
+
+    /// Characters of already-normalized text the model has no token for, each listed once
+    pub fn oov_chars(&self, processed: &str) -> Vec<char> {
+        let mut oov = Vec::new();
+        for c in processed.chars() {
+            if self.token_id(c) == -1 && !oov.contains(&c) {
+                oov.push(c);
+            }
+        }
+        oov
+    }
+}
+
+// ============================================================================
+// Pronunciation Lexicon
+// ============================================================================
+
+/// Load a pronunciation lexicon from JSON bytes.
+/// The file is a flat object mapping words to replacement spellings,
+/// e.g. `{"Tauri": "Tore ee", "SQL": "sequel"}`. Keys are matched case-insensitively.
+pub fn load_lexicon_from_bytes(bytes: &[u8]) -> Result<HashMap<String, String>> {
+    let raw: HashMap<String, String> = serde_json::from_slice(bytes)?;
+    Ok(raw
+        .into_iter()
+        .filter(|(word, _)| !word.trim().is_empty())
+        .map(|(word, spoken)| (word.trim().to_lowercase(), spoken))
+        .collect())
+}
+
+/// Replace whole words found in the lexicon with their replacement spelling.
+/// Matching is case-insensitive and bounded by word boundaries, so "SQL" is
+/// replaced but "MySQLish" is left alone.
+pub fn apply_lexicon(text: &str, lexicon: &HashMap<String, String>) -> String {
+    if lexicon.is_empty() {
+        return text.to_string();
+    }
+
+    // Longest words first so multi-word entries win over their prefixes
+    let mut words: Vec<&String> = lexicon.keys().collect();
+    words.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+
+    let alternatives: Vec<String> = words.iter().map(|w| regex::escape(w)).collect();
+    let pattern = format!(r"(?i)\b(?:{})\b", alternatives.join("|"));
+    let re = match Regex::new(&pattern) {
+        Ok(re) => re,
+        Err(_) => return text.to_string(),
+    };
+
+    re.replace_all(text, |caps: &regex::Captures| {
+        let matched = &caps[0];
+        lexicon
+            .get(&matched.to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| matched.to_string())
+    })
+    .to_string()
+}
+
+// ============================================================================
+// Ordinals
+// ============================================================================
+
+const ONES: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+    "ten", "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen",
+    "eighteen", "nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+static ORDINAL_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(\d{1,9})(st|nd|rd|th)\b").unwrap());
+
+/// English words for `n` below one billion, e.g. 121 -> "one hundred twenty-one"
+fn cardinal_words(n: u64) -> String {
+    match n {
+        0..=19 => ONES[n as usize].to_string(),
+        20..=99 => match n % 10 {
+            0 => TENS[(n / 10) as usize].to_string(),
+            ones => format!("{}-{}", TENS[(n / 10) as usize], ONES[ones as usize]),
+        },
+        _ => {
+            let (unit, name) = match n {
+                100..=999 => (100, "hundred"),
+                1_000..=999_999 => (1_000, "thousand"),
+                _ => (1_000_000, "million"),
+            };
+            let head = format!("{} {}", cardinal_words(n / unit), name);
+            match n % unit {
+                0 => head,
+                rest => format!("{} {}", head, cardinal_words(rest)),
+            }
+        }
+    }
+}
+
+/// Ordinal form of `cardinal_words`, e.g. 21 -> "twenty-first"
+fn ordinal_words(n: u64) -> String {
+    let words = cardinal_words(n);
+    // Only the last word changes: "twenty-one" -> "twenty-first"
+    let split = words.rfind([' ', '-']).map_or(0, |i| i + 1);
+    let (head, last) = words.split_at(split);
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        word if word.ends_with('y') => format!("{}ieth", &word[..word.len() - 1]),
+        word => format!("{}th", word),
+    };
+    format!("{}{}", head, last)
+}
+
+/// Spell out English ordinals: "3rd" -> "third", "21st" -> "twenty-first". The suffix
+/// isn't checked against the number, so "21th" reads the same as "21st".
+pub fn expand_ordinals(text: &str) -> String {
+    ORDINAL_PATTERN
+        .replace_all(text, |caps: &regex::Captures| match caps[1].parse::<u64>() {
+            Ok(n) => ordinal_words(n),
+            Err(_) => caps[0].to_string(),
+        })
+        .to_string()
+}
+
+// ============================================================================
+// Markdown Stripping
+// ============================================================================
+
+static CODE_FENCE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^[ \t]*(?:```|~~~).*$").unwrap());
+static HORIZONTAL_RULE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^[ \t]*(?:-{3,}|\*{3,}|_{3,})[ \t]*$").unwrap());
+static HEADING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^[ \t]{0,3}#{1,6}[ \t]+(.+?)[ \t#]*$").unwrap());
+static BLOCKQUOTE_MARKER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^[ \t]*>[ \t]?").unwrap());
+static LIST_BULLET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^[ \t]*[-*+][ \t]+").unwrap());
+static IMAGE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"!\[([^\]\n]*)\]\([^)\n]*\)").unwrap());
+static LINK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[([^\]\n]+)\]\([^)\n]*\)").unwrap());
+static INLINE_CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`([^`\n]+)`").unwrap());
+// Paired emphasis markers: inner text must not start or end with whitespace,
+// and the markers must not be glued to surrounding word characters
+static EMPHASIS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        r"(^|[^\w*])\*\*([^\s*](?:[^*\n]*[^\s*])?)\*\*($|[^\w*])",
+        r"(^|[^\w_])__([^\s_](?:[^_\n]*[^\s_])?)__($|[^\w_])",
+        r"(^|[^\w*])\*([^\s*](?:[^*\n]*[^\s*])?)\*($|[^\w*])",
+        r"(^|[^\w_])_([^\s_](?:[^_\n]*[^\s_])?)_($|[^\w_])",
+        r"(^|[^\w~])~~([^\s~](?:[^~\n]*[^\s~])?)~~($|[^\w~])",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect()
+});
+
+/// Remove common Markdown syntax so the literal symbols aren't spoken.
+/// Headings, emphasis, links/images (keeping the visible text), inline code,
+/// code fences, blockquotes and list bullets are handled. Emphasis markers are
+/// only removed when properly paired, so `2 * 3 * 4` or `my_file_name.txt` survive.
+pub fn strip_markdown(text: &str) -> String {
+    let mut text = text.to_string();
+
+    // Code fences and horizontal rules are dropped entirely; fenced content is kept
+    text = CODE_FENCE.replace_all(&text, "").to_string();
+    text = HORIZONTAL_RULE.replace_all(&text, "").to_string();
+
+    // Headings become their own sentence so they don't run into the next line
+    text = HEADING
+        .replace_all(&text, |caps: &regex::Captures| {
+            let heading = caps[1].trim_end();
+            if heading.ends_with(['.', '!', '?', ':', ';']) {
+                heading.to_string()
+            } else {
+                format!("{}.", heading)
+            }
+        })
+        .to_string();
+
+    // Blockquote markers and list bullets at the start of a line
+    text = BLOCKQUOTE_MARKER.replace_all(&text, "").to_string();
+    text = LIST_BULLET.replace_all(&text, "").to_string();
+
+    // Images and links keep only their visible text
+    text = IMAGE.replace_all(&text, "$1").to_string();
+    text = LINK.replace_all(&text, "$1").to_string();
+
+    // Inline code
+    text = INLINE_CODE.replace_all(&text, "$1").to_string();
+
+    for emphasis in EMPHASIS.iter() {
+        text = emphasis.replace_all(&text, "$1$2$3").to_string();
     }
+
+    text
+}
+
+// ============================================================================
+// Text Normalization
+// ============================================================================
+
+/// Optional preprocessing steps applied before normalization
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreprocessOptions {
+    /// Remove Markdown formatting (headings, emphasis, links, inline code)
+    #[serde(default)]
+    pub strip_markdown: bool,
+    /// Seconds of silence at paragraph breaks (blank lines) when a call would otherwise use
+    /// its usual gap there. Chunking keeps paragraphs apart before normalization collapses
+    /// whitespace, so the break survives as a chunk boundary; see `ChunkSilence::with_options`.
+    #[serde(default)]
+    pub paragraph_silence: Option<f32>,
+    /// Leave emoji in the text instead of stripping them
+    #[serde(default)]
+    pub keep_emoji: bool,
+    /// Extra `(from, to)` replacements, applied in order after the built-in symbol table
+    #[serde(default)]
+    pub symbol_replacements: Vec<(String, String)>,
+    /// Read runs of another script in their own language (see `segment_by_script`) instead
+    /// of the whole chunk in the requested one. Each run is synthesized separately and the
+    /// runs are joined without a pause.
+    #[serde(default)]
+    pub mixed_language: bool,
+    /// Leave English ordinals like "3rd" as written instead of spelling them out ("third")
+    #[serde(default)]
+    pub keep_ordinals: bool,
 }
 
-pub fn preprocess_text(text: &str, lang: &str) -> Result<String> {
+// Compiled once; `preprocess_text` runs for every chunk
+static EMOJI_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[\x{1F600}-\x{1F64F}\x{1F300}-\x{1F5FF}\x{1F680}-\x{1F6FF}\x{1F700}-\x{1F77F}\x{1F780}-\x{1F7FF}\x{1F800}-\x{1F8FF}\x{1F900}-\x{1F9FF}\x{1FA00}-\x{1FA6F}\x{1FA70}-\x{1FAFF}\x{2600}-\x{26FF}\x{2700}-\x{27BF}\x{1F1E6}-\x{1F1FF}]+").unwrap()
+});
+static SPACE_BEFORE_PUNCT: LazyLock<Vec<(Regex, &str)>> = LazyLock::new(|| {
+    [(r" ,", ","), (r" \.", "."), (r" !", "!"), (r" \?", "?"), (r" ;", ";"), (r" :", ":"), (r" '", "'")]
+        .into_iter()
+        .map(|(pattern, to)| (Regex::new(pattern).unwrap(), to))
+        .collect()
+});
+static WHITESPACE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+").unwrap());
+static ENDS_WITH_PUNCT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"[.!?;:,'"\u{201C}\u{201D}\u{2018}\u{2019})\]}…。」』】〉》›»]$"#).unwrap()
+});
+// Korean text doesn't space before full-width closing punctuation or after opening brackets
+static KO_SPACE_BEFORE_PUNCT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+([。、」』】〉》])").unwrap());
+static KO_SPACE_AFTER_BRACKET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"([「『【〈《])\s+").unwrap());
+// Sentence endings common in Korean besides those in `ENDS_WITH_PUNCT`, e.g. "좋아요~". A
+// final Hangul letter ends a sentence too: Korean often goes unpunctuated, and a Latin period
+// after it is read out. Text is NFKD by then, so syllables are conjoining jamo, also Hangul.
+static KO_ENDS_WITH_TERMINATOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[~、\p{Hangul}]$").unwrap());
+
+/// `preprocess_text` cleaned the text down to nothing speakable, e.g. it was all emoji or
+/// symbols. Wrapped in language tags it would come out as noise, so the chunk is skipped.
+#[derive(Debug, thiserror::Error)]
+#[error("Nothing left to speak after normalization")]
+pub struct EmptyAfterNormalization;
+
+pub fn preprocess_text(
+    text: &str,
+    lang: &str,
+    lexicon: &HashMap<String, String>,
+    options: &PreprocessOptions,
+) -> Result<String> {
+    // Markdown has to go before any symbol replacement touches `#`, `[` or `_`
+    let text = if options.strip_markdown {
+        strip_markdown(text)
+    } else {
+        text.to_string()
+    };
+
+    // Apply pronunciation overrides before normalization so replacements get normalized too
+    let text = apply_lexicon(&text, lexicon);
+
+    let text = if lang == "en" && !options.keep_ordinals {
+        expand_ordinals(&text)
+    } else {
+        text
+    };
+
     // TODO: Need advanced normalizer for better performance
     let mut text: String = text.nfkd().collect();
 
     // Remove emojis (wide Unicode range)
-    let emoji_pattern = Regex::new(r"[\x{1F600}-\x{1F64F}\x{1F300}-\x{1F5FF}\x{1F680}-\x{1F6FF}\x{1F700}-\x{1F77F}\x{1F780}-\x{1F7FF}\x{1F800}-\x{1F8FF}\x{1F900}-\x{1F9FF}\x{1FA00}-\x{1FA6F}\x{1FA70}-\x{1FAFF}\x{2600}-\x{26FF}\x{2700}-\x{27BF}\x{1F1E6}-\x{1F1FF}]+").unwrap();
-    text = emoji_pattern.replace_all(&text, "").to_string();
+    if !options.keep_emoji {
+        text = EMOJI_PATTERN.replace_all(&text, "").to_string();
+    }
 
     // Replace various dashes and symbols
     let replacements = [
@@ -151,6 +522,9 @@ pub fn preprocess_text(text: &str, lang: &str) -> Result<String> {
     for (from, to) in &replacements {
         text = text.replace(from, to);
     }
+    for (from, to) in &options.symbol_replacements {
+        text = text.replace(from.as_str(), to);
+    }
 
     // Remove special symbols
     let special_symbols = ["♥", "☆", "♡", "©", "\\"];
@@ -170,13 +544,9 @@ pub fn preprocess_text(text: &str, lang: &str) -> Result<String> {
     }
 
     // Fix spacing around punctuation
-    text = Regex::new(r" ,").unwrap().replace_all(&text, ",").to_string();
-    text = Regex::new(r" \.").unwrap().replace_all(&text, ".").to_string();
-    text = Regex::new(r" !").unwrap().replace_all(&text, "!").to_string();
-    text = Regex::new(r" \?").unwrap().replace_all(&text, "?").to_string();
-    text = Regex::new(r" ;").unwrap().replace_all(&text, ";").to_string();
-    text = Regex::new(r" :").unwrap().replace_all(&text, ":").to_string();
-    text = Regex::new(r" '").unwrap().replace_all(&text, "'").to_string();
+    for (pattern, to) in SPACE_BEFORE_PUNCT.iter() {
+        text = pattern.replace_all(&text, *to).to_string();
+    }
 
     // Remove duplicate quotes
     while text.contains("\"\"") {
@@ -190,15 +560,24 @@ pub fn preprocess_text(text: &str, lang: &str) -> Result<String> {
     }
 
     // Remove extra spaces
-    text = Regex::new(r"\s+").unwrap().replace_all(&text, " ").to_string();
+    text = WHITESPACE.replace_all(&text, " ").to_string();
     text = text.trim().to_string();
 
-    // If text doesn't end with punctuation, quotes, or closing brackets, add a period
-    if !text.is_empty() {
-        let ends_with_punct = Regex::new(r#"[.!?;:,'"\u{201C}\u{201D}\u{2018}\u{2019})\]}…。」』】〉》›»]$"#).unwrap();
-        if !ends_with_punct.is_match(&text) {
-            text.push('.');
-        }
+    if lang == "ko" {
+        text = KO_SPACE_BEFORE_PUNCT.replace_all(&text, "$1").to_string();
+        text = KO_SPACE_AFTER_BRACKET.replace_all(&text, "$1").to_string();
+    }
+
+    if !has_speakable_text(&text) {
+        return Err(EmptyAfterNormalization.into());
+    }
+
+    // If text doesn't end with punctuation, quotes, or closing brackets (or, in Korean, a
+    // Hangul letter), add a period
+    let ends_sentence = ENDS_WITH_PUNCT.is_match(&text)
+        || (lang == "ko" && KO_ENDS_WITH_TERMINATOR.is_match(&text));
+    if !text.is_empty() && !ends_sentence {
+        text.push('.');
     }
 
     // Validate language
@@ -212,10 +591,6 @@ pub fn preprocess_text(text: &str, lang: &str) -> Result<String> {
     Ok(text)
 }
 
-pub fn text_to_unicode_values(text: &str) -> Vec<usize> {
-    text.chars().map(|c| c as usize).collect()
-}
-
 pub fn length_to_mask(lengths: &[usize], max_len: Option<usize>) -> Array3<f32> {
     let bsz = lengths.len();
     let max_len = max_len.unwrap_or_else(|| *lengths.iter().max().unwrap_or(&0));
@@ -234,14 +609,129 @@ pub fn get_text_mask(text_ids_lengths: &[usize]) -> Array3<f32> {
     length_to_mask(text_ids_lengths, Some(max_len))
 }
 
-/// Sample noisy latent from normal distribution and apply mask
+/// Retries after a transient ORT failure
+const RUN_RETRIES: u32 = 2;
+/// Delay before the first retry; doubles for each one after
+const RUN_RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Run a model step, retrying up to `RUN_RETRIES` times if it fails with a transient ORT
+/// error (e.g. memory pressure on Android). Anything else, such as a shape mismatch,
+/// fails straight away.
+pub fn run_with_retry<T>(label: &str, mut run: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match run() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < RUN_RETRIES && is_transient_ort_error(&e) => {
+                attempt += 1;
+                log::warn!("{} failed ({:#}); retry {}/{}", label, e, attempt, RUN_RETRIES);
+                std::thread::sleep(RUN_RETRY_BACKOFF * 2u32.pow(attempt - 1));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// ORT failures worth retrying: runtime/engine errors rather than bad input or a bad model
+fn is_transient_ort_error(e: &anyhow::Error) -> bool {
+    use ort::ErrorCode;
+
+    matches!(
+        e.downcast_ref::<ort::Error>().map(|e| e.code()),
+        Some(
+            ErrorCode::GenericFailure
+                | ErrorCode::EngineError
+                | ErrorCode::RuntimeException
+                | ErrorCode::ExecutionProviderFailure
+        )
+    )
+}
+
+/// Largest relative difference between the vocoder's output length and the length the
+/// config predicts, on top of the rounding up to whole latent chunks
+const OUTPUT_LENGTH_TOLERANCE: f32 = 0.02;
+
+/// Compare the vocoder's output length, per utterance, with `duration` seconds at the
+/// configured `sample_rate`. Output is padded up to a whole latent chunk of `chunk_size`
+/// samples, so anything further off means `tts.json` doesn't match the models, and durations
+/// and trimming are wrong. Returns a description of the mismatch, if any.
+pub fn check_output_length(output_len: usize, duration: f32, sample_rate: i32, chunk_size: usize) -> Option<String> {
+    let expected = duration * sample_rate as f32;
+    if expected <= 0.0 {
+        return None;
+    }
+    let diff = output_len as f32 - expected;
+    if diff >= -1.0 && diff <= chunk_size as f32 + expected * OUTPUT_LENGTH_TOLERANCE {
+        return None;
+    }
+
+    Some(format!(
+        "Vocoder produced {} samples for {:.2}s of speech, expected about {} at the configured {} Hz \
+         (output suggests about {:.0} Hz); tts.json may not match the models",
+        output_len,
+        duration,
+        expected as usize,
+        sample_rate,
+        output_len as f32 / duration
+    ))
+}
+
+/// Cut one utterance's vocoder output down to its predicted `duration` and return the
+/// duration the samples actually cover. That's `duration` unless the vocoder produced fewer
+/// samples than predicted (possible for very short utterances), in which case it's the real
+/// length, so reported durations never run ahead of the audio.
+pub fn trim_to_duration(wav: &mut Vec<f32>, duration: f32, sample_rate: i32) -> f32 {
+    let predicted_len = (sample_rate as f32 * duration) as usize;
+    if wav.len() >= predicted_len {
+        wav.truncate(predicted_len);
+        return duration;
+    }
+    log::debug!(
+        "Vocoder produced {} samples for a predicted {}; using the shorter duration",
+        wav.len(),
+        predicted_len
+    );
+    wav.len() as f32 / sample_rate as f32
+}
+
+/// Fewest latent frames an utterance gets. A near-zero predicted duration (e.g. a single
+/// short word) would otherwise round to an empty latent and the vocoder would get nothing.
+pub const MIN_LATENT_LEN: usize = 1;
+
+/// Gaussian noise to start denoising from, sized for the longest `duration` (seconds) and
+/// masked past each utterance's own length, which is at least `MIN_LATENT_LEN` frames.
+/// Durations must be finite and positive.
 pub fn sample_noisy_latent(
     duration: &[f32],
     sample_rate: i32,
     base_chunk_size: i32,
     chunk_compress: i32,
     latent_dim: i32,
-) -> (Array3<f32>, Array3<f32>) {
+) -> Result<(Array3<f32>, Array3<f32>)> {
+    sample_noisy_latent_with_rng(
+        duration,
+        sample_rate,
+        base_chunk_size,
+        chunk_compress,
+        latent_dim,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// `sample_noisy_latent` drawing its noise from `rng`, so a seeded generator gives the same
+/// noise; the shape and mask don't depend on it
+pub fn sample_noisy_latent_with_rng<R: rand::Rng + ?Sized>(
+    duration: &[f32],
+    sample_rate: i32,
+    base_chunk_size: i32,
+    chunk_compress: i32,
+    latent_dim: i32,
+    rng: &mut R,
+) -> Result<(Array3<f32>, Array3<f32>)> {
+    if let Some(bad) = duration.iter().find(|d| !d.is_finite() || **d <= 0.0) {
+        bail!("Duration predictor returned {} seconds; expected a finite positive duration", bad);
+    }
+
     let bsz = duration.len();
     let max_dur = duration.iter().fold(0.0f32, |a, &b| a.max(b));
 
@@ -252,25 +742,24 @@ pub fn sample_noisy_latent(
         .collect();
 
     let chunk_size = (base_chunk_size * chunk_compress) as usize;
-    let latent_len = (wav_len_max + chunk_size - 1) / chunk_size;
+    let latent_len = wav_len_max.div_ceil(chunk_size).max(MIN_LATENT_LEN);
     let latent_dim_val = (latent_dim * chunk_compress) as usize;
 
     let mut noisy_latent = Array3::<f32>::zeros((bsz, latent_dim_val, latent_len));
 
     let normal = Normal::new(0.0, 1.0).unwrap();
-    let mut rng = rand::thread_rng();
 
     for b in 0..bsz {
         for d in 0..latent_dim_val {
             for t in 0..latent_len {
-                noisy_latent[[b, d, t]] = normal.sample(&mut rng);
+                noisy_latent[[b, d, t]] = normal.sample(rng);
             }
         }
     }
 
     let latent_lengths: Vec<usize> = wav_lengths
         .iter()
-        .map(|&len| (len + chunk_size - 1) / chunk_size)
+        .map(|&len| len.div_ceil(chunk_size).max(MIN_LATENT_LEN))
         .collect();
 
     let latent_mask = length_to_mask(&latent_lengths, Some(latent_len));
@@ -284,208 +773,833 @@ pub fn sample_noisy_latent(
         }
     }
 
-    (noisy_latent, latent_mask)
+    Ok((noisy_latent, latent_mask))
 }
 
 // ============================================================================
-// WAV File I/O
+// Audio Post-processing
 // ============================================================================
 
-pub fn write_wav_file<P: AsRef<Path>>(
-    filename: P,
-    audio_data: &[f32],
-    sample_rate: i32,
-) -> Result<()> {
-    let spec = WavSpec {
-        channels: 1,
-        sample_rate: sample_rate as u32,
-        bits_per_sample: 16,
-        sample_format: SampleFormat::Int,
-    };
+/// Subtract the buffer's mean so a constant DC bias doesn't eat headroom or click on playback
+pub fn remove_dc_offset(audio: &mut [f32]) {
+    if audio.is_empty() {
+        return;
+    }
+    // Accumulate in f64 so long buffers don't lose precision
+    let mean = (audio.iter().map(|&s| s as f64).sum::<f64>() / audio.len() as f64) as f32;
+    for sample in audio.iter_mut() {
+        *sample -= mean;
+    }
+}
 
-    let mut writer = WavWriter::create(filename, spec)?;
+/// Cut rumble below `cutoff_hz` with a second-order Butterworth high-pass (RBJ biquad).
+/// Cutoffs outside (0, Nyquist) would make the filter unstable, so they leave the audio as is.
+pub fn high_pass_filter(audio: &mut [f32], sample_rate: i32, cutoff_hz: f32) {
+    let nyquist = sample_rate as f32 / 2.0;
+    if !(cutoff_hz > 0.0 && cutoff_hz < nyquist) {
+        log::warn!("Skipping high-pass filter: cutoff {} Hz is outside (0, {}) Hz", cutoff_hz, nyquist);
+        return;
+    }
 
-    for &sample in audio_data {
-        let clamped = sample.max(-1.0).min(1.0);
-        let val = (clamped * 32767.0) as i16;
-        writer.write_sample(val)?;
+    // Coefficients in f64; at low cutoffs they sit close to 1 and f32 would lose the response
+    let w0 = 2.0 * std::f64::consts::PI * cutoff_hz as f64 / sample_rate as f64;
+    let alpha = w0.sin() / std::f64::consts::SQRT_2; // Q = 1/sqrt(2)
+    let cos_w0 = w0.cos();
+    let a0 = 1.0 + alpha;
+    let b0 = (1.0 + cos_w0) / 2.0 / a0;
+    let b1 = -(1.0 + cos_w0) / a0;
+    let b2 = b0;
+    let a1 = -2.0 * cos_w0 / a0;
+    let a2 = (1.0 - alpha) / a0;
+
+    let (mut x1, mut x2, mut y1, mut y2) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+    for sample in audio.iter_mut() {
+        let x0 = *sample as f64;
+        let y0 = b0 * x0 + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+        x2 = x1;
+        x1 = x0;
+        y2 = y1;
+        y1 = y0;
+        *sample = y0 as f32;
     }
+}
 
-    writer.finalize()?;
-    Ok(())
+/// Scale the buffer by `gain_db` decibels; a very negative (or -inf) gain gives silence
+pub fn apply_gain_db(audio: &mut [f32], gain_db: f32) {
+    let gain = 10f32.powf(gain_db / 20.0);
+    for sample in audio.iter_mut() {
+        *sample *= gain;
+    }
 }
 
-/// Encode audio data to WAV bytes in memory
-pub fn encode_wav_to_bytes(audio_data: &[f32], sample_rate: i32) -> Result<Vec<u8>> {
-    let spec = WavSpec {
-        channels: 1,
-        sample_rate: sample_rate as u32,
-        bits_per_sample: 16,
-        sample_format: SampleFormat::Int,
-    };
+/// How `trim_silence_adaptive` decides what counts as leading or trailing silence.
+/// The defaults only trim clearly silent runs and keep a generous pad.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrimConfig {
+    /// Samples quieter than this many dB below full scale count as silent
+    pub threshold_db: f32,
+    /// A silent run shorter than this is kept as is
+    pub min_silence_ms: u32,
+    /// Silence left before the first and after the last sound, so soft onsets and
+    /// breaths aren't cut off
+    pub keep_padding_ms: u32,
+}
 
-    let mut buffer = std::io::Cursor::new(Vec::new());
-    {
-        let mut writer = WavWriter::new(&mut buffer, spec)?;
-        for &sample in audio_data {
-            let clamped = sample.max(-1.0).min(1.0);
-            let val = (clamped * 32767.0) as i16;
-            writer.write_sample(val)?;
+impl Default for TrimConfig {
+    fn default() -> Self {
+        TrimConfig {
+            threshold_db: -60.0,
+            min_silence_ms: 100,
+            keep_padding_ms: 50,
         }
-        writer.finalize()?;
     }
-    Ok(buffer.into_inner())
 }
 
-// ============================================================================
-// Text Chunking
-// ============================================================================
-
-const MAX_CHUNK_LENGTH: usize = 300;
+/// Cut the silent run at each end of `audio` down to `keep_padding_ms`, if it lasts at
+/// least `min_silence_ms`. Quiet stretches between sounds are never touched, and audio
+/// that's silent throughout is left alone. Returns how many samples were cut from the start.
+pub fn trim_silence_adaptive(audio: &mut Vec<f32>, sample_rate: i32, config: &TrimConfig) -> usize {
+    let threshold = 10f32.powf(config.threshold_db / 20.0);
+    let (Some(first), Some(last)) = (
+        audio.iter().position(|s| s.abs() > threshold),
+        audio.iter().rposition(|s| s.abs() > threshold),
+    ) else {
+        return 0;
+    };
 
-const ABBREVIATIONS: &[&str] = &[
-    "Dr.", "Mr.", "Mrs.", "Ms.", "Prof.", "Sr.", "Jr.",
-    "St.", "Ave.", "Rd.", "Blvd.", "Dept.", "Inc.", "Ltd.",
-    "Co.", "Corp.", "etc.", "vs.", "i.e.", "e.g.", "Ph.D.",
-];
+    let ms_to_samples = |ms: u32| (ms as u64 * sample_rate as u64 / 1000) as usize;
+    let min_silence = ms_to_samples(config.min_silence_ms);
+    let padding = ms_to_samples(config.keep_padding_ms);
 
-pub fn chunk_text(text: &str, max_len: Option<usize>) -> Vec<String> {
-    let max_len = max_len.unwrap_or(MAX_CHUNK_LENGTH);
-    let text = text.trim();
-    
-    if text.is_empty() {
-        return vec![String::new()];
+    let trailing = audio.len() - 1 - last;
+    if trailing >= min_silence {
+        audio.truncate((last + 1 + padding).min(audio.len()));
     }
+    if first >= min_silence {
+        let start = first.saturating_sub(padding);
+        audio.drain(..start);
+        return start;
+    }
+    0
+}
 
-    // Split by paragraphs
-    let para_re = Regex::new(r"\n\s*\n").unwrap();
-    let paragraphs: Vec<&str> = para_re.split(text).collect();
-    let mut chunks = Vec::new();
-
-    for para in paragraphs {
-        let para = para.trim();
-        if para.is_empty() {
+/// Sample ranges `(start, end)`, end exclusive, of the sounds in `audio`: runs of samples
+/// louder than `threshold` (linear amplitude), split wherever at least `min_silence_ms` of
+/// quieter samples lie between them. Shorter quiet stretches stay inside their sound, so
+/// word-internal pauses don't split it. Audio with no such gap is one range; silent audio has none.
+pub fn segment_by_silence(audio: &[f32], sample_rate: i32, min_silence_ms: usize, threshold: f32) -> Vec<(usize, usize)> {
+    let min_silence = (min_silence_ms as u64 * sample_rate as u64 / 1000) as usize;
+    let mut segments = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+
+    for (i, sample) in audio.iter().enumerate() {
+        if sample.abs() <= threshold {
             continue;
         }
-
-        if para.len() <= max_len {
-            chunks.push(para.to_string());
-            continue;
+        match &mut current {
+            Some((_, end)) if i - *end < min_silence => *end = i + 1,
+            _ => {
+                segments.extend(current.take());
+                current = Some((i, i + 1));
+            }
         }
+    }
+    segments.extend(current);
+    segments
+}
 
-        // Split by sentences
-        let sentences = split_sentences(para);
-        let mut current = String::new();
-        let mut current_len = 0;
+/// Largest absolute amplitude in each of `buckets` equal stretches of `audio`, for drawing
+/// a waveform. Always returns `buckets` entries; stretches with no samples are 0.
+pub fn downsample_peaks(audio: &[f32], buckets: usize) -> Vec<f32> {
+    (0..buckets)
+        .map(|i| {
+            let start = i * audio.len() / buckets;
+            let end = (i + 1) * audio.len() / buckets;
+            audio[start..end].iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+        })
+        .collect()
+}
 
-        for sentence in sentences {
-            let sentence = sentence.trim();
-            if sentence.is_empty() {
-                continue;
-            }
+/// Number of samples outside [-1.0, 1.0], which WAV encoding clamps
+pub fn count_clipped_samples(audio: &[f32]) -> usize {
+    audio.iter().filter(|s| s.abs() > 1.0).count()
+}
 
-            let sentence_len = sentence.len();
-            if sentence_len > max_len {
-                // If sentence is longer than max_len, split by comma or space
-                if !current.is_empty() {
-                    chunks.push(current.trim().to_string());
-                    current.clear();
-                    current_len = 0;
-                }
+/// Scale the buffer down so its peak is `target_peak`, if it's louder than that.
+/// Returns the gain applied (1.0 when nothing changed).
+pub fn attenuate_to_peak(audio: &mut [f32], target_peak: f32) -> f32 {
+    let peak = audio.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if peak <= target_peak {
+        return 1.0;
+    }
+    let gain = target_peak / peak;
+    for sample in audio.iter_mut() {
+        *sample *= gain;
+    }
+    gain
+}
 
-                // Try splitting by comma
-                let parts: Vec<&str> = sentence.split(',').collect();
-                for part in parts {
-                    let part = part.trim();
-                    if part.is_empty() {
-                        continue;
-                    }
+/// Scale the buffer so its peak is `target_peak`, louder or quieter. Silence is left alone.
+pub fn normalize_peak(audio: &mut [f32], target_peak: f32) {
+    let peak = audio.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if peak == 0.0 {
+        return;
+    }
+    let gain = target_peak / peak;
+    for sample in audio.iter_mut() {
+        *sample *= gain;
+    }
+}
 
-                    let part_len = part.len();
-                    if part_len > max_len {
-                        // Split by space as last resort
-                        let words: Vec<&str> = part.split_whitespace().collect();
-                        let mut word_chunk = String::new();
-                        let mut word_chunk_len = 0;
-
-                        for word in words {
-                            let word_len = word.len();
-                            if word_chunk_len + word_len + 1 > max_len && !word_chunk.is_empty() {
-                                chunks.push(word_chunk.trim().to_string());
-                                word_chunk.clear();
-                                word_chunk_len = 0;
-                            }
-
-                            if !word_chunk.is_empty() {
-                                word_chunk.push(' ');
-                                word_chunk_len += 1;
-                            }
-                            word_chunk.push_str(word);
-                            word_chunk_len += word_len;
-                        }
+/// Ramp the first `fade_in_ms` up from silence and the last `fade_out_ms` down to it, so the
+/// audio doesn't start or stop with a click. Fades longer than the audio are shortened to fit.
+pub fn apply_fade(audio: &mut [f32], sample_rate: i32, fade_in_ms: u32, fade_out_ms: u32) {
+    let ms_to_samples = |ms: u32| ((ms as u64 * sample_rate as u64 / 1000) as usize).min(audio.len());
+    let fade_in = ms_to_samples(fade_in_ms);
+    let fade_out = ms_to_samples(fade_out_ms);
 
-                        if !word_chunk.is_empty() {
-                            chunks.push(word_chunk.trim().to_string());
-                        }
-                    } else {
-                        if current_len + part_len + 1 > max_len && !current.is_empty() {
-                            chunks.push(current.trim().to_string());
-                            current.clear();
-                            current_len = 0;
-                        }
+    for (i, sample) in audio[..fade_in].iter_mut().enumerate() {
+        *sample *= i as f32 / fade_in as f32;
+    }
+    let len = audio.len();
+    for (i, sample) in audio[len - fade_out..].iter_mut().rev().enumerate() {
+        *sample *= i as f32 / fade_out as f32;
+    }
+}
 
-                        if !current.is_empty() {
-                            current.push_str(", ");
-                            current_len += 2;
-                        }
-                        current.push_str(part);
-                        current_len += part_len;
-                    }
-                }
-                continue;
-            }
+/// One post-processing step of an `AudioPipeline`, with its parameters
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum AudioStage {
+    /// `remove_dc_offset`
+    RemoveDc,
+    /// `high_pass_filter` at `cutoff_hz`
+    Highpass { cutoff_hz: f32 },
+    /// `trim_silence_adaptive`
+    Trim(TrimConfig),
+    /// `apply_fade`
+    Fade { fade_in_ms: u32, fade_out_ms: u32 },
+    /// `normalize_peak` to `peak` (linear, at most 1.0)
+    Normalize { peak: f32 },
+    /// `apply_gain_db`
+    Gain { db: f32 },
+    /// `attenuate_to_peak` at `peak`: only audio louder than it is scaled down
+    Attenuate { peak: f32 },
+}
 
-            if current_len + sentence_len + 1 > max_len && !current.is_empty() {
-                chunks.push(current.trim().to_string());
-                current.clear();
-                current_len = 0;
-            }
+/// Post-processing stages run in list order on synthesized audio. Order matters: a fade
+/// before trimming fades the silence that's then cut, and gain before normalizing is undone.
+/// The recommended order is DC removal, high-pass, trim, fade, normalize, then gain.
+/// The default pipeline has no stages and leaves the audio as synthesis trimmed it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub struct AudioPipeline {
+    pub stages: Vec<AudioStage>,
+}
 
-            if !current.is_empty() {
-                current.push(' ');
-                current_len += 1;
+/// Run each stage of `pipeline` on `audio` in order. Returns how many samples trimming cut
+/// from the start, so timings can be moved to match.
+pub fn apply_pipeline(audio: &mut Vec<f32>, sample_rate: i32, pipeline: &AudioPipeline) -> usize {
+    let mut cut = 0;
+    for stage in &pipeline.stages {
+        match *stage {
+            AudioStage::RemoveDc => remove_dc_offset(audio),
+            AudioStage::Highpass { cutoff_hz } => high_pass_filter(audio, sample_rate, cutoff_hz),
+            AudioStage::Trim(config) => cut += trim_silence_adaptive(audio, sample_rate, &config),
+            AudioStage::Fade { fade_in_ms, fade_out_ms } => apply_fade(audio, sample_rate, fade_in_ms, fade_out_ms),
+            AudioStage::Normalize { peak } => normalize_peak(audio, peak),
+            AudioStage::Gain { db } => apply_gain_db(audio, db),
+            AudioStage::Attenuate { peak } => {
+                let gain = attenuate_to_peak(audio, peak);
+                if gain < 1.0 {
+                    log::info!("Attenuated audio by {:.2} dB to avoid clipping", 20.0 * gain.log10());
+                }
             }
-            current.push_str(sentence);
-            current_len += sentence_len;
-        }
-
-        if !current.is_empty() {
-            chunks.push(current.trim().to_string());
         }
     }
-
-    if chunks.is_empty() {
-        vec![String::new()]
-    } else {
-        chunks
-    }
+    cut
 }
 
-pub fn split_sentences(text: &str) -> Vec<String> {
-    // Rust's regex doesn't support lookbehind, so we use a simpler approach
-    // Split on sentence boundaries and then check if they're abbreviations
-    let re = Regex::new(r"([.!?])\s+").unwrap();
-    
-    // Find all matches
-    let matches: Vec<_> = re.find_iter(text).collect();
-    if matches.is_empty() {
-        return vec![text.to_string()];
+// ============================================================================
+// WAV File I/O
+// ============================================================================
+
+/// Bits per sample used unless a caller asks for another depth
+pub const DEFAULT_WAV_BIT_DEPTH: u16 = 16;
+
+/// Channels written unless a caller asks for stereo; the model itself is mono
+pub const DEFAULT_WAV_CHANNELS: u16 = 1;
+
+/// Write PCM WAV with `bit_depth` of 8, 16 or 24 bits per sample. With `channels` of 2 the
+/// mono audio is copied to both left and right: a container change for players that want
+/// stereo, not stereo synthesis.
+pub fn write_wav_file<P: AsRef<Path>>(
+    filename: P,
+    audio_data: &[f32],
+    sample_rate: i32,
+    bit_depth: u16,
+    channels: u16,
+) -> Result<()> {
+    let spec = wav_spec(sample_rate, bit_depth, channels)?;
+
+    let mut writer = WavWriter::create(filename, spec)?;
+    write_wav_samples(&mut writer, audio_data, bit_depth)?;
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Encode audio data to WAV bytes in memory, with `bit_depth` and `channels` as in `write_wav_file`.
+/// The writer is finalized per call, so each result is a standalone file whose header sizes
+/// match `audio_data` exactly, even when it's one chunk of a longer utterance.
+pub fn encode_wav_to_bytes(audio_data: &[f32], sample_rate: i32, bit_depth: u16, channels: u16) -> Result<Vec<u8>> {
+    let spec = wav_spec(sample_rate, bit_depth, channels)?;
+
+    // 44-byte header plus the samples
+    let bytes_per_frame = bit_depth as usize / 8 * channels as usize;
+    let mut buffer = std::io::Cursor::new(Vec::with_capacity(44 + audio_data.len() * bytes_per_frame));
+    {
+        let mut writer = WavWriter::new(&mut buffer, spec)?;
+        write_wav_samples(&mut writer, audio_data, bit_depth)?;
+        writer.finalize()?;
+    }
+    Ok(buffer.into_inner())
+}
+
+/// Writes audio to a WAV file a piece at a time, so long output never has to sit in memory
+/// whole. Samples are encoded as in `write_wav_file`.
+pub struct WavStreamWriter {
+    writer: WavWriter<std::io::BufWriter<File>>,
+    sample_rate: i32,
+    bit_depth: u16,
+    samples_written: u64,
+}
+
+impl WavStreamWriter {
+    pub fn create<P: AsRef<Path>>(filename: P, sample_rate: i32, bit_depth: u16, channels: u16) -> Result<Self> {
+        let spec = wav_spec(sample_rate, bit_depth, channels)?;
+        Ok(WavStreamWriter {
+            writer: WavWriter::create(filename, spec)?,
+            sample_rate,
+            bit_depth,
+            samples_written: 0,
+        })
+    }
+
+    pub fn write(&mut self, audio_data: &[f32]) -> Result<()> {
+        write_wav_samples(&mut self.writer, audio_data, self.bit_depth)?;
+        self.samples_written += audio_data.len() as u64;
+        Ok(())
+    }
+
+    /// Append `seconds` of silence, rounded down to whole samples like `concat_chunks_with_gaps`
+    pub fn write_silence(&mut self, seconds: f32) -> Result<()> {
+        let mut remaining = (seconds * self.sample_rate as f32) as usize;
+        let zeros = vec![0.0f32; remaining.min(WAV_ENCODE_BLOCK)];
+        while remaining > 0 {
+            let n = remaining.min(zeros.len());
+            self.write(&zeros[..n])?;
+            remaining -= n;
+        }
+        Ok(())
+    }
+
+    /// Seconds of audio written so far, however many channels it's written to
+    pub fn duration(&self) -> f32 {
+        self.samples_written as f32 / self.sample_rate as f32
+    }
+
+    /// Fix up the header and close the file, returning the total duration in seconds
+    pub fn finalize(self) -> Result<f32> {
+        let duration = self.duration();
+        self.writer.finalize()?;
+        Ok(duration)
+    }
+}
+
+/// Somewhere synthesized samples can be appended: a `Vec<f32>` in memory, or a
+/// `hound::WavWriter` to write them straight to disk
+pub trait SampleSink {
+    fn append(&mut self, samples: &[f32]) -> Result<()>;
+}
+
+impl SampleSink for Vec<f32> {
+    fn append(&mut self, samples: &[f32]) -> Result<()> {
+        self.extend_from_slice(samples);
+        Ok(())
+    }
+}
+
+/// Encodes as `write_wav_file` does, at the writer's own bit depth and channel count
+impl<W: std::io::Write + std::io::Seek> SampleSink for WavWriter<W> {
+    fn append(&mut self, samples: &[f32]) -> Result<()> {
+        let spec = self.spec();
+        if spec.sample_format != SampleFormat::Int {
+            bail!("Only integer PCM WAV writers are supported");
+        }
+        write_wav_samples(self, samples, spec.bits_per_sample)
+    }
+}
+
+impl<S: SampleSink + ?Sized> SampleSink for &mut S {
+    fn append(&mut self, samples: &[f32]) -> Result<()> {
+        (**self).append(samples)
+    }
+}
+
+/// Read a mono integer PCM WAV file as f32 samples in [-1.0, 1.0], returning (samples, sample rate)
+pub fn read_wav_file<P: AsRef<Path>>(filename: P) -> Result<(Vec<f32>, i32)> {
+    let reader = hound::WavReader::open(filename)?;
+    let spec = reader.spec();
+    if spec.channels != 1 || spec.sample_format != SampleFormat::Int {
+        bail!(
+            "Expected mono integer PCM, got {} channel(s) of {:?}",
+            spec.channels,
+            spec.sample_format
+        );
+    }
+
+    let scale = ((1i64 << (spec.bits_per_sample - 1)) - 1) as f32;
+    let samples = reader
+        .into_samples::<i32>()
+        .map(|sample| Ok(sample? as f32 / scale))
+        .collect::<Result<Vec<f32>>>()?;
+    Ok((samples, spec.sample_rate as i32))
+}
+
+/// Read WAV files and join them in order with `gap_secs` of silence between them.
+/// All files must share a sample rate. Returns (samples, sample rate, total duration).
+pub fn concat_wav_files<P: AsRef<Path>>(paths: &[P], gap_secs: f32) -> Result<(Vec<f32>, i32, f32)> {
+    let mut sample_rate = None;
+    let mut chunks = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let path = path.as_ref();
+        let (samples, rate) = read_wav_file(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        match sample_rate {
+            None => sample_rate = Some(rate),
+            Some(expected) if expected != rate => bail!(
+                "{} is {} Hz but earlier files are {} Hz",
+                path.display(),
+                rate,
+                expected
+            ),
+            Some(_) => {}
+        }
+        let duration = samples.len() as f32 / rate as f32;
+        chunks.push((path.display().to_string(), samples, duration));
+    }
+
+    let Some(sample_rate) = sample_rate else {
+        bail!("No WAV files to join");
+    };
+    let (samples, duration, _) = concat_chunks(chunks, sample_rate, gap_secs);
+    Ok((samples, sample_rate, duration))
+}
+
+fn wav_spec(sample_rate: i32, bit_depth: u16, channels: u16) -> Result<WavSpec> {
+    if !matches!(bit_depth, 8 | 16 | 24) {
+        bail!("Unsupported WAV bit depth {}; use 8, 16 or 24", bit_depth);
+    }
+    if !matches!(channels, 1 | 2) {
+        bail!("Unsupported WAV channel count {}; use 1 or 2", channels);
+    }
+    Ok(WavSpec {
+        channels,
+        sample_rate: sample_rate as u32,
+        bits_per_sample: bit_depth,
+        sample_format: SampleFormat::Int,
+    })
+}
+
+/// Write mono `audio_data`, repeating each sample once per channel in the writer's spec
+fn write_wav_samples<W: std::io::Write + std::io::Seek>(
+    writer: &mut WavWriter<W>,
+    audio_data: &[f32],
+    bit_depth: u16,
+) -> Result<()> {
+    let channels = writer.spec().channels;
+    match bit_depth {
+        16 => {
+            // Convert in blocks through hound's 16-bit writer, which skips the per-sample
+            // format dispatch and error checks of `write_sample`. Same bytes, much faster
+            // for long audio.
+            for block in audio_data.chunks(WAV_ENCODE_BLOCK) {
+                let mut block_writer = writer.get_i16_writer(block.len() as u32 * channels as u32);
+                for &sample in block {
+                    let sample = f32_to_i16(sample);
+                    for _ in 0..channels {
+                        block_writer.write_sample(sample);
+                    }
+                }
+                block_writer.flush()?;
+            }
+        }
+        // hound stores 8-bit samples unsigned, as WAV requires
+        8 => {
+            for &sample in audio_data {
+                let sample = (clamp_sample(sample) * 127.0) as i8;
+                for _ in 0..channels {
+                    writer.write_sample(sample)?;
+                }
+            }
+        }
+        // hound packs 24-bit samples into 3 bytes
+        24 => {
+            for &sample in audio_data {
+                let sample = (clamp_sample(sample) * 8_388_607.0) as i32;
+                for _ in 0..channels {
+                    writer.write_sample(sample)?;
+                }
+            }
+        }
+        _ => bail!("Unsupported WAV bit depth {}; use 8, 16 or 24", bit_depth),
+    }
+    Ok(())
+}
+
+/// Samples converted per block when writing 16-bit WAV
+const WAV_ENCODE_BLOCK: usize = 64 * 1024;
+
+#[inline]
+fn clamp_sample(sample: f32) -> f32 {
+    sample.clamp(-1.0, 1.0)
+}
+
+/// Clamp to [-1.0, 1.0] and scale to a 16-bit sample
+#[inline]
+fn f32_to_i16(sample: f32) -> i16 {
+    (clamp_sample(sample) * 32767.0) as i16
+}
+
+/// A 44-byte mono PCM WAV header announcing `total_samples` samples, for clients that send
+/// one header and then stream raw samples after it. The samples must be encoded as
+/// `encode_wav_to_bytes` would: little-endian, 8-bit unsigned, 16- or 24-bit signed.
+pub fn wav_header_only(sample_rate: i32, total_samples: u64, bit_depth: u16) -> Result<Vec<u8>> {
+    wav_spec(sample_rate, bit_depth, DEFAULT_WAV_CHANNELS)?;
+    let block_align = bit_depth / 8;
+    let data_len = total_samples
+        .checked_mul(block_align as u64)
+        .and_then(|len| u32::try_from(len).ok())
+        .filter(|len| *len <= u32::MAX - 36)
+        .context("Too many samples for a WAV file")?;
+
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(36 + data_len).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    header.extend_from_slice(&1u16.to_le_bytes()); // mono
+    header.extend_from_slice(&(sample_rate as u32).to_le_bytes());
+    header.extend_from_slice(&(sample_rate as u32 * block_align as u32).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&bit_depth.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    Ok(header)
+}
+
+/// Encode audio data as headerless 32-bit float little-endian PCM (4 bytes per sample)
+pub fn encode_pcm_f32le(audio_data: &[f32]) -> Vec<u8> {
+    audio_data.iter().flat_map(|sample| sample.to_le_bytes()).collect()
+}
+
+/// Sample encoding for `write_pcm_file`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PcmFormat {
+    /// Signed 16-bit, scaled and clamped exactly as `encode_wav_to_bytes` does
+    Int16,
+    /// 32-bit float in [-1.0, 1.0] as synthesized, unclamped
+    Float32,
+}
+
+/// Write headerless PCM for tools that take raw samples, e.g. `ffmpeg -f s16le -ac 1`.
+/// The file is nothing but mono little-endian samples in `format`, one after another, so
+/// it's exactly 2 (`Int16`) or 4 (`Float32`) bytes per sample; the sample rate isn't recorded
+/// and has to be passed along separately.
+pub fn write_pcm_file<P: AsRef<Path>>(filename: P, audio_data: &[f32], format: PcmFormat) -> Result<()> {
+    use std::io::Write;
+
+    let mut writer = std::io::BufWriter::new(File::create(filename)?);
+    for &sample in audio_data {
+        match format {
+            PcmFormat::Int16 => writer.write_all(&f32_to_i16(sample).to_le_bytes())?,
+            PcmFormat::Float32 => writer.write_all(&sample.to_le_bytes())?,
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// ============================================================================
+// Text Chunking
+// ============================================================================
+
+const MAX_CHUNK_LENGTH: usize = 300;
+
+/// Longest text `TextToSpeech::call_single` accepts as one unit; anything longer is
+/// unlikely to be a single sentence and should go through `chunk_text`
+pub const MAX_SINGLE_CALL_CHARS: usize = 2 * MAX_CHUNK_LENGTH;
+
+const ABBREVIATIONS: &[&str] = &[
+    "Dr.", "Mr.", "Mrs.", "Ms.", "Prof.", "Sr.", "Jr.",
+    "St.", "Ave.", "Rd.", "Blvd.", "Dept.", "Inc.", "Ltd.",
+    "Co.", "Corp.", "etc.", "vs.", "i.e.", "e.g.", "Ph.D.",
+];
+
+/// Controls how `chunk_text` splits long input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkConfig {
+    /// Maximum chunk length in bytes
+    pub max_len: usize,
+    /// Split sentences longer than `max_len` at commas before falling back to spaces
+    pub split_on_comma: bool,
+    /// Treat blank lines as hard chunk boundaries
+    pub split_on_paragraph: bool,
+    /// Start a new chunk at each numbered list item ("1.", "2)") at the start of a line,
+    /// dropping the marker so only the item text is read
+    pub split_list_items: bool,
+}
+
+impl Default for ChunkConfig {
+    fn default() -> Self {
+        ChunkConfig {
+            max_len: MAX_CHUNK_LENGTH,
+            split_on_comma: true,
+            split_on_paragraph: true,
+            split_list_items: true,
+        }
+    }
+}
+
+impl ChunkConfig {
+    /// Default config with the chunk length the model handles well for `lang`
+    pub fn for_language(lang: &str) -> Self {
+        ChunkConfig {
+            max_len: if lang == "ko" { 120 } else { MAX_CHUNK_LENGTH },
+            ..Default::default()
+        }
     }
+}
+
+/// Whether `text` has anything to say: at least one letter or digit. Empty, whitespace-only
+/// and symbol- or emoji-only text (which normalization strips to nothing) is rejected
+/// before it reaches the models.
+pub fn has_speakable_text(text: &str) -> bool {
+    text.chars().any(char::is_alphanumeric)
+}
+
+pub fn chunk_text(text: &str, config: &ChunkConfig) -> Vec<String> {
+    chunk_text_tagged(text, config)
+        .into_iter()
+        .map(|(chunk, _)| chunk)
+        .collect()
+}
+
+/// `chunk_text`, also flagging each chunk that begins a new paragraph (after a blank line).
+/// The first chunk is never flagged.
+pub fn chunk_text_tagged(text: &str, config: &ChunkConfig) -> Vec<(String, bool)> {
+    let max_len = config.max_len;
+    let text = text.trim();
     
-    let mut sentences = Vec::new();
+    if text.is_empty() {
+        return vec![(String::new(), false)];
+    }
+
+    // Split by paragraphs
+    let paragraphs: Vec<&str> = if config.split_on_paragraph {
+        split_paragraphs(text)
+    } else {
+        vec![text]
+    };
+    let mut chunks = Vec::new();
+    let mut paragraph_starts = Vec::new();
+
+    // Numbered list items are split off as blocks of their own; only a paragraph's first
+    // block starts a paragraph
+    let blocks: Vec<(&str, bool)> = paragraphs
+        .into_iter()
+        .flat_map(|para| {
+            let items = if config.split_list_items {
+                split_list_items(para)
+            } else {
+                vec![para]
+            };
+            items.into_iter().enumerate().map(|(i, item)| (item, i == 0))
+        })
+        .collect();
+
+    for (para, starts_paragraph) in blocks {
+        let para = para.trim();
+        if para.is_empty() {
+            continue;
+        }
+        if starts_paragraph && !chunks.is_empty() {
+            paragraph_starts.push(chunks.len());
+        }
+
+        if para.len() <= max_len {
+            chunks.push(para.to_string());
+            continue;
+        }
+
+        // Split by sentences
+        let sentences = split_sentences(para);
+        let mut current = String::new();
+        let mut current_len = 0;
+
+        for sentence in sentences {
+            let sentence = sentence.trim();
+            if sentence.is_empty() {
+                continue;
+            }
+
+            let sentence_len = sentence.len();
+            if sentence_len > max_len {
+                // If sentence is longer than max_len, split by comma or space
+                if !current.is_empty() {
+                    chunks.push(current.trim().to_string());
+                    current.clear();
+                    current_len = 0;
+                }
+
+                if !config.split_on_comma {
+                    // Keep the sentence intact up to a hard split on spaces
+                    chunks.extend(split_by_words(sentence, max_len));
+                    continue;
+                }
+
+                // Try splitting by comma
+                let parts: Vec<&str> = sentence.split(',').collect();
+                for part in parts {
+                    let part = part.trim();
+                    if part.is_empty() {
+                        continue;
+                    }
+
+                    let part_len = part.len();
+                    if part_len > max_len {
+                        // Split by space as last resort
+                        chunks.extend(split_by_words(part, max_len));
+                    } else {
+                        if current_len + part_len + 1 > max_len && !current.is_empty() {
+                            chunks.push(current.trim().to_string());
+                            current.clear();
+                            current_len = 0;
+                        }
+
+                        if !current.is_empty() {
+                            current.push_str(", ");
+                            current_len += 2;
+                        }
+                        current.push_str(part);
+                        current_len += part_len;
+                    }
+                }
+                continue;
+            }
+
+            if current_len + sentence_len + 1 > max_len && !current.is_empty() {
+                chunks.push(current.trim().to_string());
+                current.clear();
+                current_len = 0;
+            }
+
+            if !current.is_empty() {
+                current.push(' ');
+                current_len += 1;
+            }
+            current.push_str(sentence);
+            current_len += sentence_len;
+        }
+
+        if !current.is_empty() {
+            chunks.push(current.trim().to_string());
+        }
+    }
+
+    if chunks.is_empty() {
+        return vec![(String::new(), false)];
+    }
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| (chunk, paragraph_starts.contains(&i)))
+        .collect()
+}
+
+static PARAGRAPH_BREAK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n\s*\n").unwrap());
+
+/// Split text at blank lines into paragraphs
+pub fn split_paragraphs(text: &str) -> Vec<&str> {
+    PARAGRAPH_BREAK.split(text).collect()
+}
+
+// A numbered list marker ("1.", "2)") at the start of a line; group 1 is the marker itself
+static LIST_MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^[ \t]*(\d{1,3}[.)])[ \t]+").unwrap());
+
+/// Split text into numbered list items, dropping the "1." / "2)" markers. Text before the
+/// first marker is kept as an item of its own; text without markers comes back whole.
+pub fn split_list_items(text: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut last = 0;
+    for marker in LIST_MARKER.find_iter(text) {
+        items.push(&text[last..marker.start()]);
+        last = marker.end();
+    }
+    items.push(&text[last..]);
+    items.retain(|item| !item.trim().is_empty());
+    items
+}
+
+/// Whether the punctuation at `pos` closes a list marker such as "1." at the start of a line
+fn is_list_marker_at(text: &str, pos: usize) -> bool {
+    let line_start = text[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let head = text[line_start..pos].trim_start_matches([' ', '\t']);
+    (1..=3).contains(&head.len()) && head.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Greedily pack whitespace-separated words into pieces no longer than `max_len`
+fn split_by_words(text: &str, max_len: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut word_chunk = String::new();
+    let mut word_chunk_len = 0;
+
+    for word in text.split_whitespace() {
+        let word_len = word.len();
+        if word_chunk_len + word_len + 1 > max_len && !word_chunk.is_empty() {
+            pieces.push(word_chunk.trim().to_string());
+            word_chunk.clear();
+            word_chunk_len = 0;
+        }
+
+        if !word_chunk.is_empty() {
+            word_chunk.push(' ');
+            word_chunk_len += 1;
+        }
+        word_chunk.push_str(word);
+        word_chunk_len += word_len;
+    }
+
+    if !word_chunk.is_empty() {
+        pieces.push(word_chunk.trim().to_string());
+    }
+
+    pieces
+}
+
+static SENTENCE_END: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"([.!?])\s+").unwrap());
+
+pub fn split_sentences(text: &str) -> Vec<String> {
+    // Rust's regex doesn't support lookbehind, so we use a simpler approach
+    // Split on sentence boundaries and then check if they're abbreviations
+    let mut boundaries = Vec::new();
     let mut last_end = 0;
-    
-    for m in matches {
+
+    for m in SENTENCE_END.find_iter(text) {
         // Get the text before the punctuation
         let before_punc = &text[last_end..m.start()];
         
@@ -498,458 +1612,2674 @@ pub fn split_sentences(text: &str) -> Vec<String> {
                 break;
             }
         }
-        
-        if !is_abbrev {
-            // This is a real sentence boundary
-            sentences.push(text[last_end..m.end()].to_string());
-            last_end = m.end();
+        
+        // Neither is the "1." that opens a numbered list item
+        if !is_abbrev && !is_list_marker_at(text, m.start()) {
+            // This is a real sentence boundary
+            boundaries.push(m.end());
+            last_end = m.end();
+        }
+    }
+
+    // A list item starts a new sentence even when the line before it has no end punctuation
+    for caps in LIST_MARKER.captures_iter(text) {
+        let start = caps.get(1).unwrap().start();
+        if start > 0 {
+            boundaries.push(start);
+        }
+    }
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for end in boundaries {
+        sentences.push(text[start..end].to_string());
+        start = end;
+    }
+
+    // Add the remaining text
+    if start < text.len() {
+        sentences.push(text[start..].to_string());
+    }
+
+    if sentences.is_empty() {
+        vec![text.to_string()]
+    } else {
+        sentences
+    }
+}
+
+// ============================================================================
+// Pause Markers
+// ============================================================================
+
+static PAUSE_MARKER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\[pause:([^\]]*)\]").unwrap());
+
+/// Split `text` on inline `[pause:<ms>]` markers into `(text, pause_after_seconds)` parts.
+/// Invalid markers (negative or non-numeric) are dropped with a warning. Marker text is
+/// always removed, so it never reaches `preprocess_text`.
+pub fn split_pause_markers(text: &str) -> Vec<(String, f32)> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut last = 0;
+
+    for caps in PAUSE_MARKER.captures_iter(text) {
+        let m = caps.get(0).unwrap();
+        current.push_str(&text[last..m.start()]);
+        last = m.end();
+
+        match caps[1].trim().parse::<f32>() {
+            Ok(ms) if ms.is_finite() && ms >= 0.0 => {
+                parts.push((std::mem::take(&mut current), ms / 1000.0));
+            }
+            _ => {
+                log::warn!("Ignoring invalid pause marker {}", m.as_str());
+                current.push(' ');
+            }
+        }
+    }
+
+    current.push_str(&text[last..]);
+    parts.push((current, 0.0));
+    parts
+}
+
+/// Progress through a multi-chunk synthesis, reported as each chunk finishes
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkProgress {
+    /// Chunks finished so far, counting up to `total_chunks`
+    pub chunks_done: usize,
+    pub total_chunks: usize,
+    /// Seconds of speech synthesized so far, not counting silence between chunks
+    pub duration_done: f32,
+}
+
+/// Number of chunks `synthesize_with_pauses` will synthesize for `text`
+pub fn count_synthesis_chunks(text: &str, lang: &str) -> usize {
+    let config = ChunkConfig::for_language(lang);
+    split_pause_markers(text)
+        .iter()
+        .filter(|(part, _)| has_speakable_text(part))
+        .map(|(part, _)| chunk_text(part, &config).len())
+        .sum()
+}
+
+/// Cut `text` after the chunk that brings the running character count up to `max_chars`,
+/// keeping pause markers and paragraph breaks before the cut. Returns the text to synthesize
+/// and whether anything was dropped; `text` comes back unchanged when it fits.
+pub fn truncate_to_char_budget(text: &str, lang: &str, max_chars: usize) -> (String, bool) {
+    let config = ChunkConfig::for_language(lang);
+    let parts = split_pause_markers(text);
+    let mut kept = String::new();
+    let mut used = 0;
+
+    for (i, (part, pause)) in parts.iter().enumerate() {
+        if has_speakable_text(part) {
+            let chunks = chunk_text_tagged(part, &config);
+            for (j, (chunk, starts_paragraph)) in chunks.iter().enumerate() {
+                if j > 0 {
+                    kept.push_str(if *starts_paragraph { "\n\n" } else { " " });
+                }
+                kept.push_str(chunk);
+                used += chunk.chars().count();
+
+                if used >= max_chars {
+                    let rest_speakable = j + 1 < chunks.len()
+                        || parts[i + 1..].iter().any(|(rest, _)| has_speakable_text(rest));
+                    return if rest_speakable { (kept, true) } else { (text.to_string(), false) };
+                }
+            }
+        }
+        if i + 1 < parts.len() {
+            kept.push_str(&format!(" [pause:{}] ", pause * 1000.0));
+        }
+    }
+
+    (text.to_string(), false)
+}
+
+/// Synthesize `text` honoring `[pause:<ms>]` markers: each stretch of text between markers
+/// goes through `synthesize`, and the marker's silence is inserted where it stood.
+/// Returns (samples, duration, chunk timings) like `TextToSpeech::call_with_timings`.
+pub fn synthesize_with_pauses<F, E>(
+    text: &str,
+    sample_rate: i32,
+    mut synthesize: F,
+) -> std::result::Result<(Vec<f32>, f32, Vec<ChunkTiming>), E>
+where
+    F: FnMut(&str) -> std::result::Result<(Vec<f32>, f32, Vec<ChunkTiming>), E>,
+{
+    let mut leading = 0.0f32;
+    let mut synthesized = Vec::new();
+    let mut gaps: Vec<f32> = Vec::new();
+    let mut part_timings = Vec::new();
+
+    for (part, pause) in split_pause_markers(text) {
+        if !has_speakable_text(&part) {
+            // Back-to-back markers add up
+            match gaps.last_mut() {
+                Some(gap) => *gap += pause,
+                None => leading += pause,
+            }
+            continue;
+        }
+
+        let (wav, dur, timings) = synthesize(&part)?;
+        synthesized.push((part, wav, dur));
+        gaps.push(pause);
+        part_timings.push(timings);
+    }
+
+    let trailing = gaps.last().copied().unwrap_or(0.0);
+    let (wav_cat, dur_cat, spans) = concat_chunks_with_gaps(synthesized, &gaps, sample_rate);
+
+    let mut wav = vec![0.0f32; (leading * sample_rate as f32) as usize];
+    wav.extend_from_slice(&wav_cat);
+    wav.resize(wav.len() + (trailing * sample_rate as f32) as usize, 0.0);
+
+    // Shift each part's chunk timings to where the part landed in the output
+    let timings = part_timings
+        .into_iter()
+        .zip(&spans)
+        .flat_map(|(timings, span)| {
+            let offset = leading + span.start;
+            timings.into_iter().map(move |t| ChunkTiming {
+                text: t.text,
+                start: t.start + offset,
+                end: t.end + offset,
+            })
+        })
+        .collect();
+
+    Ok((wav, leading + dur_cat + trailing, timings))
+}
+
+/// Cut joined audio after the last chunk in `timings` that ends within `cap` seconds, as
+/// `TextToSpeech::call` stops adding chunks at `max_duration_seconds`. The first chunk is
+/// always kept. Returns whether anything was cut.
+pub fn cap_at_chunk_boundary(wav: &mut Vec<f32>, timings: &mut Vec<ChunkTiming>, sample_rate: i32, cap: f32) -> bool {
+    let kept = timings.iter().skip(1).take_while(|t| t.end <= cap).count() + 1;
+    let end = match timings.get(kept - 1) {
+        Some(last) if kept < timings.len() || last.end > cap => last.end,
+        _ => return false,
+    };
+    timings.truncate(kept);
+    wav.truncate(((end * sample_rate as f32).round() as usize).min(wav.len()));
+    true
+}
+
+// ============================================================================
+// Subtitles
+// ============================================================================
+
+/// A synthesized chunk's text and its position in the output audio, in seconds
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkTiming {
+    pub text: String,
+    pub start: f32,
+    pub end: f32,
+}
+
+/// Seconds of silence inserted between chunks: `paragraph` before a chunk that starts a new
+/// paragraph, `sentence` everywhere else
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkSilence {
+    pub sentence: f32,
+    pub paragraph: f32,
+    /// Exact number of zero samples between chunks, used by `TextToSpeech::call` in place of
+    /// both durations. Converting seconds rounds down to whole samples, differently at each
+    /// sample rate; this gives gapless joins and audio matched against other sources.
+    pub samples: Option<usize>,
+}
+
+impl ChunkSilence {
+    /// The same silence at every chunk boundary
+    pub fn uniform(seconds: f32) -> Self {
+        ChunkSilence {
+            sentence: seconds,
+            paragraph: seconds,
+            samples: None,
+        }
+    }
+
+    /// Use the preprocessing options' `paragraph_silence` between paragraphs, if set
+    pub fn with_options(self, options: &PreprocessOptions) -> Self {
+        ChunkSilence {
+            paragraph: options.paragraph_silence.unwrap_or(self.paragraph),
+            ..self
+        }
+    }
+
+    /// Gaps for `concat_chunks_with_gaps` between chunks flagged by `chunk_text_tagged`
+    pub fn gaps(&self, starts_paragraph: &[bool]) -> Vec<f32> {
+        starts_paragraph
+            .iter()
+            .skip(1)
+            .map(|&starts| if starts { self.paragraph } else { self.sentence })
+            .collect()
+    }
+}
+
+/// Join synthesized `(text, samples, duration)` chunks in order with `silence_duration`
+/// seconds of silence between them, returning (samples, total duration, chunk timings)
+pub fn concat_chunks(
+    chunks: Vec<(String, Vec<f32>, f32)>,
+    sample_rate: i32,
+    silence_duration: f32,
+) -> (Vec<f32>, f32, Vec<ChunkTiming>) {
+    let gaps = vec![silence_duration; chunks.len()];
+    concat_chunks_with_gaps(chunks, &gaps, sample_rate)
+}
+
+/// `concat_chunks` with its own silence after each chunk: `gaps[i]` seconds go between
+/// chunk `i` and `i + 1`. Nothing is added after the last chunk.
+pub fn concat_chunks_with_gaps(
+    chunks: Vec<(String, Vec<f32>, f32)>,
+    gaps: &[f32],
+    sample_rate: i32,
+) -> (Vec<f32>, f32, Vec<ChunkTiming>) {
+    let mut writer = ChunkWriter::new(Vec::new(), sample_rate, ChunkSilence::uniform(0.0));
+    for (i, (text, wav, dur)) in chunks.into_iter().enumerate() {
+        let gap = if i == 0 { 0.0 } else { gaps[i - 1] };
+        writer
+            .push_chunk(text, &wav, dur, gap)
+            .expect("appending to a Vec can't fail");
+    }
+    writer.finish()
+}
+
+/// Synthesizes one chunk of text into `(samples, duration)`
+pub type ChunkSynthesizer<'a> = dyn FnMut(&str) -> Result<(Vec<f32>, f32)> + 'a;
+
+/// Chunk `text` and join what `synthesize` makes of each chunk as `concat_chunks_with_gaps`
+/// would, with `FAILED_CHUNK_SILENCE` in place of chunks it fails on. Returns the audio, its
+/// duration and the failures; errors if every chunk fails.
+pub fn best_effort_chunks(
+    synthesize: &mut ChunkSynthesizer<'_>,
+    text: &str,
+    lang: &str,
+    sample_rate: i32,
+    silence: ChunkSilence,
+) -> Result<(Vec<f32>, f32, Vec<ChunkError>)> {
+    if !has_speakable_text(text) {
+        bail!("Text has nothing to speak");
+    }
+    let (chunks, starts_paragraph): (Vec<String>, Vec<bool>) =
+        chunk_text_tagged(text, &ChunkConfig::for_language(lang)).into_iter().unzip();
+    let gaps = silence.gaps(&starts_paragraph);
+
+    let total = chunks.len();
+    let mut failures = Vec::new();
+    let mut synthesized = Vec::with_capacity(total);
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        match synthesize(&chunk) {
+            Ok((wav, dur)) => synthesized.push((chunk, wav, dur)),
+            Err(e) => {
+                log::warn!("Chunk {} of {} failed, leaving silence in its place: {:#}", index + 1, total, e);
+                let placeholder = vec![0.0f32; (FAILED_CHUNK_SILENCE * sample_rate as f32) as usize];
+                failures.push(ChunkError { index, text: chunk.clone(), error: format!("{:#}", e) });
+                synthesized.push((chunk, placeholder, FAILED_CHUNK_SILENCE));
+            }
+        }
+    }
+    if failures.len() == total {
+        bail!("Every chunk failed; the first: {}", failures[0].error);
+    }
+
+    let (wav, duration, _) = concat_chunks_with_gaps(synthesized, &gaps, sample_rate);
+    Ok((wav, duration, failures))
+}
+
+/// Appends chunks to a `SampleSink` with silence between them, keeping track of where each
+/// one lands. Several `TextToSpeech::call_into_writer` calls can share one writer to build
+/// a long file piece by piece; each call's chunks are timed from the end of the last.
+pub struct ChunkWriter<S: SampleSink> {
+    sink: S,
+    sample_rate: i32,
+    /// Between chunks within one `call_into_writer` call; calls follow each other with no gap
+    silence: ChunkSilence,
+    duration: f32,
+    timings: Vec<ChunkTiming>,
+}
+
+impl<S: SampleSink> ChunkWriter<S> {
+    pub fn new(sink: S, sample_rate: i32, silence: ChunkSilence) -> Self {
+        ChunkWriter {
+            sink,
+            sample_rate,
+            silence,
+            duration: 0.0,
+            timings: Vec::new(),
+        }
+    }
+
+    /// Append `wav` after `gap` seconds of silence, rounded down to whole samples
+    pub fn push_chunk(&mut self, text: String, wav: &[f32], dur: f32, gap: f32) -> Result<()> {
+        let silence_len = (gap * self.sample_rate as f32) as usize;
+        self.append_chunk(text, wav, dur, silence_len, gap)
+    }
+
+    /// Append `wav` after exactly `silence_len` zero samples
+    pub fn push_chunk_after_samples(&mut self, text: String, wav: &[f32], dur: f32, silence_len: usize) -> Result<()> {
+        let gap = silence_len as f32 / self.sample_rate as f32;
+        self.append_chunk(text, wav, dur, silence_len, gap)
+    }
+
+    /// Append `silence_len` zero samples then `wav`, counting the silence as `gap` seconds
+    fn append_chunk(&mut self, text: String, wav: &[f32], dur: f32, silence_len: usize, gap: f32) -> Result<()> {
+        if silence_len > 0 {
+            self.sink.append(&vec![0.0f32; silence_len])?;
+        }
+        self.sink.append(wav)?;
+        self.duration += gap + dur;
+
+        self.timings.push(ChunkTiming {
+            text,
+            start: self.duration - dur,
+            end: self.duration,
+        });
+        Ok(())
+    }
+
+    /// Return the sink, the total duration and every chunk's timing
+    pub fn finish(self) -> (S, f32, Vec<ChunkTiming>) {
+        (self.sink, self.duration, self.timings)
+    }
+}
+
+/// A word and its approximate position in the output audio, in seconds
+#[derive(Debug, Clone, Serialize)]
+pub struct WordTiming {
+    pub word: String,
+    pub start_s: f32,
+    pub end_s: f32,
+}
+
+/// Estimate per-word timings from chunk timings.
+///
+/// The duration predictor returns a single duration per synthesized chunk, not per token,
+/// so words can't be aligned exactly. Instead each chunk's span is split across its
+/// whitespace-separated words in proportion to their length in characters, since the
+/// model reads text one character per token. Chunk boundaries (and the silence between
+/// chunks) are exact; positions within a chunk are approximate.
+pub fn word_timings(chunks: &[ChunkTiming]) -> Vec<WordTiming> {
+    let mut timings = Vec::new();
+
+    for chunk in chunks {
+        let words: Vec<&str> = chunk.text.split_whitespace().collect();
+        let total_chars: usize = words.iter().map(|w| w.chars().count()).sum();
+        if total_chars == 0 {
+            continue;
+        }
+
+        let span = chunk.end - chunk.start;
+        let mut chars_before = 0;
+        for (i, word) in words.iter().enumerate() {
+            let start_s = chunk.start + span * chars_before as f32 / total_chars as f32;
+            chars_before += word.chars().count();
+            // Pin the last word to the chunk end so rounding can't leave a gap
+            let end_s = if i + 1 == words.len() {
+                chunk.end
+            } else {
+                chunk.start + span * chars_before as f32 / total_chars as f32
+            };
+
+            timings.push(WordTiming { word: word.to_string(), start_s, end_s });
+        }
+    }
+
+    timings
+}
+
+/// A character the model read and its approximate position in the output audio, in seconds
+#[derive(Debug, Clone, Serialize)]
+pub struct PhonemeTiming {
+    pub char: char,
+    pub start_s: f32,
+    pub end_s: f32,
+}
+
+/// Estimate per-character timings, e.g. to drive mouth shapes, from chunk timings and each
+/// chunk's text as `preprocess_text` returns it.
+///
+/// The model is character-based, so a "phoneme" here is one character of the processed
+/// text. As with `word_timings` there is only a duration per chunk, so each chunk's span is
+/// split evenly across its characters. The `<lang>...</lang>` tags wrapping processed text
+/// are left out. Characters tile each chunk exactly, start to end; the silence between
+/// chunks belongs to no character.
+pub fn phoneme_timings(chunks: &[ChunkTiming], processed: &[String], lang: &str) -> Vec<PhonemeTiming> {
+    let (open_tag, close_tag) = (format!("<{}>", lang), format!("</{}>", lang));
+    let mut timings = Vec::new();
+
+    for (chunk, text) in chunks.iter().zip(processed) {
+        let text = text
+            .strip_prefix(&open_tag)
+            .and_then(|text| text.strip_suffix(&close_tag))
+            .unwrap_or(text);
+        let chars: Vec<char> = text.chars().collect();
+
+        let span = chunk.end - chunk.start;
+        for (i, &c) in chars.iter().enumerate() {
+            let start_s = chunk.start + span * i as f32 / chars.len() as f32;
+            // Pin the last character to the chunk end so rounding can't leave a gap
+            let end_s = if i + 1 == chars.len() {
+                chunk.end
+            } else {
+                chunk.start + span * (i + 1) as f32 / chars.len() as f32
+            };
+            timings.push(PhonemeTiming { char: c, start_s, end_s });
+        }
+    }
+
+    timings
+}
+
+/// Format seconds as an SRT timestamp (`HH:MM:SS,mmm`)
+pub fn format_srt_timestamp(seconds: f32) -> String {
+    let total_ms = (seconds.max(0.0) as f64 * 1000.0).round() as u64;
+    let ms = total_ms % 1000;
+    let total_secs = total_ms / 1000;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        total_secs / 3600,
+        (total_secs / 60) % 60,
+        total_secs % 60,
+        ms
+    )
+}
+
+/// Format chunk timings as SRT cues, numbered from 1
+pub fn format_srt(timings: &[ChunkTiming]) -> String {
+    let mut srt = String::new();
+    for (i, timing) in timings.iter().enumerate() {
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            format_srt_timestamp(timing.start),
+            format_srt_timestamp(timing.end),
+            timing.text.trim()
+        ));
+    }
+    srt
+}
+
+// ============================================================================
+// Utility Functions
+// ============================================================================
+
+/// Speed factor that makes `word_count` words predicted to take `predicted_duration`
+/// seconds come out at `target_wpm` words per minute. Returns 1.0 when there's
+/// nothing to scale (no words, no duration or a non-positive target).
+pub fn speed_for_wpm(word_count: usize, predicted_duration: f32, target_wpm: f32) -> f32 {
+    if word_count == 0 || predicted_duration <= 0.0 || target_wpm <= 0.0 {
+        return 1.0;
+    }
+    let natural_wpm = word_count as f32 * 60.0 / predicted_duration;
+    target_wpm / natural_wpm
+}
+
+pub fn sanitize_filename(text: &str, max_len: usize) -> String {
+    // Take first max_len characters (Unicode code points, not bytes)
+    text.chars()
+        .take(max_len)
+        .map(|c| {
+            // is_alphanumeric() works with all Unicode letters and digits
+            if c.is_alphanumeric() {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// ============================================================================
+// ONNX Runtime Integration via ort crate
+// ============================================================================
+//
+// Using ort with load-dynamic feature for Android compatibility.
+// On Android, libonnxruntime.so must be bundled in jniLibs/arm64-v8a/
+//
+
+use ort::session::{Session, SessionOutputs, builder::{GraphOptimizationLevel, SessionBuilder}};
+use ndarray::Array2;
+
+pub struct Style {
+    pub ttl: Array3<f32>,
+    pub dp: Array3<f32>,
+}
+
+/// Milliseconds spent in each model, summed over the chunks synthesized
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct SynthesisMetrics {
+    pub dp_ms: f64,
+    pub text_enc_ms: f64,
+    /// The whole denoising loop, all steps
+    pub denoise_ms: f64,
+    pub vocoder_ms: f64,
+    pub chunks: usize,
+}
+
+impl std::ops::AddAssign for SynthesisMetrics {
+    fn add_assign(&mut self, other: SynthesisMetrics) {
+        self.dp_ms += other.dp_ms;
+        self.text_enc_ms += other.text_enc_ms;
+        self.denoise_ms += other.denoise_ms;
+        self.vocoder_ms += other.vocoder_ms;
+        self.chunks += other.chunks;
+    }
+}
+
+/// A chunk `TextToSpeech::call_best_effort` couldn't synthesize, and why
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChunkError {
+    /// Position among the text's chunks, from 0
+    pub index: usize,
+    pub text: String,
+    pub error: String,
+}
+
+/// Seconds of silence `call_best_effort` puts in place of a chunk that failed
+pub const FAILED_CHUNK_SILENCE: f32 = 0.5;
+
+/// Milliseconds since `start`, or 0 when timing is off
+fn elapsed_ms(start: Option<std::time::Instant>) -> f64 {
+    start.map_or(0.0, |start| start.elapsed().as_secs_f64() * 1000.0)
+}
+
+/// Context for a failed model run, listing the input shapes passed in order, since
+/// ORT's own errors don't say which stage failed or what it was given
+fn run_failure_context(stage: &str, inputs: &[(&str, &[usize])]) -> String {
+    let shapes: Vec<String> = inputs
+        .iter()
+        .map(|(name, shape)| format!("{} {:?}", name, shape))
+        .collect();
+    format!("{} inference failed: {} (check the model's input order and shapes)", stage, shapes.join(", "))
+}
+
+/// How fast to speak: a plain multiplier on the predicted duration, or a target words per minute
+#[derive(Debug, Clone, Copy)]
+pub enum SpeakingRate {
+    Speed(f32),
+    WordsPerMinute(f32),
+}
+
+/// Where the denoising steps fall between pure noise (0.0) and the finished latent (1.0).
+/// Uneven schedules can spend fewer steps where the latent changes little.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepSchedule {
+    /// `total_step` evenly spaced steps
+    #[default]
+    Linear,
+    /// `total_step` steps, short near the noise and growing toward the end
+    Cosine,
+    /// One step per value, each the normalized time the step starts at, ascending in
+    /// [0.0, 1.0); the last step runs to 1.0. `total_step` is ignored.
+    Explicit(Vec<f32>),
+}
+
+impl StepSchedule {
+    pub fn validate(&self) -> Result<()> {
+        if let StepSchedule::Explicit(starts) = self {
+            if starts.is_empty() {
+                bail!("Explicit step schedule has no steps");
+            }
+            if starts.iter().any(|t| !(0.0..1.0).contains(t)) {
+                bail!("Step times must be in [0.0, 1.0), got {:?}", starts);
+            }
+            if starts.windows(2).any(|w| w[1] <= w[0]) {
+                bail!("Step times must be strictly increasing, got {:?}", starts);
+            }
+        }
+        Ok(())
+    }
+
+    /// `(current_step, total_step)` fed to the vector estimator for each step. The estimator
+    /// starts a step at `current_step / total_step` and advances `1 / total_step`, so an uneven
+    /// step is expressed with fractional values. `Linear` gives exactly `(step, total_step)`.
+    pub fn step_inputs(&self, total_step: usize) -> Result<Vec<(f32, f32)>> {
+        let times: Vec<f32> = match self {
+            StepSchedule::Linear => {
+                return Ok((0..total_step).map(|step| (step as f32, total_step as f32)).collect());
+            }
+            StepSchedule::Cosine => (0..=total_step)
+                .map(|i| 1.0 - (std::f32::consts::FRAC_PI_2 * i as f32 / total_step as f32).cos())
+                .collect(),
+            StepSchedule::Explicit(starts) => {
+                self.validate()?;
+                starts.iter().copied().chain(std::iter::once(1.0)).collect()
+            }
+        };
+
+        Ok(times
+            .windows(2)
+            .map(|w| {
+                let step_len = w[1] - w[0];
+                (w[0] / step_len, 1.0 / step_len)
+            })
+            .collect())
+    }
+}
+
+/// Inputs to one vector estimator (denoising) step besides the latent itself
+pub struct DenoiseInputs<'a> {
+    pub text_emb: &'a Array3<f32>,
+    pub style_ttl: &'a Array3<f32>,
+    pub latent_mask: &'a Array3<f32>,
+    pub text_mask: &'a Array3<f32>,
+    pub current_step: &'a ndarray::Array1<f32>,
+    pub total_step: &'a ndarray::Array1<f32>,
+}
+
+/// The four models `TextToSpeech` runs. `OrtModels` holds the ONNX Runtime sessions;
+/// tests substitute a stub so synthesis can run without model files.
+pub trait TtsModels: Send {
+    /// Seconds per text, before any speed factor
+    fn predict_duration(&mut self, text_ids: &Array2<i64>, style_dp: &Array3<f32>, text_mask: &Array3<f32>) -> Result<Vec<f32>>;
+    fn encode_text(&mut self, text_ids: &Array2<i64>, style_ttl: &Array3<f32>, text_mask: &Array3<f32>) -> Result<Array3<f32>>;
+    /// One denoising step, overwriting `xt` with the denoised latent
+    fn denoise(&mut self, xt: &mut Array3<f32>, inputs: &DenoiseInputs<'_>) -> Result<()>;
+    /// Samples for every utterance in `xt`, each padded to the longest
+    fn vocode(&mut self, xt: &Array3<f32>) -> Result<Vec<f32>>;
+    /// Fail with the mismatch when `style` isn't shaped for these models
+    fn check_style(&self, style: &Style) -> Result<()>;
+}
+
+/// The exported Supertonic models, run with ONNX Runtime
+pub struct OrtModels {
+    pub duration_predictor: Session,
+    pub text_encoder: Session,
+    pub vector_estimator: Session,
+    pub vocoder: Session,
+}
+
+impl TtsModels for OrtModels {
+    fn predict_duration(&mut self, text_ids: &Array2<i64>, style_dp: &Array3<f32>, text_mask: &Array3<f32>) -> Result<Vec<f32>> {
+        use ort::value::TensorRef;
+
+        // Input order: text_ids, style_dp, text_mask
+        let outputs: SessionOutputs = self.duration_predictor.run(ort::inputs![
+            TensorRef::from_array_view(text_ids)?,
+            TensorRef::from_array_view(style_dp)?,
+            TensorRef::from_array_view(text_mask)?
+        ])?;
+        let (_, duration) = outputs[0].try_extract_tensor::<f32>()?;
+        Ok(duration.to_vec())
+    }
+
+    fn encode_text(&mut self, text_ids: &Array2<i64>, style_ttl: &Array3<f32>, text_mask: &Array3<f32>) -> Result<Array3<f32>> {
+        use ort::value::TensorRef;
+
+        // Input order: text_ids, style_ttl, text_mask
+        let outputs: SessionOutputs = self.text_encoder.run(ort::inputs![
+            TensorRef::from_array_view(text_ids)?,
+            TensorRef::from_array_view(style_ttl)?,
+            TensorRef::from_array_view(text_mask)?
+        ])?;
+        let (shape, data) = outputs[0].try_extract_tensor::<f32>()?;
+        Ok(Array3::from_shape_vec(
+            (shape[0] as usize, shape[1] as usize, shape[2] as usize),
+            data.to_vec(),
+        )?)
+    }
+
+    fn denoise(&mut self, xt: &mut Array3<f32>, inputs: &DenoiseInputs<'_>) -> Result<()> {
+        use ort::value::TensorRef;
+
+        // Input order: xt, text_emb, style_ttl, latent_mask, text_mask, current_step, total_step
+        let outputs: SessionOutputs = self.vector_estimator.run(ort::inputs![
+            TensorRef::from_array_view(&*xt)?,
+            TensorRef::from_array_view(inputs.text_emb)?,
+            TensorRef::from_array_view(inputs.style_ttl)?,
+            TensorRef::from_array_view(inputs.latent_mask)?,
+            TensorRef::from_array_view(inputs.text_mask)?,
+            TensorRef::from_array_view(inputs.current_step)?,
+            TensorRef::from_array_view(inputs.total_step)?
+        ])?;
+
+        // The denoised latent has the same shape as `xt`, so copy it into the existing buffer
+        let (denoised_shape, denoised_data) = outputs[0].try_extract_tensor::<f32>()?;
+        let expected_shape = [xt.dim().0 as i64, xt.dim().1 as i64, xt.dim().2 as i64];
+        if **denoised_shape != expected_shape {
+            bail!("Vector estimator returned shape {:?}, expected {:?}", denoised_shape, expected_shape);
+        }
+        xt.as_slice_mut()
+            .context("Latent buffer is not contiguous")?
+            .copy_from_slice(denoised_data);
+        Ok(())
+    }
+
+    fn vocode(&mut self, xt: &Array3<f32>) -> Result<Vec<f32>> {
+        use ort::value::TensorRef;
+
+        let outputs: SessionOutputs = self.vocoder.run(ort::inputs![TensorRef::from_array_view(xt)?])?;
+        let (_, wav) = outputs[0].try_extract_tensor::<f32>()?;
+        Ok(wav.to_vec())
+    }
+
+    fn check_style(&self, style: &Style) -> Result<()> {
+        // Each embedding's position among its first model's inputs, as passed above
+        check_style_shape("style_dp", style.dp.dim(), &self.duration_predictor, 1)?;
+        check_style_shape("style_ttl", style.ttl.dim(), &self.text_encoder, 1)
+    }
+}
+
+pub struct TextToSpeech {
+    cfgs: Config,
+    text_processor: UnicodeProcessor,
+    models: Box<dyn TtsModels>,
+    pub sample_rate: i32,
+    /// Model run times accumulated since the last `take_metrics`
+    metrics: SynthesisMetrics,
+    /// Whether model runs are timed into `metrics`; chunks are counted either way
+    collect_metrics: bool,
+    step_schedule: StepSchedule,
+    /// Whether `check_output_length` has run yet; it only needs to once per engine
+    output_length_checked: bool,
+    output_length_warning: Option<String>,
+    /// Seed for the starting noise of every synthesis, making output repeatable; random when unset
+    seed: Option<u64>,
+    /// Longest audio `call` produces and longest any one chunk is synthesized as; no cap when unset
+    max_duration_seconds: Option<f32>,
+    /// Whether the last `call` stopped early at `max_duration_seconds`
+    truncated: bool,
+}
+
+impl TextToSpeech {
+    pub fn new(cfgs: Config, text_processor: UnicodeProcessor, models: Box<dyn TtsModels>) -> Self {
+        let sample_rate = cfgs.ae.sample_rate;
+        TextToSpeech {
+            cfgs,
+            text_processor,
+            models,
+            sample_rate,
+            metrics: SynthesisMetrics::default(),
+            collect_metrics: false,
+            step_schedule: StepSchedule::default(),
+            output_length_checked: false,
+            output_length_warning: None,
+            seed: None,
+            max_duration_seconds: None,
+            truncated: false,
+        }
+    }
+
+    /// Model configuration loaded from `tts.json`
+    pub fn config(&self) -> &Config {
+        &self.cfgs
+    }
+
+    pub fn text_processor(&self) -> &UnicodeProcessor {
+        &self.text_processor
+    }
+
+    /// Replace the pronunciation lexicon applied during text preprocessing
+    pub fn set_lexicon(&mut self, lexicon: HashMap<String, String>) {
+        self.text_processor.lexicon = lexicon;
+    }
+
+    pub fn preprocess_options(&self) -> &PreprocessOptions {
+        &self.text_processor.options
+    }
+
+    pub fn set_preprocess_options(&mut self, options: PreprocessOptions) {
+        self.text_processor.options = options;
+    }
+
+    /// Start every synthesis from the same noise, so the same input gives the same audio
+    /// (e.g. for benchmarking); `None` goes back to fresh noise each time
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    /// Cap how long synthesized audio may get, against runaway input or a bad duration
+    /// prediction: `call` stops adding chunks before passing the cap, and a chunk predicted
+    /// to run longer is shortened to it before its latent is allocated
+    pub fn set_max_duration_seconds(&mut self, max_duration_seconds: Option<f32>) {
+        self.max_duration_seconds = max_duration_seconds;
+    }
+
+    /// Whether the last `call` (or `call_with_timings`, `call_into_writer`) left out chunks
+    /// to stay within `max_duration_seconds`
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn set_step_schedule(&mut self, schedule: StepSchedule) {
+        self.step_schedule = schedule;
+    }
+
+    /// Set when the first synthesis found the vocoder output length at odds with the config
+    pub fn output_length_warning(&self) -> Option<&str> {
+        self.output_length_warning.as_deref()
+    }
+
+    /// Time each model run into the metrics `take_metrics` returns. Off by default, leaving
+    /// every time at 0.
+    pub fn set_collect_metrics(&mut self, collect_metrics: bool) {
+        self.collect_metrics = collect_metrics;
+    }
+
+    /// Model run times since the last call, resetting them. Times stay at 0 unless
+    /// `set_collect_metrics` turned timing on.
+    pub fn take_metrics(&mut self) -> SynthesisMetrics {
+        std::mem::take(&mut self.metrics)
+    }
+
+    fn _infer(
+        &mut self,
+        text_list: &[String],
+        lang_list: &[String],
+        style: &Style,
+        total_step: usize,
+        rate: SpeakingRate,
+    ) -> Result<(Vec<f32>, Vec<f32>)> {
+        // Process text
+        let (text_ids, text_mask, _) = self.text_processor.call(text_list, lang_list)?;
+        let word_counts: Vec<usize> = text_list.iter().map(|t| t.split_whitespace().count()).collect();
+
+        self._infer_from_ids(text_ids, text_mask, &word_counts, style, total_step, rate)
+    }
+
+    /// Run only the duration predictor, returning each text's length in seconds with `rate`
+    /// applied. `word_counts` is only used for a `SpeakingRate::WordsPerMinute`.
+    fn predict_durations(
+        &mut self,
+        text_ids_array: &Array2<i64>,
+        text_mask: &Array3<f32>,
+        word_counts: &[usize],
+        style: &Style,
+        rate: SpeakingRate,
+    ) -> Result<Vec<f32>> {
+        let start = self.collect_metrics.then(std::time::Instant::now);
+        let mut duration = run_with_retry("Duration predictor", || {
+            self.models.predict_duration(text_ids_array, &style.dp, text_mask)
+        }).with_context(|| run_failure_context("Duration predictor", &[
+            ("text_ids", text_ids_array.shape()),
+            ("style_dp", style.dp.shape()),
+            ("text_mask", text_mask.shape()),
+        ]))?;
+        self.metrics.dp_ms += elapsed_ms(start);
+
+        // Apply speed factor to duration. A target WPM needs the predicted duration,
+        // so its factor is worked out per text here, after prediction.
+        for (i, dur) in duration.iter_mut().enumerate() {
+            let speed = match rate {
+                SpeakingRate::Speed(speed) => speed,
+                SpeakingRate::WordsPerMinute(wpm) => {
+                    speed_for_wpm(word_counts.get(i).copied().unwrap_or(0), *dur, wpm)
+                }
+            };
+            *dur /= speed;
+        }
+        Ok(duration)
+    }
+
+    /// Run the models on already-tokenized input. `word_counts` is only used to apply
+    /// a `SpeakingRate::WordsPerMinute`; texts without a count keep their natural pace.
+    fn _infer_from_ids(
+        &mut self,
+        text_ids: Vec<Vec<i64>>,
+        text_mask: Array3<f32>,
+        word_counts: &[usize],
+        style: &Style,
+        total_step: usize,
+        rate: SpeakingRate,
+    ) -> Result<(Vec<f32>, Vec<f32>)> {
+        self.check_style(style)?;
+        let bsz = text_ids.len();
+
+        let seq_len = text_ids[0].len();
+        let text_ids_flat: Vec<i64> = text_ids.into_iter().flatten().collect();
+        let text_ids_array = Array2::from_shape_vec((bsz, seq_len), text_ids_flat)?;
+
+        // Inputs are passed by reference: the models read them in place, so nothing below
+        // is cloned per model run or per denoising step. Each run goes through
+        // `run_with_retry`, which copies out what it needs so a failed run can be repeated.
+
+        let mut duration = self.predict_durations(&text_ids_array, &text_mask, word_counts, style, rate)?;
+        if let Some(cap) = self.max_duration_seconds {
+            for dur in duration.iter_mut().filter(|dur| **dur > cap) {
+                log::warn!("Predicted duration {:.2}s is over the {:.2}s cap; shortening it", dur, cap);
+                *dur = cap;
+            }
+        }
+
+        // Encode text
+        let start = self.collect_metrics.then(std::time::Instant::now);
+        let text_emb = run_with_retry("Text encoder", || {
+            self.models.encode_text(&text_ids_array, &style.ttl, &text_mask)
+        }).with_context(|| run_failure_context("Text encoder", &[
+            ("text_ids", text_ids_array.shape()),
+            ("style_ttl", style.ttl.shape()),
+            ("text_mask", text_mask.shape()),
+        ]))?;
+        self.metrics.text_enc_ms += elapsed_ms(start);
+
+        // Sample noisy latent
+        let base_chunk_size = self.cfgs.ae.base_chunk_size;
+        let chunk_compress = self.cfgs.ttl.chunk_compress_factor;
+        let latent_dim = self.cfgs.ttl.latent_dim;
+        let (mut xt, latent_mask) = match self.seed {
+            Some(seed) => sample_noisy_latent_with_rng(
+                &duration,
+                self.sample_rate,
+                base_chunk_size,
+                chunk_compress,
+                latent_dim,
+                &mut StdRng::seed_from_u64(seed),
+            )?,
+            None => sample_noisy_latent(&duration, self.sample_rate, base_chunk_size, chunk_compress, latent_dim)?,
+        };
+
+        let step_inputs = self.step_schedule.step_inputs(total_step)?;
+        let mut current_step_array = ndarray::Array1::<f32>::zeros(bsz);
+        let mut total_step_array = ndarray::Array1::<f32>::zeros(bsz);
+
+        // Denoising loop
+        let start = self.collect_metrics.then(std::time::Instant::now);
+        for (step, &(current, total)) in step_inputs.iter().enumerate() {
+            current_step_array.fill(current);
+            total_step_array.fill(total);
+
+            let inputs = DenoiseInputs {
+                text_emb: &text_emb,
+                style_ttl: &style.ttl,
+                latent_mask: &latent_mask,
+                text_mask: &text_mask,
+                current_step: &current_step_array,
+                total_step: &total_step_array,
+            };
+            run_with_retry(&format!("Vector estimator (step {})", step), || {
+                self.models.denoise(&mut xt, &inputs)
+            }).with_context(|| run_failure_context("Vector estimator", &[
+                ("xt", xt.shape()),
+                ("text_emb", text_emb.shape()),
+                ("style_ttl", style.ttl.shape()),
+                ("latent_mask", latent_mask.shape()),
+                ("text_mask", text_mask.shape()),
+                ("current_step", current_step_array.shape()),
+                ("total_step", total_step_array.shape()),
+            ]))?;
+        }
+        self.metrics.denoise_ms += elapsed_ms(start);
+
+        // Generate waveform
+        let start = self.collect_metrics.then(std::time::Instant::now);
+        let wav = run_with_retry("Vocoder", || self.models.vocode(&xt)).with_context(|| run_failure_context("Vocoder", &[("xt", xt.shape())]))?;
+        self.metrics.vocoder_ms += elapsed_ms(start);
+        self.metrics.chunks += bsz;
+
+        // Every utterance's output is padded to the longest one
+        if !self.output_length_checked {
+            self.output_length_checked = true;
+            let max_duration = duration.iter().fold(0.0f32, |a, &b| a.max(b));
+            let chunk_size = (self.cfgs.ae.base_chunk_size * self.cfgs.ttl.chunk_compress_factor) as usize;
+            self.output_length_warning = check_output_length(wav.len() / bsz, max_duration, self.sample_rate, chunk_size);
+            if let Some(warning) = &self.output_length_warning {
+                log::warn!("{}", warning);
+            }
+        }
+
+        Ok((wav, duration))
+    }
+
+    pub fn call(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+        silence: ChunkSilence,
+    ) -> Result<(Vec<f32>, f32)> {
+        let (wav, duration, _) = self.call_with_timings(text, lang, style, total_step, SpeakingRate::Speed(speed), silence)?;
+        Ok((wav, duration))
+    }
+
+    /// Like `call`, but a chunk that fails to synthesize becomes `FAILED_CHUNK_SILENCE` seconds
+    /// of silence and is reported instead of failing the whole text, so one bad chunk in a
+    /// long document doesn't lose the rest. Errors only if every chunk fails.
+    pub fn call_best_effort(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        rate: SpeakingRate,
+        silence: ChunkSilence,
+    ) -> Result<(Vec<f32>, f32, Vec<ChunkError>)> {
+        let sample_rate = self.sample_rate;
+        let silence = silence.with_options(self.preprocess_options());
+        let mut synthesize = |chunk: &str| self.synthesize_chunk(chunk, lang, style, total_step, rate);
+        best_effort_chunks(&mut synthesize, text, lang, sample_rate, silence)
+    }
+
+    /// Like `call`, but also returns where each chunk sits in the output audio,
+    /// with the silence inserted between chunks accounted for
+    pub fn call_with_timings(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        rate: SpeakingRate,
+        silence: ChunkSilence,
+    ) -> Result<(Vec<f32>, f32, Vec<ChunkTiming>)> {
+        let mut writer = ChunkWriter::new(Vec::new(), self.sample_rate, silence);
+        self.call_into_writer(&mut writer, text, lang, style, total_step, rate)?;
+        Ok(writer.finish())
+    }
+
+    /// Like `call_with_timings`, but appends each chunk to `writer` as soon as it's
+    /// synthesized instead of returning the audio, so a long file can be assembled from
+    /// several calls without holding it all in memory. Errors if the writer's sample rate
+    /// isn't the model's.
+    pub fn call_into_writer<S: SampleSink>(
+        &mut self,
+        writer: &mut ChunkWriter<S>,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        rate: SpeakingRate,
+    ) -> Result<()> {
+        if writer.sample_rate != self.sample_rate {
+            bail!(
+                "Writer expects {} Hz but the model produces {} Hz",
+                writer.sample_rate,
+                self.sample_rate
+            );
+        }
+        if !has_speakable_text(text) {
+            bail!("Text has nothing to speak");
+        }
+        let (chunks, starts_paragraph): (Vec<String>, Vec<bool>) =
+            chunk_text_tagged(text, &ChunkConfig::for_language(lang)).into_iter().unzip();
+        let silence = writer.silence.with_options(self.preprocess_options());
+        let gaps = match silence.samples {
+            Some(samples) => vec![samples as f32 / self.sample_rate as f32; starts_paragraph.len().saturating_sub(1)],
+            None => silence.gaps(&starts_paragraph),
+        };
+
+        self.truncated = false;
+        let total = chunks.len();
+        let written_before = writer.timings.len();
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            // No silence before the first chunk actually written, even if earlier ones were skipped
+            let first = writer.timings.len() == written_before;
+            let gap = if first { 0.0 } else { gaps[i - 1] };
+            // The first chunk written always fits, since synthesis shortens it to the cap
+            let cap = self.max_duration_seconds.filter(|_| !writer.timings.is_empty());
+            if cap.is_some_and(|cap| writer.duration + gap >= cap) {
+                self.truncated = true;
+            } else {
+                let (wav, dur) = match self.synthesize_chunk(&chunk, lang, style, total_step, rate) {
+                    Ok(synthesized) => synthesized,
+                    Err(e) if e.is::<EmptyAfterNormalization>() => {
+                        log::warn!("Skipping chunk {} of {}: nothing to speak in {:?}", i + 1, total, chunk);
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                if cap.is_some_and(|cap| writer.duration + gap + dur > cap) {
+                    self.truncated = true;
+                } else {
+                    match silence.samples {
+                        Some(samples) => {
+                            let silence_len = if first { 0 } else { samples };
+                            writer.push_chunk_after_samples(chunk, &wav, dur, silence_len)?;
+                        }
+                        None => writer.push_chunk(chunk, &wav, dur, gap)?,
+                    }
+                }
+            }
+            if self.truncated {
+                log::warn!("Stopped after {} of {} chunks to stay within the duration cap", i, total);
+                break;
+            }
+        }
+        if writer.timings.len() == written_before && !self.truncated {
+            return Err(EmptyAfterNormalization.into());
+        }
+        Ok(())
+    }
+
+    /// Like `call`, but synthesizes `text` as one unit without running `chunk_text`, so
+    /// pre-chunked text slightly over the chunk limit isn't split into two with a pause.
+    /// Text over `MAX_SINGLE_CALL_CHARS` is rejected.
+    pub fn call_single(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+    ) -> Result<(Vec<f32>, f32)> {
+        let text = text.trim();
+        if !has_speakable_text(text) {
+            bail!("Text has nothing to speak");
+        }
+        let len = text.chars().count();
+        if len > MAX_SINGLE_CALL_CHARS {
+            bail!("Text is {} characters; at most {} can be synthesized as one unit", len, MAX_SINGLE_CALL_CHARS);
+        }
+        self.synthesize_chunk(text, lang, style, total_step, SpeakingRate::Speed(speed))
+    }
+
+    /// Seconds of audio `call_with_timings` would produce for `text`, silence between chunks
+    /// included, from the duration predictor alone. Far cheaper than synthesizing it, and
+    /// exact up to rounding since synthesis trims each chunk to this same prediction.
+    pub fn estimate_duration(
+        &mut self,
+        text: &str,
+        lang: &str,
+        style: &Style,
+        rate: SpeakingRate,
+        silence: ChunkSilence,
+    ) -> Result<f32> {
+        if !has_speakable_text(text) {
+            bail!("Text has nothing to speak");
+        }
+        self.check_style(style)?;
+        let (chunks, starts_paragraph): (Vec<String>, Vec<bool>) =
+            chunk_text_tagged(text, &ChunkConfig::for_language(lang)).into_iter().unzip();
+
+        // Chunk by chunk, as synthesis runs them; the style embedding has a batch size of 1
+        let segments: Vec<ScriptSegment> =
+            chunks.iter().flat_map(|chunk| self.language_segments(chunk, lang)).collect();
+        let mut total = 0.0;
+        for segment in segments {
+            // Synthesis skips these too
+            let (mut text_ids, text_mask, _) =
+                match self.text_processor.call(std::slice::from_ref(&segment.text), &[segment.lang]) {
+                    Ok(processed) => processed,
+                    Err(e) if e.is::<EmptyAfterNormalization>() => continue,
+                    Err(e) => return Err(e),
+                };
+            let ids = text_ids.remove(0);
+            let text_ids_array = Array2::from_shape_vec((1, ids.len()), ids)?;
+            let word_count = segment.text.split_whitespace().count();
+            total += self.predict_durations(&text_ids_array, &text_mask, &[word_count], style, rate)?[0];
+        }
+
+        let gaps = silence.with_options(self.preprocess_options()).gaps(&starts_paragraph);
+        Ok(total + gaps.iter().sum::<f32>())
+    }
+
+    /// Synthesize one chunk of text, trimmed to its predicted duration. With
+    /// `PreprocessOptions::mixed_language`, each script run is synthesized in its own
+    /// language and the runs are joined back to back.
+    pub fn synthesize_chunk(
+        &mut self,
+        chunk: &str,
+        lang: &str,
+        style: &Style,
+        total_step: usize,
+        rate: SpeakingRate,
+    ) -> Result<(Vec<f32>, f32)> {
+        let mut wav = Vec::new();
+        let mut dur = 0.0;
+        let mut spoken = false;
+        for segment in self.language_segments(chunk, lang) {
+            let (mut segment_wav, duration) =
+                match self._infer(&[segment.text], &[segment.lang], style, total_step, rate) {
+                    Ok(synthesized) => synthesized,
+                    // A run of symbols between two scripts; the rest of the chunk still has speech
+                    Err(e) if e.is::<EmptyAfterNormalization>() => continue,
+                    Err(e) => return Err(e),
+                };
+            dur += trim_to_duration(&mut segment_wav, duration[0], self.sample_rate);
+            wav.extend_from_slice(&segment_wav);
+            spoken = true;
+        }
+        if !spoken {
+            return Err(EmptyAfterNormalization.into());
+        }
+        Ok((wav, dur))
+    }
+
+    /// The pieces of a chunk synthesized separately: its script runs in mixed-language mode,
+    /// otherwise the whole chunk in `lang`
+    fn language_segments(&self, chunk: &str, lang: &str) -> Vec<ScriptSegment> {
+        if self.preprocess_options().mixed_language {
+            let segments = segment_by_script(chunk, lang);
+            if !segments.is_empty() {
+                return segments;
+            }
+        }
+        vec![ScriptSegment {
+            text: chunk.to_string(),
+            lang: lang.to_string(),
+        }]
+    }
+
+    /// Token IDs the models see for `text`, after normalization and language tagging
+    pub fn tokenize(&self, text: &str, lang: &str) -> Result<Vec<i64>> {
+        let (mut text_ids, _, _) = self.text_processor.call(&[text.to_string()], &[lang.to_string()])?;
+        Ok(text_ids.remove(0))
+    }
+
+    /// Reject empty sequences and IDs the indexer can't produce (anything but -1 and 0..=max)
+    pub fn check_token_ids(&self, token_ids: &[i64]) -> Result<()> {
+        if token_ids.is_empty() {
+            bail!("Token sequence is empty");
+        }
+        let max_id = self.text_processor.indexer.iter().copied().max().unwrap_or(-1);
+        if let Some(bad) = token_ids.iter().find(|&&id| id < -1 || id > max_id) {
+            bail!("Token ID {} out of range; expected -1 or 0..={}", bad, max_id);
+        }
+        Ok(())
+    }
+
+    /// Fail with the mismatch when `style` isn't shaped for these models, e.g. a voice
+    /// style made for another model version
+    pub fn check_style(&self, style: &Style) -> Result<()> {
+        self.models.check_style(style)
+    }
+
+    /// Synthesize a raw token sequence (as returned by `tokenize`), skipping text
+    /// normalization entirely. IDs must be -1 (unknown) or a token the indexer produces.
+    pub fn synthesize_ids(
+        &mut self,
+        token_ids: &[i64],
+        style: &Style,
+        total_step: usize,
+        speed: f32,
+    ) -> Result<(Vec<f32>, f32)> {
+        self.check_token_ids(token_ids)?;
+
+        let text_mask = get_text_mask(&[token_ids.len()]);
+        let (mut wav, duration) = self._infer_from_ids(
+            vec![token_ids.to_vec()],
+            text_mask,
+            &[],
+            style,
+            total_step,
+            SpeakingRate::Speed(speed),
+        )?;
+
+        let dur = trim_to_duration(&mut wav, duration[0], self.sample_rate);
+        Ok((wav, dur))
+    }
+
+    /// Run a single-step inference on a short phrase to confirm every model loads and runs
+    pub fn warmup(&mut self, style: &Style) -> Result<()> {
+        self._infer(&["Hello.".to_string()], &["en".to_string()], style, 1, SpeakingRate::Speed(1.0))?;
+        Ok(())
+    }
+}
+
+/// Compare an embedding's rows and columns with what `session` declares for input `index`.
+/// Dimensions the model leaves dynamic (negative) match anything.
+fn check_style_shape(
+    name: &str,
+    (_, rows, cols): (usize, usize, usize),
+    session: &Session,
+    index: usize,
+) -> Result<()> {
+    let Some(shape) = session.inputs().get(index).and_then(|input| input.dtype().tensor_shape()) else {
+        return Ok(());
+    };
+    if shape.len() != 3 {
+        return Ok(());
+    }
+
+    let matches = |actual: usize, expected: i64| expected < 0 || actual as i64 == expected;
+    if !matches(rows, shape[1]) || !matches(cols, shape[2]) {
+        let dim = |d: i64| if d < 0 { "?".to_string() } else { d.to_string() };
+        bail!(
+            "Voice style {} is {}x{} but the models expect {}x{}; it was probably made for a different model version",
+            name,
+            rows,
+            cols,
+            dim(shape[1]),
+            dim(shape[2])
+        );
+    }
+    Ok(())
+}
+
+/// Synthesize `text` with `engine` into a complete WAV file (16-bit mono), returning its bytes
+/// and duration in seconds. Needs nothing from the Tauri app, so tests and command-line tools
+/// can use the library directly. `call` already trims each chunk to its predicted length.
+pub fn synthesize_once(
+    engine: &mut TextToSpeech,
+    text: &str,
+    lang: &str,
+    style: &Style,
+    total_step: usize,
+    speed: f32,
+    silence: ChunkSilence,
+) -> Result<(Vec<u8>, f32)> {
+    let (wav, duration) = engine.call(text, lang, style, total_step, speed, silence)?;
+    let bytes = encode_wav_to_bytes(&wav, engine.sample_rate, DEFAULT_WAV_BIT_DEPTH, DEFAULT_WAV_CHANNELS)?;
+    Ok((bytes, duration))
+}
+
+// ============================================================================
+// Component Loading Functions
+// ============================================================================
+
+/// Load voice style from JSON bytes (for Android resource loading)
+pub fn load_voice_style_from_bytes(bytes: &[u8]) -> Result<Style> {
+    let data: VoiceStyleData = serde_json::from_slice(bytes)?;
+
+    let ttl_style = component_array("style_ttl", data.style_ttl)?;
+    let dp_style = component_array("style_dp", data.style_dp)?;
+
+    Ok(Style {
+        ttl: ttl_style,
+        dp: dp_style,
+    })
+}
+
+/// Build a voice style from flattened, row-major embeddings, e.g. ones computed outside the app.
+/// Each length must match the product of its dims.
+pub fn load_voice_style_from_arrays(
+    ttl: Vec<f32>,
+    ttl_dims: [usize; 3],
+    dp: Vec<f32>,
+    dp_dims: [usize; 3],
+) -> Result<Style> {
+    let ttl_style = style_array("style_ttl", ttl, ttl_dims)?;
+    let dp_style = style_array("style_dp", dp, dp_dims)?;
+
+    Ok(Style {
+        ttl: ttl_style,
+        dp: dp_style,
+    })
+}
+
+/// Flatten a style file's nested `data` and shape it by its `dims`, which must have 3 entries
+fn component_array(name: &str, component: StyleComponent) -> Result<Array3<f32>> {
+    let Ok(dims) = <[usize; 3]>::try_from(component.dims.as_slice()) else {
+        bail!("{} dims {:?} should have 3 entries (batch, rows, columns)", name, component.dims);
+    };
+    let flat = component.data.into_iter().flatten().flatten().collect();
+    style_array(name, flat, dims)
+}
+
+fn style_array(name: &str, data: Vec<f32>, dims: [usize; 3]) -> Result<Array3<f32>> {
+    let expected: usize = dims.iter().product();
+    if data.len() != expected {
+        bail!(
+            "{} has {} values but dims {:?} need {}",
+            name,
+            data.len(),
+            dims,
+            expected
+        );
+    }
+    Ok(Array3::from_shape_vec((dims[0], dims[1], dims[2]), data)?)
+}
+
+/// Serialize a single voice style to the JSON shape of the voice style files,
+/// readable by `load_voice_style_from_bytes`
+pub fn voice_style_to_bytes(style: &Style) -> Result<Vec<u8>> {
+    let data = VoiceStyleData {
+        style_ttl: style_component(&style.ttl)?,
+        style_dp: style_component(&style.dp)?,
+    };
+    Ok(serde_json::to_vec(&data)?)
+}
+
+fn style_component(array: &Array3<f32>) -> Result<StyleComponent> {
+    let (bsz, dim1, dim2) = array.dim();
+    if bsz != 1 {
+        bail!("Can only save a single voice style, got batch size {}", bsz);
+    }
+
+    let data = array
+        .outer_iter()
+        .map(|batch| batch.outer_iter().map(|row| row.to_vec()).collect())
+        .collect();
+
+    Ok(StyleComponent {
+        data,
+        dims: vec![bsz, dim1, dim2],
+        dtype: "float32".to_string(),
+    })
+}
+
+/// How much ORT optimizes each model's graph while loading it. Optimizing takes several
+/// seconds on phones at `Level3`; lower levels load faster but run inference slower.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OptimizationLevel {
+    Disable,
+    Level1,
+    Level2,
+    #[default]
+    Level3,
+}
+
+impl From<OptimizationLevel> for GraphOptimizationLevel {
+    fn from(level: OptimizationLevel) -> Self {
+        match level {
+            OptimizationLevel::Disable => GraphOptimizationLevel::Disable,
+            OptimizationLevel::Level1 => GraphOptimizationLevel::Level1,
+            OptimizationLevel::Level2 => GraphOptimizationLevel::Level2,
+            OptimizationLevel::Level3 => GraphOptimizationLevel::Level3,
+        }
+    }
+}
+
+/// How the loaders build each model's session
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub optimization_level: OptimizationLevel,
+    /// Where to keep each model's optimized graph (`<model>.ort`) between launches, so
+    /// only the first load pays for optimization. A graph is rebuilt when its source model
+    /// or the optimization level changes.
+    pub optimized_model_dir: Option<PathBuf>,
+}
+
+/// Set once ORT's library is loaded, by `set_onnxruntime_path` or the first session
+/// builder; from then on a different library can't be swapped in
+static ORT_INITIALIZED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Load ONNX Runtime from the library at `path` (e.g. a GPU-enabled build installed
+/// elsewhere) instead of the default search. Must happen before any model loads.
+pub fn set_onnxruntime_path(path: &Path) -> Result<()> {
+    use std::sync::atomic::Ordering;
+
+    if ORT_INITIALIZED.load(Ordering::SeqCst) {
+        bail!("ONNX Runtime is already initialized; its library path must be set before any model loads");
+    }
+    if !path.is_file() {
+        bail!("ONNX Runtime library not found at {}", path.display());
+    }
+    let committed = ort::init_from(path)
+        .with_context(|| format!("Failed to load ONNX Runtime from {}", path.display()))?
+        .commit();
+    ORT_INITIALIZED.store(true, Ordering::SeqCst);
+    if !committed {
+        log::warn!("ONNX Runtime environment was already configured; keeping its settings");
+    }
+    Ok(())
+}
+
+/// Builder every model session starts from, so all four share one optimization level
+fn session_builder(level: OptimizationLevel) -> Result<SessionBuilder> {
+    ORT_INITIALIZED.store(true, std::sync::atomic::Ordering::SeqCst);
+    Ok(Session::builder()?.with_optimization_level(level.into())?)
+}
+
+/// A model's optimized graph in the cache, with a tag file naming the source model's
+/// SHA-256 and the optimization level it was built at
+struct OptimizedModel {
+    graph: PathBuf,
+    tag_file: PathBuf,
+    tag: String,
+}
+
+impl OptimizedModel {
+    fn new(dir: &Path, model_name: &str, source_digest: &str, level: OptimizationLevel) -> Self {
+        OptimizedModel {
+            graph: dir.join(format!("{}.ort", model_name)),
+            tag_file: dir.join(format!("{}.ort.tag", model_name)),
+            tag: format!("{} {:?}", source_digest, level),
+        }
+    }
+
+    /// Built from this exact source model at this level
+    fn is_current(&self) -> bool {
+        self.graph.exists() && std::fs::read_to_string(&self.tag_file).is_ok_and(|tag| tag == self.tag)
+    }
+}
+
+/// Build a session with `commit`, going through `options`' optimized graph cache when it has
+/// one: a current cached graph is loaded as is, otherwise ORT writes a fresh one while
+/// optimizing the source. Cache problems only cost the speedup, never the load.
+fn commit_session(
+    options: &LoadOptions,
+    model_name: &str,
+    source_digest: impl FnOnce() -> Result<String>,
+    commit: impl FnOnce(SessionBuilder) -> ort::Result<Session>,
+) -> Result<Session> {
+    let level = options.optimization_level;
+    let Some(dir) = &options.optimized_model_dir else {
+        return Ok(commit(session_builder(level)?)?);
+    };
+    let cached = OptimizedModel::new(dir, model_name, &source_digest()?, level);
+
+    if cached.is_current() {
+        // Already optimized, so don't spend time optimizing again
+        match session_builder(OptimizationLevel::Disable)?.commit_from_file(&cached.graph) {
+            Ok(session) => {
+                log::info!("Loaded optimized {} from {}", model_name, cached.graph.display());
+                return Ok(session);
+            }
+            Err(e) => log::warn!("Rebuilding unreadable optimized graph {}: {}", cached.graph.display(), e),
+        }
+    }
+
+    let _ = std::fs::remove_file(&cached.tag_file);
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        log::info!("Not caching optimized graphs in {}: {}", dir.display(), e);
+        return Ok(commit(session_builder(level)?)?);
+    }
+    let session = commit(session_builder(level)?.with_optimized_model_path(&cached.graph)?)?;
+    // Tagged only once ORT has written the graph, so a failed load never looks current
+    if let Err(e) = std::fs::write(&cached.tag_file, &cached.tag) {
+        log::warn!("Failed to record optimized graph for {}: {}", model_name, e);
+    }
+    Ok(session)
+}
+
+/// Load one model file through the optimized graph cache
+#[cfg(not(target_os = "android"))]
+fn commit_session_from_file(path: &str, model_name: &str, options: &LoadOptions) -> Result<Session> {
+    commit_session(
+        options,
+        model_name,
+        || crate::model_download::sha256_file(Path::new(path)),
+        |builder| builder.commit_from_file(path),
+    )
+}
+
+/// Load TTS components using ort (ONNX Runtime) from file paths
+/// Use this for desktop platforms. ORT reads each model file itself, so unlike
+/// the bytes loader no copy of the model data is held on the Rust side.
+#[cfg(not(target_os = "android"))]
+pub fn load_text_to_speech(onnx_dir: &str, _use_gpu: bool, options: &LoadOptions) -> Result<TextToSpeech> {
+    println!("Loading TTS models with ONNX Runtime (CPU inference)...\n");
+
+    let cfgs = load_cfgs(onnx_dir)?;
+
+    let dp_path = format!("{}/duration_predictor.onnx", onnx_dir);
+    let text_enc_path = format!("{}/text_encoder.onnx", onnx_dir);
+    let vector_est_path = format!("{}/vector_estimator.onnx", onnx_dir);
+    let vocoder_path = format!("{}/vocoder.onnx", onnx_dir);
+
+    println!("Loading duration predictor...");
+    let dp_model = commit_session_from_file(&dp_path, "duration_predictor", options)
+        .with_context(|| format!("Failed to load duration predictor from {}", dp_path))?;
+
+    println!("Loading text encoder...");
+    let text_enc_model = commit_session_from_file(&text_enc_path, "text_encoder", options)
+        .with_context(|| format!("Failed to load text encoder from {}", text_enc_path))?;
+
+    println!("Loading vector estimator...");
+    let vector_est_model = commit_session_from_file(&vector_est_path, "vector_estimator", options)
+        .with_context(|| format!("Failed to load vector estimator from {}", vector_est_path))?;
+
+    println!("Loading vocoder...");
+    let vocoder_model = commit_session_from_file(&vocoder_path, "vocoder", options)
+        .with_context(|| format!("Failed to load vocoder from {}", vocoder_path))?;
+
+    let unicode_indexer_path = format!("{}/unicode_indexer.json", onnx_dir);
+    let text_processor = UnicodeProcessor::new(&unicode_indexer_path)?;
+
+    println!("All models loaded successfully!\n");
+
+    Ok(TextToSpeech::new(
+        cfgs,
+        text_processor,
+        Box::new(OrtModels {
+            duration_predictor: dp_model,
+            text_encoder: text_enc_model,
+            vector_estimator: vector_est_model,
+            vocoder: vocoder_model,
+        }),
+    ))
+}
+
+/// Build a session from model bytes. ORT keeps its own copy of the graph, so the bytes
+/// are taken by value and freed as soon as the session exists.
+#[cfg(target_os = "android")]
+fn commit_session_from_bytes(bytes: Vec<u8>, name: &str, options: &LoadOptions) -> Result<Session> {
+    println!("Loading {} from bytes ({:.1} MB)...", name, bytes.len() as f64 / 1_048_576.0);
+    commit_session(
+        options,
+        &name.replace(' ', "_"),
+        || Ok(crate::model_download::sha256_bytes(&bytes)),
+        |builder| builder.commit_from_memory(&bytes),
+    )
+    .with_context(|| format!("Failed to load {}", name))
+}
+
+/// Reads one model file by its path under the models root, e.g. `onnx/vocoder.onnx`
+/// (for Android resource loading)
+#[cfg(target_os = "android")]
+pub type ModelFileReader<'a> = dyn Fn(&str) -> std::result::Result<Vec<u8>, String> + 'a;
+
+/// Load TTS components from bytes (for Android/mobile platforms)
+/// Note: On Android, libonnxruntime.so must be available in jniLibs
+///
+/// Each model file is read just before its session is built and freed right after, so
+/// peak memory while loading is the sessions built so far plus one model file, rather
+/// than every model file at once on top of the sessions.
+#[cfg(target_os = "android")]
+pub fn load_text_to_speech_from_reader(read: &ModelFileReader<'_>, options: &LoadOptions) -> Result<TextToSpeech> {
+    println!("Loading TTS models from bytes (mobile mode)...\n");
+    let read = |rel_path: &str| read(rel_path).map_err(anyhow::Error::msg);
+
+    // Parse config from bytes
+    let cfgs: Config = serde_json::from_slice(&read("onnx/tts.json")?)?;
+
+    // Parse unicode indexer from bytes
+    let indexer: Vec<i64> = serde_json::from_slice(&read("onnx/unicode_indexer.json")?)?;
+    let text_processor = UnicodeProcessor {
+        indexer,
+        lexicon: HashMap::new(),
+        options: PreprocessOptions::default(),
+    };
+
+    let dp_model = commit_session_from_bytes(read("onnx/duration_predictor.onnx")?, "duration predictor", options)?;
+    let text_enc_model = commit_session_from_bytes(read("onnx/text_encoder.onnx")?, "text encoder", options)?;
+    let vector_est_model = commit_session_from_bytes(read("onnx/vector_estimator.onnx")?, "vector estimator", options)?;
+    let vocoder_model = commit_session_from_bytes(read("onnx/vocoder.onnx")?, "vocoder", options)?;
+
+    println!("All models loaded successfully!\n");
+
+    Ok(TextToSpeech::new(
+        cfgs,
+        text_processor,
+        Box::new(OrtModels {
+            duration_predictor: dp_model,
+            text_encoder: text_enc_model,
+            vector_estimator: vector_est_model,
+            vocoder: vocoder_model,
+        }),
+    ))
+}
+
+/// Stand-in models so tests can synthesize without model files
+#[cfg(test)]
+pub(crate) mod stub {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Output sample rate of `engine()`, kept low so tests stay fast
+    pub const SAMPLE_RATE: i32 = 1_000;
+    /// Seconds the stub duration predictor gives each character of normalized text
+    pub const SECONDS_PER_CHAR: f32 = 0.05;
+
+    /// Predicts `SECONDS_PER_CHAR` per character and vocodes every frame of an utterance to
+    /// one level derived from its token IDs, so the audio's length follows the text and its
+    /// level tells texts apart, whatever the noise
+    #[derive(Default)]
+    pub struct StubModels {
+        /// Denoising steps run so far
+        pub steps: Arc<AtomicUsize>,
+    }
+
+    impl TtsModels for StubModels {
+        fn predict_duration(&mut self, _text_ids: &Array2<i64>, _style_dp: &Array3<f32>, text_mask: &Array3<f32>) -> Result<Vec<f32>> {
+            Ok(text_mask.outer_iter().map(|mask| mask.sum() * SECONDS_PER_CHAR).collect())
+        }
+
+        fn encode_text(&mut self, text_ids: &Array2<i64>, _style_ttl: &Array3<f32>, _text_mask: &Array3<f32>) -> Result<Array3<f32>> {
+            let levels = text_ids.outer_iter().map(|ids| level_for(ids.sum())).collect();
+            Ok(Array3::from_shape_vec((text_ids.nrows(), 1, 1), levels)?)
+        }
+
+        fn denoise(&mut self, xt: &mut Array3<f32>, inputs: &DenoiseInputs<'_>) -> Result<()> {
+            self.steps.fetch_add(1, Ordering::Relaxed);
+            for ((b, d, t), value) in xt.indexed_iter_mut() {
+                *value = if d == 0 { inputs.text_emb[[b, 0, 0]] * inputs.latent_mask[[b, 0, t]] } else { 0.0 };
+            }
+            Ok(())
+        }
+
+        fn vocode(&mut self, xt: &Array3<f32>) -> Result<Vec<f32>> {
+            let chunk_size = (config().ae.base_chunk_size * config().ttl.chunk_compress_factor) as usize;
+            Ok(xt
+                .outer_iter()
+                .flat_map(|latent| latent.row(0).iter().flat_map(|&level| vec![level; chunk_size]).collect::<Vec<_>>())
+                .collect())
+        }
+
+        fn check_style(&self, _style: &Style) -> Result<()> {
+            Ok(())
         }
     }
-    
-    // Add the remaining text
-    if last_end < text.len() {
-        sentences.push(text[last_end..].to_string());
+
+    /// Level `StubModels` vocodes text with these summed token IDs to
+    pub fn level_for(token_sum: i64) -> f32 {
+        0.1 + (token_sum.rem_euclid(50)) as f32 / 100.0
     }
-    
-    if sentences.is_empty() {
-        vec![text.to_string()]
-    } else {
-        sentences
+
+    pub fn config() -> Config {
+        Config {
+            ae: AEConfig { sample_rate: SAMPLE_RATE, base_chunk_size: 10 },
+            ttl: TTLConfig { chunk_compress_factor: 1, latent_dim: 1 },
+        }
     }
-}
 
-// ============================================================================
-// Utility Functions
-// ============================================================================
+    /// An engine on `StubModels` whose indexer maps every code point up to Hangul to itself
+    pub fn engine() -> TextToSpeech {
+        counting_engine().0
+    }
 
-pub fn timer<F, T>(name: &str, f: F) -> Result<T>
-where
-    F: FnOnce() -> Result<T>,
-{
-    let start = std::time::Instant::now();
-    println!("{}...", name);
-    let result = f()?;
-    let elapsed = start.elapsed().as_secs_f64();
-    println!("  -> {} completed in {:.2} sec", name, elapsed);
-    Ok(result)
-}
+    /// `engine()`, plus a count of the denoising steps it runs
+    pub fn counting_engine() -> (TextToSpeech, Arc<AtomicUsize>) {
+        let models = StubModels::default();
+        let steps = models.steps.clone();
+        (engine_on(Box::new(models)), steps)
+    }
 
-pub fn sanitize_filename(text: &str, max_len: usize) -> String {
-    // Take first max_len characters (Unicode code points, not bytes)
-    text.chars()
-        .take(max_len)
-        .map(|c| {
-            // is_alphanumeric() works with all Unicode letters and digits
-            if c.is_alphanumeric() {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect()
+    /// An engine like `engine()` running on `models`, e.g. ones wrapping `StubModels`
+    pub fn engine_on(models: Box<dyn TtsModels>) -> TextToSpeech {
+        let text_processor = UnicodeProcessor {
+            indexer: (0..0xD7A4).collect(),
+            lexicon: HashMap::new(),
+            options: PreprocessOptions::default(),
+        };
+        TextToSpeech::new(config(), text_processor, models)
+    }
+
+    pub fn style() -> Style {
+        Style { ttl: Array3::zeros((1, 1, 1)), dp: Array3::zeros((1, 1, 1)) }
+    }
 }
 
-// ============================================================================
-// ONNX Runtime Integration via ort crate
-// ============================================================================
-//
-// Using ort with load-dynamic feature for Android compatibility.
-// On Android, libonnxruntime.so must be bundled in jniLibs/arm64-v8a/
-//
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
-use ort::session::{Session, SessionOutputs, builder::GraphOptimizationLevel};
-use ndarray::Array2;
+    fn preprocess(text: &str, lang: &str) -> String {
+        preprocess_text(text, lang, &HashMap::new(), &PreprocessOptions::default()).unwrap()
+    }
 
-pub struct Style {
-    pub ttl: Array3<f32>,
-    pub dp: Array3<f32>,
-}
+    #[test]
+    fn english_gets_a_period_but_korean_ending_in_hangul_does_not() {
+        assert_eq!(preprocess("Hello there", "en"), "<en>Hello there.</en>");
 
-pub struct TextToSpeech {
-    cfgs: Config,
-    text_processor: UnicodeProcessor,
-    dp_model: Session,
-    text_enc_model: Session,
-    vector_est_model: Session,
-    vocoder_model: Session,
-    pub sample_rate: i32,
-}
+        let korean: String = "안녕하세요".nfkd().collect();
+        assert_eq!(preprocess("안녕하세요", "ko"), format!("<ko>{}</ko>", korean));
+        // Anything else still gets one
+        assert_eq!(preprocess("안녕 ABC", "ko"), format!("<ko>{} ABC.</ko>", "안녕".nfkd().collect::<String>()));
+    }
 
-impl TextToSpeech {
-    pub fn new(
-        cfgs: Config,
-        text_processor: UnicodeProcessor,
-        dp_model: Session,
-        text_enc_model: Session,
-        vector_est_model: Session,
-        vocoder_model: Session,
-    ) -> Self {
-        let sample_rate = cfgs.ae.sample_rate;
-        TextToSpeech {
-            cfgs,
-            text_processor,
-            dp_model,
-            text_enc_model,
-            vector_est_model,
-            vocoder_model,
-            sample_rate,
-        }
+    #[test]
+    fn output_length_matching_the_config_passes() {
+        // 1.5s at 44.1 kHz, padded up to a whole chunk
+        assert!(check_output_length(66_150 + 512, 1.5, 44_100, 3072).is_none());
     }
 
-    fn _infer(
-        &mut self,
-        text_list: &[String],
-        lang_list: &[String],
-        style: &Style,
-        total_step: usize,
-        speed: f32,
-    ) -> Result<(Vec<f32>, Vec<f32>)> {
-        use ort::value::Tensor;
+    #[test]
+    fn output_length_at_another_sample_rate_is_reported() {
+        // The vocoder actually runs at 24 kHz but tts.json claims 44.1 kHz
+        let msg = check_output_length(36_000, 1.5, 44_100, 3072).expect("mismatch not detected");
+        assert!(msg.contains("44100 Hz"), "{}", msg);
+        assert!(msg.contains("24000 Hz"), "{}", msg);
+    }
 
-        let bsz = text_list.len();
+    fn timing(text: &str, start: f32, end: f32) -> ChunkTiming {
+        ChunkTiming { text: text.to_string(), start, end }
+    }
 
-        // Process text
-        let (text_ids, text_mask) = self.text_processor.call(text_list, lang_list)?;
+    #[test]
+    fn duration_cap_cuts_at_the_last_chunk_that_fits() {
+        // Three 1s chunks with 0.5s gaps, at 10 Hz
+        let mut wav = vec![0.5; 40];
+        let mut timings = vec![timing("a", 0.0, 1.0), timing("b", 1.5, 2.5), timing("c", 3.0, 4.0)];
 
-        let seq_len = text_ids[0].len();
-        let text_ids_flat: Vec<i64> = text_ids.iter().flat_map(|row| row.iter().cloned()).collect();
-        let text_ids_array = Array2::from_shape_vec((bsz, seq_len), text_ids_flat)?;
+        assert!(cap_at_chunk_boundary(&mut wav, &mut timings, 10, 3.5));
+        assert_eq!(timings.len(), 2);
+        assert_eq!(wav.len(), 25);
 
-        // Predict duration
-        // Input order: text_ids, style_dp, text_mask
-        let dp_outputs: SessionOutputs = self.dp_model.run(ort::inputs![
-            Tensor::from_array(text_ids_array.clone())?,
-            Tensor::from_array(style.dp.clone())?,
-            Tensor::from_array(text_mask.clone())?
-        ])?;
+        // Already within the cap: nothing to do
+        assert!(!cap_at_chunk_boundary(&mut wav, &mut timings, 10, 3.5));
+        assert_eq!(wav.len(), 25);
+    }
 
-        let duration_output = &dp_outputs[0];
-        let (_, duration_data) = duration_output.try_extract_tensor::<f32>()?;
-        let mut duration: Vec<f32> = duration_data.iter().cloned().collect();
+    #[test]
+    fn synthesize_once_on_stub_models_produces_a_playable_wav() {
+        let mut engine = stub::engine();
+        let (bytes, duration) = synthesize_once(
+            &mut engine,
+            "Hello there. This is a test.",
+            "en",
+            &stub::style(),
+            2,
+            1.0,
+            ChunkSilence::uniform(0.3),
+        )
+        .unwrap();
+
+        let reader = hound::WavReader::new(std::io::Cursor::new(&bytes)).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.sample_rate, stub::SAMPLE_RATE as u32);
+        assert_eq!((spec.channels, spec.bits_per_sample), (DEFAULT_WAV_CHANNELS, DEFAULT_WAV_BIT_DEPTH));
+        assert!((reader.duration() as f32 / spec.sample_rate as f32 - duration).abs() < 0.01);
+
+        // Stub speech is never silent, so only the silence between chunks reads as zero
+        let samples: Vec<i16> = reader.into_samples().map(Result::unwrap).collect();
+        assert!(samples.iter().filter(|&&s| s != 0).count() as f32 / stub::SAMPLE_RATE as f32 > 0.5);
+    }
 
-        // Apply speed factor to duration
-        for dur in duration.iter_mut() {
-            *dur /= speed;
-        }
+    /// Needs the model files: `SUPERTONIC_ASSETS=<dir with onnx/ and voice_styles/> cargo test -- --ignored`
+    #[test]
+    #[ignore = "needs the model files; set SUPERTONIC_ASSETS"]
+    fn synthesize_once_produces_a_playable_wav() {
+        let assets = std::env::var("SUPERTONIC_ASSETS").expect("SUPERTONIC_ASSETS is not set");
+        let mut engine = load_text_to_speech(&format!("{}/onnx", assets), false, &LoadOptions::default()).unwrap();
+        let style = load_voice_style_from_bytes(&std::fs::read(format!("{}/voice_styles/F1.json", assets)).unwrap()).unwrap();
+
+        let (bytes, duration) = synthesize_once(
+            &mut engine,
+            "Hello there. This is a test.",
+            "en",
+            &style,
+            5,
+            1.05,
+            ChunkSilence::uniform(0.3),
+        )
+        .unwrap();
+
+        let reader = hound::WavReader::new(std::io::Cursor::new(&bytes)).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.sample_rate, engine.sample_rate as u32);
+        assert_eq!((spec.channels, spec.bits_per_sample), (DEFAULT_WAV_CHANNELS, DEFAULT_WAV_BIT_DEPTH));
+        assert!(duration > 0.5);
+        assert!((reader.duration() as f32 / spec.sample_rate as f32 - duration).abs() < 0.01);
+    }
 
-        // Encode text
-        // Input order: text_ids, style_ttl, text_mask
-        let text_enc_outputs: SessionOutputs = self.text_enc_model.run(ort::inputs![
-            Tensor::from_array(text_ids_array)?,
-            Tensor::from_array(style.ttl.clone())?,
-            Tensor::from_array(text_mask.clone())?
-        ])?;
+    #[test]
+    fn pipeline_stages_run_in_list_order() {
+        let audio = vec![0.1, -0.2, 0.4, -0.1];
+        let run = |stages: Vec<AudioStage>| {
+            let mut audio = audio.clone();
+            apply_pipeline(&mut audio, 24_000, &AudioPipeline { stages });
+            audio
+        };
+
+        let gain_then_normalize = run(vec![AudioStage::Gain { db: 6.0 }, AudioStage::Normalize { peak: 0.5 }]);
+        let normalize_then_gain = run(vec![AudioStage::Normalize { peak: 0.5 }, AudioStage::Gain { db: 6.0 }]);
+        assert!((gain_then_normalize[2] - 0.5).abs() < 1e-6);
+        assert!(normalize_then_gain[2] > 0.9);
+    }
 
-        let text_emb_output = &text_enc_outputs[0];
-        let (text_emb_shape, text_emb_data) = text_emb_output.try_extract_tensor::<f32>()?;
-        let text_emb = Array3::from_shape_vec(
-            (text_emb_shape[0] as usize, text_emb_shape[1] as usize, text_emb_shape[2] as usize),
-            text_emb_data.iter().cloned().collect()
-        )?;
+    #[test]
+    fn empty_pipeline_leaves_audio_alone() {
+        let mut audio = vec![0.0, 0.3, -0.7, 0.2, 0.0];
+        assert_eq!(apply_pipeline(&mut audio, 24_000, &AudioPipeline::default()), 0);
+        assert_eq!(audio, [0.0, 0.3, -0.7, 0.2, 0.0]);
+    }
 
-        // Sample noisy latent
-        let (mut xt, latent_mask) = sample_noisy_latent(
-            &duration,
-            self.sample_rate,
-            self.cfgs.ae.base_chunk_size,
-            self.cfgs.ttl.chunk_compress_factor,
-            self.cfgs.ttl.latent_dim,
+    #[test]
+    fn best_effort_keeps_the_chunks_around_a_failure() {
+        // One sample per character at 10 Hz, except the second sentence fails
+        let mut synthesize = |chunk: &str| -> Result<(Vec<f32>, f32)> {
+            if chunk.starts_with("Second") {
+                bail!("stub failure");
+            }
+            let len = chunk.chars().count();
+            Ok((vec![1.0; len], len as f32 / 10.0))
+        };
+        // Sentences too long to share a chunk
+        let text = ["First", "Second", "Third"]
+            .map(|word| format!("{} {}end.", word, "sentence words ".repeat(12)))
+            .join(" ");
+        let chunks = chunk_text(&text, &ChunkConfig::for_language("en"));
+        assert_eq!(chunks.len(), 3, "test text should split into three chunks");
+
+        let (wav, _, failures) = best_effort_chunks(&mut synthesize, &text, "en", 10, ChunkSilence::uniform(0.0)).unwrap();
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!((failures[0].index, failures[0].error.as_str()), (1, "stub failure"));
+        let spoken = chunks[0].chars().count() + chunks[2].chars().count();
+        assert_eq!(wav.iter().filter(|&&s| s == 1.0).count(), spoken);
+        assert_eq!(wav.len(), spoken + (FAILED_CHUNK_SILENCE * 10.0) as usize);
+
+        let mut always_fails = |_: &str| -> Result<(Vec<f32>, f32)> { bail!("stub failure") };
+        assert!(best_effort_chunks(&mut always_fails, &text, "en", 10, ChunkSilence::uniform(0.0)).is_err());
+    }
+
+    #[test]
+    fn lexicon_replaces_whole_words_only() {
+        let lexicon = HashMap::from([("sql".to_string(), "sequel".to_string())]);
+        assert_eq!(apply_lexicon("Learn SQL today", &lexicon), "Learn sequel today");
+        assert_eq!(apply_lexicon("MySQLish tools", &lexicon), "MySQLish tools");
+    }
+
+    #[test]
+    fn markdown_heading_bold_and_link_are_stripped() {
+        assert_eq!(strip_markdown("# Intro"), "Intro.");
+        assert_eq!(strip_markdown("This is **important**."), "This is important.");
+        assert_eq!(strip_markdown("See [the docs](https://example.com)."), "See the docs.");
+        assert_eq!(strip_markdown("2 * 3 * 4"), "2 * 3 * 4");
+    }
+
+    #[test]
+    fn long_sentence_without_comma_splitting_breaks_on_words() {
+        let sentence = "Alpha beta gamma, delta epsilon zeta, eta theta iota kappa.";
+        let config = |split_on_comma| ChunkConfig { max_len: 30, split_on_comma, ..Default::default() };
+
+        assert_eq!(
+            chunk_text(sentence, &config(true)),
+            vec!["Alpha beta gamma", "delta epsilon zeta", "eta theta iota kappa."]
+        );
+        assert_eq!(
+            chunk_text(sentence, &config(false)),
+            vec!["Alpha beta gamma, delta", "epsilon zeta, eta theta iota", "kappa."]
         );
+    }
 
-        // Denoising loop
-        for step in 0..total_step {
-            let current_step_array = ndarray::Array1::from_elem(bsz, step as f32);
-            let total_step_array = ndarray::Array1::from_elem(bsz, total_step as f32);
-
-            // Input order: xt, text_emb, style_ttl, latent_mask, text_mask, current_step, total_step
-            let vector_est_outputs: SessionOutputs = self.vector_est_model.run(ort::inputs![
-                Tensor::from_array(xt.clone())?,
-                Tensor::from_array(text_emb.clone())?,
-                Tensor::from_array(style.ttl.clone())?,
-                Tensor::from_array(latent_mask.clone())?,
-                Tensor::from_array(text_mask.clone())?,
-                Tensor::from_array(current_step_array)?,
-                Tensor::from_array(total_step_array)?
-            ])?;
-
-            let denoised_output = &vector_est_outputs[0];
-            let (denoised_shape, denoised_data) = denoised_output.try_extract_tensor::<f32>()?;
-            xt = Array3::from_shape_vec(
-                (denoised_shape[0] as usize, denoised_shape[1] as usize, denoised_shape[2] as usize),
-                denoised_data.iter().cloned().collect()
-            )?;
+    #[test]
+    fn wav_24_bit_round_trips_through_hound() {
+        let audio = [0.0, 0.5, -0.5, 1.0, -1.0, 2.0];
+        let bytes = encode_wav_to_bytes(&audio, 24_000, 24, 1).unwrap();
+
+        let mut reader = hound::WavReader::new(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 24);
+        let decoded: Vec<f32> = reader
+            .samples::<i32>()
+            .map(|s| s.unwrap() as f32 / 8_388_607.0)
+            .collect();
+        let clamped = [0.0, 0.5, -0.5, 1.0, -1.0, 1.0];
+        for (got, want) in decoded.iter().zip(clamped) {
+            assert!((got - want).abs() < 1e-6, "{} vs {}", got, want);
         }
+        assert_eq!(decoded.len(), audio.len());
+    }
 
-        // Generate waveform
-        let vocoder_outputs: SessionOutputs = self.vocoder_model.run(ort::inputs![
-            Tensor::from_array(xt)?
-        ])?;
+    #[test]
+    fn stereo_doubles_the_interleaved_sample_count() {
+        let audio = [0.25, -0.25, 0.5];
+        let read = |channels| {
+            let bytes = encode_wav_to_bytes(&audio, 24_000, 16, channels).unwrap();
+            let mut reader = hound::WavReader::new(std::io::Cursor::new(bytes)).unwrap();
+            assert_eq!(reader.spec().channels, channels);
+            reader.samples::<i16>().map(|s| s.unwrap()).collect::<Vec<_>>()
+        };
+
+        let mono = read(1);
+        let stereo = read(2);
+        assert_eq!(stereo.len(), 2 * mono.len());
+        // Each mono sample is copied to left and right
+        for (frame, sample) in stereo.chunks(2).zip(&mono) {
+            assert_eq!(frame, [*sample, *sample]);
+        }
+    }
 
-        let wav_output = &vocoder_outputs[0];
-        let (_, wav_data) = wav_output.try_extract_tensor::<f32>()?;
-        let wav: Vec<f32> = wav_data.iter().cloned().collect();
+    #[test]
+    fn ramp_peaks_follow_the_amplitude_in_each_bucket() {
+        // -1.0 up to just under 1.0 in 0.01 steps
+        let ramp: Vec<f32> = (0..200).map(|i| i as f32 / 100.0 - 1.0).collect();
+        let peaks = downsample_peaks(&ramp, 4);
 
-        Ok((wav, duration))
+        assert_eq!(peaks.len(), 4);
+        let expected = [1.0, 0.5, 0.49, 0.99];
+        for (got, want) in peaks.iter().zip(expected) {
+            assert!((got - want).abs() < 1e-5, "{:?}", peaks);
+        }
+        // More buckets than samples still gives one entry per bucket
+        assert_eq!(downsample_peaks(&[0.5], 3), vec![0.0, 0.0, 0.5]);
     }
 
-    pub fn call(
-        &mut self,
-        text: &str,
-        lang: &str,
-        style: &Style,
-        total_step: usize,
-        speed: f32,
-        silence_duration: f32,
-    ) -> Result<(Vec<f32>, f32)> {
-        let max_len = if lang == "ko" { 120 } else { 300 };
-        let chunks = chunk_text(text, Some(max_len));
+    #[test]
+    fn quiet_stretch_between_sounds_is_not_trimmed() {
+        let sample_rate = 1_000;
+        let config = TrimConfig::default();
+        // 200 ms silence, 100 ms sound, 300 ms dip, 100 ms sound, 200 ms silence
+        let mut audio = vec![0.0; 200];
+        audio.extend(vec![0.5; 100]);
+        audio.extend(vec![0.0; 300]);
+        audio.extend(vec![0.5; 100]);
+        audio.extend(vec![0.0; 200]);
+
+        let cut = trim_silence_adaptive(&mut audio, sample_rate, &config);
+
+        // Each end keeps 50 ms of padding; the dip in the middle survives whole
+        assert_eq!(cut, 150);
+        assert_eq!(audio.len(), 50 + 100 + 300 + 100 + 50);
+        assert!(audio[150..450].iter().all(|s| *s == 0.0));
+    }
 
-        let mut wav_cat: Vec<f32> = Vec::new();
-        let mut dur_cat: f32 = 0.0;
+    #[test]
+    fn numbered_list_items_become_their_own_chunks() {
+        let chunks = chunk_text("1. First item\n2. Second item", &ChunkConfig::default());
+        assert_eq!(chunks, vec!["First item", "Second item"]);
+    }
 
-        for (i, chunk) in chunks.iter().enumerate() {
-            let (wav, duration) = self._infer(&[chunk.clone()], &[lang.to_string()], style, total_step, speed)?;
+    #[test]
+    fn english_ordinals_are_spelled_out() {
+        assert_eq!(expand_ordinals("the 21st century"), "the twenty-first century");
+        assert_eq!(expand_ordinals("3rd and 12th"), "third and twelfth");
+        assert_eq!(preprocess("Her 21st birthday", "en"), "<en>Her twenty-first birthday.</en>");
+    }
 
-            let dur = duration[0];
-            let wav_len = (self.sample_rate as f32 * dur) as usize;
-            let wav_chunk = &wav[..wav_len.min(wav.len())];
+    #[test]
+    fn silence_splits_segments_only_when_long_enough() {
+        let sample_rate = 1_000;
+        // Sound, 50 ms gap, sound, 300 ms gap, sound
+        let mut audio = vec![0.5; 100];
+        audio.extend(vec![0.0; 50]);
+        audio.extend(vec![0.5; 100]);
+        audio.extend(vec![0.0; 300]);
+        audio.extend(vec![0.5; 100]);
+
+        assert_eq!(segment_by_silence(&audio, sample_rate, 200, 0.01), vec![(0, 250), (550, 650)]);
+        assert!(segment_by_silence(&[0.0; 100], sample_rate, 200, 0.01).is_empty());
+    }
 
-            if i == 0 {
-                wav_cat.extend_from_slice(wav_chunk);
-                dur_cat = dur;
-            } else {
-                let silence_len = (silence_duration * self.sample_rate as f32) as usize;
-                let silence = vec![0.0f32; silence_len];
 
-                wav_cat.extend_from_slice(&silence);
-                wav_cat.extend_from_slice(wav_chunk);
-                dur_cat += silence_duration + dur;
+    #[test]
+    fn seeded_noise_is_reproducible_and_masked_past_each_length() {
+        // 10 Hz with 2-sample chunks: 0.4s is 2 frames, 1.0s is 5
+        let sample =
+            |seed| sample_noisy_latent_with_rng(&[0.4, 1.0], 10, 2, 1, 3, &mut StdRng::seed_from_u64(seed)).unwrap();
+        let (latent, mask) = sample(7);
+        assert_eq!(latent.shape(), [2, 3, 5]);
+        assert_eq!(sample(7).0, latent);
+        assert_ne!(sample(8).0, latent);
+
+        for (b, len) in [(0, 2), (1, 5)] {
+            for t in 0..5 {
+                assert_eq!(mask[[b, 0, t]], if t < len { 1.0 } else { 0.0 });
+                for d in 0..3 {
+                    assert_eq!(latent[[b, d, t]] == 0.0, t >= len, "item {} frame {}", b, t);
+                }
             }
         }
+    }
+
+
+    #[test]
+    fn emoji_survive_normalization_only_when_kept() {
+        let stripped = preprocess_text("Good news 🎉 today", "en", &HashMap::new(), &PreprocessOptions::default()).unwrap();
+        assert_eq!(stripped, "<en>Good news today.</en>");
 
-        Ok((wav_cat, dur_cat))
+        let options = PreprocessOptions { keep_emoji: true, ..PreprocessOptions::default() };
+        let kept = preprocess_text("Good news 🎉 today", "en", &HashMap::new(), &options).unwrap();
+        assert!(kept.contains('🎉'), "{}", kept);
     }
 
-    pub fn batch(
-        &mut self,
-        text_list: &[String],
-        lang_list: &[String],
-        style: &Style,
-        total_step: usize,
-        speed: f32,
-    ) -> Result<(Vec<f32>, Vec<f32>)> {
-        self._infer(text_list, lang_list, style, total_step, speed)
+    #[test]
+    fn last_subtitle_cue_ends_at_the_total_duration() {
+        let mut engine = stub::engine();
+        // Paragraphs always start a new chunk
+        let text = "Hello there.\n\nThis is a test.\n\nAnd one more sentence.";
+        let (_, duration, timings) = engine
+            .call_with_timings(text, "en", &stub::style(), 2, SpeakingRate::Speed(1.0), ChunkSilence::uniform(0.3))
+            .unwrap();
+        assert_eq!(timings.len(), 3);
+        // The silence between chunks sits between cues
+        assert!(timings.windows(2).all(|pair| pair[1].start >= pair[0].end + 0.29));
+
+        let srt = format_srt(&timings);
+        let cues: Vec<&str> = srt.trim_end().split("\n\n").collect();
+        for (i, cue) in cues.iter().enumerate() {
+            assert!(cue.starts_with(&format!("{}\n", i + 1)), "{:?}", cue);
+        }
+        let last_times = cues[2].lines().nth(1).unwrap();
+        assert_eq!(last_times.split(" --> ").nth(1).unwrap(), format_srt_timestamp(duration));
+        assert_eq!(format_srt_timestamp(3725.5), "01:02:05,500");
     }
-}
 
-// ============================================================================
-// Component Loading Functions
-// ============================================================================
+    #[test]
+    fn word_timings_increase_and_end_at_the_total_duration() {
+        let mut engine = stub::engine();
+        let text = "Hello there, friend.\n\nThis is a longer test sentence.";
+        let (_, duration, timings) = engine
+            .call_with_timings(text, "en", &stub::style(), 2, SpeakingRate::Speed(1.0), ChunkSilence::uniform(0.3))
+            .unwrap();
+
+        let words = word_timings(&timings);
+        assert_eq!(words.len(), 9);
+        assert_eq!(words[0].start_s, 0.0);
+        assert!(words.iter().all(|w| w.start_s < w.end_s), "{:?}", words);
+        assert!(words.windows(2).all(|pair| pair[0].end_s <= pair[1].start_s), "{:?}", words);
+        assert_eq!(words.last().unwrap().end_s, duration);
+    }
 
-/// Load voice style from JSON bytes (for Android resource loading)
-pub fn load_voice_style_from_bytes(bytes: &[u8]) -> Result<Style> {
-    let data: VoiceStyleData = serde_json::from_slice(bytes)?;
+    #[test]
+    fn higher_words_per_minute_gives_shorter_audio() {
+        // Ten words predicted at five seconds read at 120 wpm
+        assert_eq!(speed_for_wpm(10, 5.0, 240.0), 2.0);
+        assert_eq!(speed_for_wpm(0, 5.0, 240.0), 1.0);
+
+        let mut engine = stub::engine();
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let mut duration_at = |wpm: f32| {
+            let rate = SpeakingRate::WordsPerMinute(wpm);
+            engine.call_with_timings(text, "en", &stub::style(), 2, rate, ChunkSilence::uniform(0.3)).unwrap().1
+        };
+        let slow = duration_at(120.0);
+        let fast = duration_at(240.0);
+        assert!(fast < slow, "{} at 240 wpm vs {} at 120 wpm", fast, slow);
+        // Nine words at 120 wpm take about 4.5 seconds
+        assert!((slow - 4.5).abs() < 0.1, "{}", slow);
+    }
 
-    let ttl_dims = &data.style_ttl.dims;
-    let dp_dims = &data.style_dp.dims;
+    /// `StubModels` whose vocoder fails with `code` for its first `failures` runs
+    struct FlakyModels {
+        stub: stub::StubModels,
+        code: ort::ErrorCode,
+        failures: usize,
+        vocoder_runs: Arc<AtomicUsize>,
+    }
 
-    // Single voice style, so batch size is 1
-    let bsz = 1;
-    let ttl_dim1 = ttl_dims[1];
-    let ttl_dim2 = ttl_dims[2];
-    let dp_dim1 = dp_dims[1];
-    let dp_dim2 = dp_dims[2];
+    impl TtsModels for FlakyModels {
+        fn predict_duration(
+            &mut self,
+            text_ids: &Array2<i64>,
+            style_dp: &Array3<f32>,
+            text_mask: &Array3<f32>,
+        ) -> Result<Vec<f32>> {
+            self.stub.predict_duration(text_ids, style_dp, text_mask)
+        }
 
-    // Flatten TTL data
-    let mut ttl_flat = Vec::with_capacity(bsz * ttl_dim1 * ttl_dim2);
-    for batch in &data.style_ttl.data {
-        for row in batch {
-            ttl_flat.extend_from_slice(row);
+        fn encode_text(
+            &mut self,
+            text_ids: &Array2<i64>,
+            style_ttl: &Array3<f32>,
+            text_mask: &Array3<f32>,
+        ) -> Result<Array3<f32>> {
+            self.stub.encode_text(text_ids, style_ttl, text_mask)
         }
-    }
 
-    // Flatten DP data
-    let mut dp_flat = Vec::with_capacity(bsz * dp_dim1 * dp_dim2);
-    for batch in &data.style_dp.data {
-        for row in batch {
-            dp_flat.extend_from_slice(row);
+        fn denoise(&mut self, xt: &mut Array3<f32>, inputs: &DenoiseInputs<'_>) -> Result<()> {
+            self.stub.denoise(xt, inputs)
+        }
+
+        fn vocode(&mut self, xt: &Array3<f32>) -> Result<Vec<f32>> {
+            let run = self.vocoder_runs.fetch_add(1, Ordering::Relaxed);
+            if run < self.failures {
+                return Err(ort::Error::new_with_code(self.code, "simulated failure").into());
+            }
+            self.stub.vocode(xt)
+        }
+
+        fn check_style(&self, style: &Style) -> Result<()> {
+            self.stub.check_style(style)
         }
     }
 
-    let ttl_style = Array3::from_shape_vec((bsz, ttl_dim1, ttl_dim2), ttl_flat)?;
-    let dp_style = Array3::from_shape_vec((bsz, dp_dim1, dp_dim2), dp_flat)?;
+    #[test]
+    fn transient_model_failure_is_retried_until_it_succeeds() {
+        let call = |engine: &mut TextToSpeech| {
+            engine.call("Hello there.", "en", &stub::style(), 2, 1.0, ChunkSilence::uniform(0.3)).map(|(wav, _)| wav)
+        };
+        let synthesize = |code, failures| {
+            let vocoder_runs = Arc::new(AtomicUsize::new(0));
+            let stub = stub::StubModels::default();
+            let models = FlakyModels { stub, code, failures, vocoder_runs: vocoder_runs.clone() };
+            let result = call(&mut stub::engine_on(Box::new(models)));
+            (result, vocoder_runs.load(Ordering::Relaxed))
+        };
+        let expected = call(&mut stub::engine()).unwrap();
+
+        let (wav, runs) = synthesize(ort::ErrorCode::GenericFailure, 1);
+        assert_eq!(wav.unwrap(), expected);
+        assert_eq!(runs, 2);
+
+        // Bad input isn't retried, and a failure that outlasts the retries is returned
+        let (wav, runs) = synthesize(ort::ErrorCode::InvalidArgument, 1);
+        assert!(wav.is_err());
+        assert_eq!(runs, 1);
+        let (wav, runs) = synthesize(ort::ErrorCode::GenericFailure, 5);
+        assert!(wav.is_err());
+        assert_eq!(runs, RUN_RETRIES as usize + 1);
+    }
 
-    Ok(Style {
-        ttl: ttl_style,
-        dp: dp_style,
-    })
-}
+    #[test]
+    fn constant_offset_is_removed() {
+        let mut audio: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.05).sin() * 0.5 + 0.1).collect();
+        let original = audio.clone();
+        remove_dc_offset(&mut audio);
 
-/// Load voice style from JSON files
-pub fn load_voice_style(voice_style_paths: &[String], verbose: bool) -> Result<Style> {
-    let bsz = voice_style_paths.len();
-
-    // Read first file to get dimensions
-    let first_file = File::open(&voice_style_paths[0])
-        .context("Failed to open voice style file")?;
-    let first_reader = BufReader::new(first_file);
-    let first_data: VoiceStyleData = serde_json::from_reader(first_reader)?;
-
-    let ttl_dims = &first_data.style_ttl.dims;
-    let dp_dims = &first_data.style_dp.dims;
-
-    let ttl_dim1 = ttl_dims[1];
-    let ttl_dim2 = ttl_dims[2];
-    let dp_dim1 = dp_dims[1];
-    let dp_dim2 = dp_dims[2];
-
-    // Pre-allocate arrays with full batch size
-    let ttl_size = bsz * ttl_dim1 * ttl_dim2;
-    let dp_size = bsz * dp_dim1 * dp_dim2;
-    let mut ttl_flat = vec![0.0f32; ttl_size];
-    let mut dp_flat = vec![0.0f32; dp_size];
-
-    // Fill in the data
-    for (i, path) in voice_style_paths.iter().enumerate() {
-        let file = File::open(path).context("Failed to open voice style file")?;
-        let reader = BufReader::new(file);
-        let data: VoiceStyleData = serde_json::from_reader(reader)?;
-
-        // Flatten TTL data
-        let ttl_offset = i * ttl_dim1 * ttl_dim2;
-        let mut idx = 0;
-        for batch in &data.style_ttl.data {
-            for row in batch {
-                for &val in row {
-                    ttl_flat[ttl_offset + idx] = val;
-                    idx += 1;
-                }
-            }
+        let mean = audio.iter().sum::<f32>() / audio.len() as f32;
+        assert!(mean.abs() < 1e-4, "{}", mean);
+        // Only the offset moves; the signal's shape is unchanged
+        let shift = original[0] - audio[0];
+        assert!(original.iter().zip(&audio).all(|(a, b)| (a - b - shift).abs() < 1e-6));
+
+        let mut empty: Vec<f32> = Vec::new();
+        remove_dc_offset(&mut empty);
+    }
+
+    #[test]
+    fn samples_past_full_scale_are_counted_as_clipped() {
+        let mut audio = vec![0.5, 1.5, -1.5, 1.0, -1.0, 0.0, 1.5];
+        assert_eq!(count_clipped_samples(&audio), 3);
+
+        // Attenuating to the peak leaves nothing to clip
+        let gain = attenuate_to_peak(&mut audio, 0.99);
+        assert!((gain - 0.66).abs() < 1e-6, "{}", gain);
+        assert_eq!(count_clipped_samples(&audio), 0);
+    }
+
+    #[test]
+    fn six_db_of_gain_roughly_doubles_the_amplitude() {
+        let original = vec![0.25, -0.4, 0.6, 0.0];
+
+        let mut boosted = original.clone();
+        apply_gain_db(&mut boosted, 6.0);
+        for (boosted, original) in boosted.iter().zip(&original) {
+            assert!((boosted - original * 2.0).abs() < 0.01, "{} vs {}", boosted, original);
         }
+        // Boosting isn't clamped here, so clipping is still counted afterwards
+        assert_eq!(count_clipped_samples(&boosted), 1);
 
-        // Flatten DP data
-        let dp_offset = i * dp_dim1 * dp_dim2;
-        idx = 0;
-        for batch in &data.style_dp.data {
-            for row in batch {
-                for &val in row {
-                    dp_flat[dp_offset + idx] = val;
-                    idx += 1;
-                }
-            }
+        for gain_db in [-120.0, f32::NEG_INFINITY] {
+            let mut quiet = original.clone();
+            apply_gain_db(&mut quiet, gain_db);
+            assert!(quiet.iter().all(|s| s.abs() < 1e-5), "{:?} at {} dB", quiet, gain_db);
         }
     }
 
-    let ttl_style = Array3::from_shape_vec((bsz, ttl_dim1, ttl_dim2), ttl_flat)?;
-    let dp_style = Array3::from_shape_vec((bsz, dp_dim1, dp_dim2), dp_flat)?;
+    #[test]
+    fn joining_two_wavs_adds_their_samples_and_the_gap() {
+        let dir = std::env::temp_dir().join("tts_concat_wavs");
+        std::fs::create_dir_all(&dir).unwrap();
+        let (first, second) = (dir.join("sentence_0.wav"), dir.join("sentence_1.wav"));
+        let other_rate = dir.join("other_rate.wav");
+        write_wav_file(&first, &[0.5; 100], 1000, 16, 1).unwrap();
+        write_wav_file(&second, &[-0.25; 150], 1000, 16, 1).unwrap();
+        write_wav_file(&other_rate, &[0.5; 100], 2000, 16, 1).unwrap();
+
+        let (samples, sample_rate, duration) = concat_wav_files(&[&first, &second], 0.05).unwrap();
+        assert_eq!(sample_rate, 1000);
+        assert_eq!(samples.len(), 100 + 50 + 150);
+        assert!((duration - 0.3).abs() < 1e-6, "{}", duration);
+        assert!((samples[0] - 0.5).abs() < 1e-3 && samples[120] == 0.0 && (samples[299] + 0.25).abs() < 1e-3);
+
+        assert!(concat_wav_files(&[&first, &other_rate], 0.0).is_err());
+    }
 
-    if verbose {
-        println!("Loaded {} voice styles\n", bsz);
+    #[test]
+    fn paragraph_break_gets_a_longer_gap_than_a_sentence_break() {
+        // Two sentences too long to share a chunk, then a second paragraph
+        let long = "This sentence keeps going with plenty of words so it fills most of a chunk by itself ".repeat(2);
+        let text = format!("{0}one. {0}two.\n\nA new paragraph.", long);
+        let silence = ChunkSilence { sentence: 0.2, paragraph: 0.8, samples: None };
+
+        let (_, _, timings) = stub::engine()
+            .call_with_timings(&text, "en", &stub::style(), 2, SpeakingRate::Speed(1.0), silence)
+            .unwrap();
+        let gaps: Vec<f32> = timings.windows(2).map(|pair| pair[1].start - pair[0].end).collect();
+        assert_eq!(gaps.len(), 2);
+        assert!((gaps[0] - 0.2).abs() < 0.01, "{:?}", gaps);
+        assert!((gaps[1] - 0.8).abs() < 0.01, "{:?}", gaps);
     }
 
-    Ok(Style {
-        ttl: ttl_style,
-        dp: dp_style,
-    })
-}
+    #[test]
+    fn phoneme_timings_cover_each_processed_character_in_order() {
+        let mut engine = stub::engine();
+        let text = "Hello there, friend.\n\nThis is a longer test sentence.";
+        let (_, duration, timings) = engine
+            .call_with_timings(text, "en", &stub::style(), 2, SpeakingRate::Speed(1.0), ChunkSilence::uniform(0.3))
+            .unwrap();
+        let processed: Vec<String> = timings.iter().map(|timing| preprocess(&timing.text, "en")).collect();
+        assert!(processed[0].starts_with("<en>"), "{:?}", processed);
+
+        let phonemes = phoneme_timings(&timings, &processed, "en");
+        let untagged: String = processed
+            .iter()
+            .map(|text| text.trim_start_matches("<en>").trim_end_matches("</en>"))
+            .collect();
+        assert_eq!(phonemes.len(), untagged.chars().count());
+        assert_eq!(phonemes.iter().map(|p| p.char).collect::<String>(), untagged);
+        assert!(phonemes.iter().all(|p| p.start_s < p.end_s), "{:?}", phonemes);
+        assert!(phonemes.windows(2).all(|pair| pair[0].end_s <= pair[1].start_s), "{:?}", phonemes);
+        assert_eq!(phonemes[0].start_s, 0.0);
+        assert_eq!(phonemes.last().unwrap().end_s, duration);
+    }
 
-/// Load TTS components using ort (ONNX Runtime) from file paths
-/// Use this for desktop platforms
-pub fn load_text_to_speech(onnx_dir: &str, _use_gpu: bool) -> Result<TextToSpeech> {
-    println!("Loading TTS models with ONNX Runtime (CPU inference)...\n");
+    #[test]
+    fn paragraph_silence_option_lengthens_the_pause_at_blank_lines() {
+        let duration_of = |engine: &mut TextToSpeech, text: &str| {
+            engine.call(text, "en", &stub::style(), 2, 1.0, ChunkSilence::uniform(0.3)).unwrap().1
+        };
+        let mut engine = stub::engine();
+        let joined = duration_of(&mut engine, "First paragraph here. Second paragraph here.");
+        let default_break = duration_of(&mut engine, "First paragraph here.\n\nSecond paragraph here.");
 
-    let cfgs = load_cfgs(onnx_dir)?;
+        engine.set_preprocess_options(PreprocessOptions { paragraph_silence: Some(1.0), ..Default::default() });
+        let long_break = duration_of(&mut engine, "First paragraph here.\n\nSecond paragraph here.");
 
-    let dp_path = format!("{}/duration_predictor.onnx", onnx_dir);
-    let text_enc_path = format!("{}/text_encoder.onnx", onnx_dir);
-    let vector_est_path = format!("{}/vector_estimator.onnx", onnx_dir);
-    let vocoder_path = format!("{}/vocoder.onnx", onnx_dir);
+        assert!(long_break > joined + 0.9, "{} vs {}", long_break, joined);
+        assert!((long_break - default_break - 0.7).abs() < 0.01, "{} vs {}", long_break, default_break);
+    }
 
-    println!("Loading duration predictor...");
-    let dp_model = Session::builder()?
-        .with_optimization_level(GraphOptimizationLevel::Level3)?
-        .commit_from_file(&dp_path)?;
+    #[test]
+    fn each_chunk_encodes_to_a_standalone_wav() {
+        let mut engine = stub::engine();
+        let text = "First chunk of the stream.\n\nSecond chunk, a bit longer than the first one.";
+        let mut all_pcm = Vec::new();
 
-    println!("Loading text encoder...");
-    let text_enc_model = Session::builder()?
-        .with_optimization_level(GraphOptimizationLevel::Level3)?
-        .commit_from_file(&text_enc_path)?;
+        for chunk in chunk_text(text, &ChunkConfig::for_language("en")) {
+            let (wav, _) = engine.synthesize_chunk(&chunk, "en", &stub::style(), 2, SpeakingRate::Speed(1.0)).unwrap();
+            let bytes = encode_wav_to_bytes(&wav, stub::SAMPLE_RATE, 16, 1).unwrap();
 
-    println!("Loading vector estimator...");
-    let vector_est_model = Session::builder()?
-        .with_optimization_level(GraphOptimizationLevel::Level3)?
-        .commit_from_file(&vector_est_path)?;
+            let reader = hound::WavReader::new(std::io::Cursor::new(&bytes)).unwrap();
+            assert_eq!(reader.len() as usize, wav.len());
+            assert_eq!(bytes.len(), 44 + 2 * wav.len());
+            all_pcm.extend_from_slice(&bytes[44..]);
+        }
 
-    println!("Loading vocoder...");
-    let vocoder_model = Session::builder()?
-        .with_optimization_level(GraphOptimizationLevel::Level3)?
-        .commit_from_file(&vocoder_path)?;
+        // One header up front, then every chunk's raw samples
+        let total_samples = all_pcm.len() as u64 / 2;
+        let mut stream = wav_header_only(stub::SAMPLE_RATE, total_samples, 16).unwrap();
+        assert_eq!(stream.len(), 44);
+        stream.extend_from_slice(&all_pcm);
+        let reader = hound::WavReader::new(std::io::Cursor::new(&stream)).unwrap();
+        assert_eq!(reader.len() as u64, total_samples);
+        assert_eq!(reader.spec().sample_rate, stub::SAMPLE_RATE as u32);
+        assert_eq!(reader.into_samples::<i16>().count() as u64, total_samples);
+    }
 
-    let unicode_indexer_path = format!("{}/unicode_indexer.json", onnx_dir);
-    let text_processor = UnicodeProcessor::new(&unicode_indexer_path)?;
+    #[test]
+    fn char_budget_cuts_at_the_chunk_that_reaches_it() {
+        let text = "First paragraph.\n\nSecond paragraph.\n\nThird paragraph.";
 
-    println!("All models loaded successfully!\n");
+        assert_eq!(truncate_to_char_budget(text, "en", 5), ("First paragraph.".to_string(), true));
+        assert_eq!(
+            truncate_to_char_budget(text, "en", 17),
+            ("First paragraph.\n\nSecond paragraph.".to_string(), true)
+        );
+        assert_eq!(truncate_to_char_budget(text, "en", 1000), (text.to_string(), false));
+        // Reaching the budget on the last chunk drops nothing
+        assert_eq!(truncate_to_char_budget(text, "en", 48), (text.to_string(), false));
+
+        let (kept, truncated) = truncate_to_char_budget("Before. [pause:500] After.\n\nLast.", "en", 8);
+        assert!(truncated);
+        assert_eq!(split_pause_markers(&kept).len(), 2, "{:?}", kept);
+        assert!(!kept.contains("Last"), "{:?}", kept);
+    }
 
-    Ok(TextToSpeech::new(
-        cfgs,
-        text_processor,
-        dp_model,
-        text_enc_model,
-        vector_est_model,
-        vocoder_model,
-    ))
-}
+    #[test]
+    fn high_pass_removes_rumble_but_keeps_a_mid_tone() {
+        let sample_rate = 16000;
+        let tone = |hz: f32| -> Vec<f32> {
+            (0..sample_rate)
+                .map(|i| (2.0 * std::f32::consts::PI * hz * i as f32 / sample_rate as f32).sin() * 0.5)
+                .collect()
+        };
+        // Skip the filter's settling time at the start
+        let energy = |audio: &[f32]| audio[1000..].iter().map(|s| s * s).sum::<f32>();
+
+        let mut rumble: Vec<f32> = tone(30.0).iter().map(|s| s + 0.3).collect();
+        let before = energy(&rumble);
+        high_pass_filter(&mut rumble, sample_rate, 80.0);
+        assert!(energy(&rumble) < before * 0.2, "{} vs {}", energy(&rumble), before);
+
+        let mut mid = tone(1000.0);
+        let before = energy(&mid);
+        high_pass_filter(&mut mid, sample_rate, 80.0);
+        assert!(mid.iter().all(|s| s.is_finite()));
+        assert!((energy(&mid) / before - 1.0).abs() < 0.05, "{} vs {}", energy(&mid), before);
+
+        // An out-of-range cutoff leaves the audio alone
+        let mut untouched = tone(30.0);
+        high_pass_filter(&mut untouched, sample_rate, 9000.0);
+        assert_eq!(untouched, tone(30.0));
+    }
 
-/// Model data loaded from bytes (for Android resource loading)
-pub struct ModelBytes {
-    pub config: Vec<u8>,
-    pub unicode_indexer: Vec<u8>,
-    pub duration_predictor: Vec<u8>,
-    pub text_encoder: Vec<u8>,
-    pub vector_estimator: Vec<u8>,
-    pub vocoder: Vec<u8>,
-}
+    #[test]
+    fn malformed_style_dims_are_reported_by_name() {
+        let style_json = |ttl: &str, dp: &str| format!(r#"{{"style_ttl": {}, "style_dp": {}}}"#, ttl, dp);
+        let good = r#"{"data": [[[0.1, 0.2], [0.3, 0.4]]], "dims": [1, 2, 2], "type": "float32"}"#;
+        assert_eq!(load_voice_style_from_bytes(style_json(good, good).as_bytes()).unwrap().ttl.dim(), (1, 2, 2));
 
-/// Load TTS components from bytes (for Android/mobile platforms)
-/// Note: On Android, libonnxruntime.so must be available in jniLibs
-pub fn load_text_to_speech_from_bytes(model_bytes: ModelBytes) -> Result<TextToSpeech> {
-    println!("Loading TTS models from bytes (mobile mode)...\n");
+        let too_short = r#"{"data": [[[0.1, 0.2, 0.3]]], "dims": [1, 2, 2], "type": "float32"}"#;
+        let err = load_voice_style_from_bytes(style_json(good, too_short).as_bytes()).err().unwrap().to_string();
+        assert!(err.contains("style_dp has 3 values but dims [1, 2, 2] need 4"), "{}", err);
 
-    // Parse config from bytes
-    let cfgs: Config = serde_json::from_slice(&model_bytes.config)?;
+        let wrong_rank = r#"{"data": [[[0.1, 0.2, 0.3, 0.4]]], "dims": [1, 4], "type": "float32"}"#;
+        let err = load_voice_style_from_bytes(style_json(wrong_rank, good).as_bytes()).err().unwrap().to_string();
+        assert!(err.contains("style_ttl dims [1, 4] should have 3 entries"), "{}", err);
+    }
 
-    // Parse unicode indexer from bytes
-    let indexer: Vec<i64> = serde_json::from_slice(&model_bytes.unicode_indexer)?;
-    let text_processor = UnicodeProcessor { indexer };
+    #[test]
+    fn call_single_keeps_a_long_korean_sentence_in_one_piece() {
+        let mut engine = stub::engine();
+        let sentence = format!("{}끝.", "안녕하세요 반갑습니다 ".repeat(17));
+        assert!(sentence.chars().count() > 200);
+        assert!(chunk_text(&sentence, &ChunkConfig::for_language("ko")).len() > 1);
 
-    println!("Loading duration predictor from bytes...");
-    let dp_model = Session::builder()?
-        .with_optimization_level(GraphOptimizationLevel::Level3)?
-        .commit_from_memory(&model_bytes.duration_predictor)?;
+        let (wav, duration) = engine.call_single(&sentence, "ko", &stub::style(), 2, 1.0).unwrap();
+        // Stub speech is never silent, so a gap between chunks would show up as zeros
+        assert!(wav.iter().all(|&s| s != 0.0));
 
-    println!("Loading text encoder from bytes...");
-    let text_enc_model = Session::builder()?
-        .with_optimization_level(GraphOptimizationLevel::Level3)?
-        .commit_from_memory(&model_bytes.text_encoder)?;
+        let (_, rechunked) = engine.call(&sentence, "ko", &stub::style(), 2, 1.0, ChunkSilence::uniform(0.5)).unwrap();
+        assert!(rechunked > duration + 0.4, "{} vs {}", rechunked, duration);
 
-    println!("Loading vector estimator from bytes...");
-    let vector_est_model = Session::builder()?
-        .with_optimization_level(GraphOptimizationLevel::Level3)?
-        .commit_from_memory(&model_bytes.vector_estimator)?;
+        let too_long = "가".repeat(MAX_SINGLE_CALL_CHARS + 1);
+        assert!(engine.call_single(&too_long, "ko", &stub::style(), 2, 1.0).is_err());
+    }
 
-    println!("Loading vocoder from bytes...");
-    let vocoder_model = Session::builder()?
-        .with_optimization_level(GraphOptimizationLevel::Level3)?
-        .commit_from_memory(&model_bytes.vocoder)?;
+    #[test]
+    fn near_zero_duration_still_gets_a_latent_frame() {
+        let (latent, mask) = sample_noisy_latent(&[1e-6], 44100, 512, 6, 24).unwrap();
+        assert_eq!(latent.dim(), (1, 144, MIN_LATENT_LEN));
+        assert_eq!(mask.dim(), (1, 1, MIN_LATENT_LEN));
+        assert!(mask.iter().all(|&m| m == 1.0));
+
+        // Next to a longer utterance the short one still keeps its first frame
+        let (latent, mask) = sample_noisy_latent(&[1e-6, 1.0], 44100, 512, 6, 24).unwrap();
+        assert_eq!(latent.dim(), (2, 144, 15));
+        assert_eq!(mask[[0, 0, 0]], 1.0);
+        assert_eq!(mask.slice(ndarray::s![0, 0, 1..]).sum(), 0.0);
+
+        for bad in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(sample_noisy_latent(&[bad], 44100, 512, 6, 24).is_err(), "{}", bad);
+        }
+    }
 
-    println!("All models loaded successfully!\n");
+    #[test]
+    fn failed_model_run_names_the_stage_and_input_shapes() {
+        let stub = stub::StubModels::default();
+        let vocoder_runs = Arc::new(AtomicUsize::new(0));
+        let models = FlakyModels { stub, code: ort::ErrorCode::InvalidArgument, failures: 1, vocoder_runs };
+        let mut engine = stub::engine_on(Box::new(models));
+        let latent_channels = engine.config().ttl.latent_dim * engine.config().ttl.chunk_compress_factor;
+
+        let err = engine.call("Hello there.", "en", &stub::style(), 2, 1.0, ChunkSilence::uniform(0.3)).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains(&format!("Vocoder inference failed: xt [1, {}, ", latent_channels)), "{}", message);
+        assert!(message.contains("check the model's input order and shapes"), "{}", message);
+        assert!(message.contains("simulated failure"), "{}", message);
+
+        assert_eq!(
+            run_failure_context("Duration predictor", &[("text_ids", &[1, 37]), ("style_dp", &[1, 8, 16])]),
+            "Duration predictor inference failed: text_ids [1, 37], style_dp [1, 8, 16] \
+             (check the model's input order and shapes)"
+        );
+    }
 
-    Ok(TextToSpeech::new(
-        cfgs,
-        text_processor,
-        dp_model,
-        text_enc_model,
-        vector_est_model,
-        vocoder_model,
-    ))
+    /// Needs the model files, see `synthesize_once_produces_a_playable_wav`
+    #[test]
+    #[ignore = "needs the model files; set SUPERTONIC_ASSETS"]
+    fn every_model_is_built_at_the_chosen_optimization_level() {
+        let assets = std::env::var("SUPERTONIC_ASSETS").expect("SUPERTONIC_ASSETS is not set");
+        let dir = std::env::temp_dir().join("tts_optimization_level");
+        let _ = std::fs::remove_dir_all(&dir);
+        let options = LoadOptions {
+            optimization_level: OptimizationLevel::Disable,
+            optimized_model_dir: Some(dir.clone()),
+        };
+
+        load_text_to_speech(&format!("{}/onnx", assets), false, &options).unwrap();
+
+        // Each cached graph's tag records the level its session was built at
+        for model in ["duration_predictor", "text_encoder", "vector_estimator", "vocoder"] {
+            let tag = std::fs::read_to_string(dir.join(format!("{}.ort.tag", model))).unwrap();
+            assert!(tag.ends_with(" Disable"), "{}: {}", model, tag);
+        }
+    }
+
+    #[test]
+    fn optimized_graph_is_stale_once_its_model_or_level_changes() {
+        let dir = std::env::temp_dir().join("tts_optimized_tags");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let cached = OptimizedModel::new(&dir, "vocoder", "abc123", OptimizationLevel::Level3);
+        assert!(!cached.is_current());
+
+        std::fs::write(&cached.graph, "graph").unwrap();
+        // A graph without a tag may be a half-written one
+        assert!(!cached.is_current());
+        std::fs::write(&cached.tag_file, &cached.tag).unwrap();
+        assert!(cached.is_current());
+
+        assert!(!OptimizedModel::new(&dir, "vocoder", "def456", OptimizationLevel::Level3).is_current());
+        assert!(!OptimizedModel::new(&dir, "vocoder", "abc123", OptimizationLevel::Level1).is_current());
+        assert!(!OptimizedModel::new(&dir, "text_encoder", "abc123", OptimizationLevel::Level3).is_current());
+    }
+
+    /// Needs the model files, see `synthesize_once_produces_a_playable_wav`
+    #[test]
+    #[ignore = "needs the model files; set SUPERTONIC_ASSETS"]
+    fn second_load_reuses_the_optimized_graphs() {
+        let assets = std::env::var("SUPERTONIC_ASSETS").expect("SUPERTONIC_ASSETS is not set");
+        let dir = std::env::temp_dir().join("tts_optimized_graphs");
+        let _ = std::fs::remove_dir_all(&dir);
+        let options = LoadOptions { optimized_model_dir: Some(dir.clone()), ..Default::default() };
+        let models = ["duration_predictor", "text_encoder", "vector_estimator", "vocoder"];
+        let modified = |model: &str| std::fs::metadata(dir.join(format!("{}.ort", model))).unwrap().modified().unwrap();
+
+        load_text_to_speech(&format!("{}/onnx", assets), false, &options).unwrap();
+        let written: Vec<_> = models.iter().map(|model| modified(model)).collect();
+
+        let mut engine = load_text_to_speech(&format!("{}/onnx", assets), false, &options).unwrap();
+        assert_eq!(models.iter().map(|model| modified(model)).collect::<Vec<_>>(), written);
+        let style = load_voice_style_from_bytes(&std::fs::read(format!("{}/voice_styles/F1.json", assets)).unwrap()).unwrap();
+        let (wav, _) = engine.call("Hello there.", "en", &style, 5, 1.0, ChunkSilence::uniform(0.3)).unwrap();
+        assert!(!wav.is_empty());
+    }
+
+    #[test]
+    fn mixed_script_text_splits_into_runs_with_their_own_language() {
+        let segment = |text: &str, lang: &str| ScriptSegment { text: text.to_string(), lang: lang.to_string() };
+        assert_eq!(
+            segment_by_script("The word 안녕 means hello", "en"),
+            [segment("The word ", "en"), segment("안녕 ", "ko"), segment("means hello", "en")]
+        );
+        assert_eq!(
+            segment_by_script("\"안녕\" is Korean", "ko"),
+            [segment("\"안녕\" ", "ko"), segment("is Korean", "en")]
+        );
+        assert_eq!(segment_by_script("Bonjour à tous", "fr"), [segment("Bonjour à tous", "fr")]);
+
+        // In mixed-language mode each run is its own model pass
+        let (mut engine, steps) = stub::counting_engine();
+        let rate = SpeakingRate::Speed(1.0);
+        engine.synthesize_chunk("The word 안녕 means hello", "en", &stub::style(), 2, rate).unwrap();
+        assert_eq!(steps.swap(0, Ordering::Relaxed), 2);
+        engine.set_preprocess_options(PreprocessOptions { mixed_language: true, ..Default::default() });
+        engine.synthesize_chunk("The word 안녕 means hello", "en", &stub::style(), 2, rate).unwrap();
+        assert_eq!(steps.load(Ordering::Relaxed), 6);
+    }
+
+    #[test]
+    fn silence_samples_inserts_exactly_that_many_zeros() {
+        let mut engine = stub::engine();
+        let rate = SpeakingRate::Speed(1.0);
+        let (first, _) = engine.synthesize_chunk("First paragraph.", "en", &stub::style(), 2, rate).unwrap();
+        let (second, _) = engine.synthesize_chunk("Second paragraph.", "en", &stub::style(), 2, rate).unwrap();
+
+        let text = "First paragraph.\n\nSecond paragraph.";
+        let silence = ChunkSilence { samples: Some(1000), ..ChunkSilence::uniform(0.3) };
+        let (wav, duration) = engine.call(text, "en", &stub::style(), 2, 1.0, silence).unwrap();
+
+        let expected: Vec<f32> = first.iter().chain(&vec![0.0; 1000]).chain(&second).copied().collect();
+        assert_eq!(wav, expected);
+        assert!((duration - wav.len() as f32 / stub::SAMPLE_RATE as f32).abs() < 1e-3, "{}", duration);
+    }
+
+    #[test]
+    fn indexer_stats_and_char_support_follow_the_indexer() {
+        // Every code point up to U+01FF has a token except the section sign
+        let mut indexer: Vec<i64> = (0..0x200).collect();
+        indexer['§' as usize] = -1;
+        let processor = UnicodeProcessor { indexer, lexicon: HashMap::new(), options: PreprocessOptions::default() };
+
+        let stats = processor.stats();
+        assert_eq!((stats.size, stats.max_index, stats.negative_count), (0x200, 0x1FF, 1));
+        assert!(processor.token_id('a') >= 0);
+        assert_eq!(processor.token_id('§'), -1);
+        // Past the end of the indexer
+        assert_eq!(processor.token_id('가'), -1);
+    }
+
+    #[test]
+    fn text_with_nothing_to_speak_is_skipped_not_synthesized() {
+        let err = preprocess_text("😀🎉 ✨", "en", &HashMap::new(), &PreprocessOptions::default()).unwrap_err();
+        assert!(err.is::<EmptyAfterNormalization>(), "{}", err);
+
+        let mut engine = stub::engine();
+        let call = |engine: &mut TextToSpeech, text: &str| {
+            let silence = ChunkSilence::uniform(0.3);
+            engine.call_with_timings(text, "en", &stub::style(), 2, SpeakingRate::Speed(1.0), silence)
+        };
+        let err = call(&mut engine, "😀🎉\n\n✨✨").unwrap_err();
+        assert!(err.to_string().contains("nothing to speak"), "{}", err);
+        let err = engine.synthesize_chunk("😀🎉", "en", &stub::style(), 2, SpeakingRate::Speed(1.0)).unwrap_err();
+        assert!(err.is::<EmptyAfterNormalization>(), "{}", err);
+
+        // An emoji-only paragraph contributes nothing, and the chunks around it join as usual
+        let (wav, duration, timings) = call(&mut engine, "First paragraph.\n\n😀🎉\n\nLast paragraph.").unwrap();
+        let (expected_wav, expected_duration, _) = call(&mut engine, "First paragraph.\n\nLast paragraph.").unwrap();
+        let texts: Vec<&str> = timings.iter().map(|timing| timing.text.as_str()).collect();
+        assert_eq!(texts, ["First paragraph.", "Last paragraph."]);
+        assert_eq!((wav, duration), (expected_wav, expected_duration));
+    }
+
+    /// `StubModels` whose vocoder returns only the first half of its output
+    struct ShortVocoderModels(stub::StubModels);
+
+    impl TtsModels for ShortVocoderModels {
+        fn predict_duration(
+            &mut self,
+            text_ids: &Array2<i64>,
+            style_dp: &Array3<f32>,
+            text_mask: &Array3<f32>,
+        ) -> Result<Vec<f32>> {
+            self.0.predict_duration(text_ids, style_dp, text_mask)
+        }
+
+        fn encode_text(
+            &mut self,
+            text_ids: &Array2<i64>,
+            style_ttl: &Array3<f32>,
+            text_mask: &Array3<f32>,
+        ) -> Result<Array3<f32>> {
+            self.0.encode_text(text_ids, style_ttl, text_mask)
+        }
+
+        fn denoise(&mut self, xt: &mut Array3<f32>, inputs: &DenoiseInputs<'_>) -> Result<()> {
+            self.0.denoise(xt, inputs)
+        }
+
+        fn vocode(&mut self, xt: &Array3<f32>) -> Result<Vec<f32>> {
+            let mut wav = self.0.vocode(xt)?;
+            wav.truncate(wav.len() / 2);
+            Ok(wav)
+        }
+
+        fn check_style(&self, style: &Style) -> Result<()> {
+            self.0.check_style(style)
+        }
+    }
+
+    #[test]
+    fn short_vocoder_output_reports_its_real_duration() {
+        let mut wav = vec![0.5; 300];
+        assert_eq!(trim_to_duration(&mut wav, 0.2, 1000), 0.2);
+        assert_eq!(wav.len(), 200);
+        assert_eq!(trim_to_duration(&mut wav, 0.5, 1000), 0.2);
+        assert_eq!(wav.len(), 200);
+
+        let rate = SpeakingRate::Speed(1.0);
+        let (_, predicted) = stub::engine().synthesize_chunk("Hello there.", "en", &stub::style(), 2, rate).unwrap();
+        let mut engine = stub::engine_on(Box::new(ShortVocoderModels(stub::StubModels::default())));
+        let (wav, duration) = engine.synthesize_chunk("Hello there.", "en", &stub::style(), 2, rate).unwrap();
+
+        assert!(duration < predicted, "{} vs {}", duration, predicted);
+        assert_eq!(duration, wav.len() as f32 / stub::SAMPLE_RATE as f32);
+        let silence = ChunkSilence::uniform(0.3);
+        let (wav, duration) = engine.call("Hello there.", "en", &stub::style(), 2, 1.0, silence).unwrap();
+        assert_eq!(duration, wav.len() as f32 / stub::SAMPLE_RATE as f32);
+    }
 }
@@ -11,7 +11,35 @@ interface SynthesizeRequest {
   language: string;
   voice_style: string;
   total_step: number;
+  quality?: "Fast" | "Balanced" | "High";
   speed: number;
+  speaking_rate_wpm?: number;
+  silence_duration?: number;
+  paragraph_silence?: number;
+  bit_depth?: 8 | 16 | 24;
+  channels?: 1 | 2;
+  highpass_hz?: number;
+  remove_dc?: boolean;
+  gain_db?: number;
+  prevent_clipping?: boolean;
+  collect_metrics?: boolean;
+  max_chars?: number;
+  trim_silence?: TrimConfig;
+}
+
+// Omitted fields take the conservative defaults: -60 dB, 100 ms, 50 ms
+interface TrimConfig {
+  threshold_db?: number;
+  min_silence_ms?: number;
+  keep_padding_ms?: number;
+}
+
+interface SynthesisMetrics {
+  dp_ms: number;
+  text_enc_ms: number;
+  denoise_ms: number;
+  vocoder_ms: number;
+  chunks: number;
 }
 
 interface SynthesizeResponse {
@@ -19,6 +47,22 @@ interface SynthesizeResponse {
   message: string;
   audio_base64?: string;
   duration?: number;
+  sample_rate?: number;
+  oov_chars?: string[];
+  clipped_samples?: number;
+  metrics?: SynthesisMetrics | null;
+  truncated?: boolean;
+  failed_chunks?: { index: number; text: string; error: string }[];
+}
+
+// Structured error returned by every backend command
+interface TtsError {
+  kind: string;
+  message: string;
+}
+
+function errorMessage(error: unknown): string {
+  return (error as TtsError)?.message ?? String(error);
 }
 
 interface ModelStatus {
@@ -27,6 +71,16 @@ interface ModelStatus {
   missing_files: string[];
   total_files: number;
   downloaded_files: number;
+  file_sizes: [string, number][];
+  downloaded_bytes: number;
+  total_expected_bytes: number | null;
+}
+
+interface DownloadSummary {
+  total_files: number;
+  total_bytes: number | null;
+  already_downloaded_bytes: number | null;
+  remaining_bytes: number | null;
 }
 
 // Configure the base URL for model downloads
@@ -114,7 +168,7 @@ function App() {
         setAvailableLanguages(languages);
       }
     } catch (error) {
-      setTtsStatus(`Error initializing TTS: ${error}`);
+      setTtsStatus(`Error initializing TTS: ${errorMessage(error)}`);
     }
   }
 
@@ -186,7 +240,7 @@ function App() {
 
     } catch (error) {
       console.error("Download error:", error);
-      setDownloadError(`Download failed: ${error}`);
+      setDownloadError(`Download failed: ${errorMessage(error)}`);
     } finally {
       setDownloading(false);
     }
@@ -215,7 +269,7 @@ function App() {
     } catch (error) {
       setSynthesizeResult({
         success: false,
-        message: `Error: ${error}`,
+        message: `Error: ${errorMessage(error)}`,
       });
     } finally {
       setSynthesizing(false);
@@ -435,6 +489,12 @@ function App() {
             <Show when={result().duration}>
               <p><strong>Duration:</strong> {result().duration?.toFixed(2)} seconds</p>
             </Show>
+            <Show when={result().oov_chars?.length}>
+              <p><strong>Unsupported characters:</strong> {result().oov_chars!.join(" ")}</p>
+            </Show>
+            <Show when={result().clipped_samples}>
+              <p><strong>Clipped samples:</strong> {result().clipped_samples}</p>
+            </Show>
             <Show when={audioUrl()}>
               <div>
                 <p><strong>Audio:</strong></p>
@@ -173,7 +173,7 @@ export function usePlaybackQueue(options: UsePlaybackQueueOptions): UsePlaybackQ
       console.error(`Failed to generate sentence ${index}:`, error);
       setState("sentences", index, produce((s) => {
         s.status = 'error';
-        s.error = String(error);
+        s.error = (error as { message?: string })?.message ?? String(error);
       }));
       generatingSet.delete(index);
       return false;